@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::filesystem::{lowercase, FileSystem, FilterState};
+
+// Representative queries, add to this when new query syntax is supported so its cost can be compared
+const QUERIES: [(&str, &str); 5] = [
+    ("single character", "a"),
+    ("short term", "doc"),
+    ("common extension", ".dll"),
+    ("long term", "microsoft.windows"),
+    ("no matches", "zzzzzzzzzzzzzzzz"),
+];
+
+// How many times each query is repeated, the fastest run is reported
+const RUNS: usize = 5;
+
+pub fn run(filesystem: &mut FileSystem) {
+    println!("{} files", filesystem.filenames.len());
+
+    // Unicode lowercasing is paid for every filename when loading and in the query
+    for (name, unicode_case) in [("ASCII", false), ("Unicode", true)] {
+        let mut fastest = Duration::MAX;
+
+        for _ in 0..RUNS {
+            let start = Instant::now();
+
+            let lowercase_size: usize = filesystem
+                .filenames
+                .par_iter()
+                .map(|filename| lowercase(filename, unicode_case).len())
+                .sum();
+
+            fastest = fastest.min(start.elapsed());
+
+            std::hint::black_box(lowercase_size);
+        }
+
+        println!("{name} case, lowercasing filenames took {fastest:?}");
+
+        filesystem.set_unicode_case(unicode_case);
+        run_queries(filesystem);
+    }
+}
+
+fn run_queries(filesystem: &mut FileSystem) {
+    for (name, query) in QUERIES {
+        let mut fastest = Duration::MAX;
+
+        for _ in 0..RUNS {
+            let start = Instant::now();
+
+            filesystem.search(query, &FilterState::default());
+
+            fastest = fastest.min(start.elapsed());
+        }
+
+        println!(
+            "{name:<20} {query:<20} {:>10} matches {:?}",
+            filesystem.shown.len(),
+            fastest
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+
+use log::warn;
+
+use crate::export;
+
+pub struct Args {
+    // Load the MFT, run the benchmark queries and exit without opening the GUI
+    pub bench: bool,
+    // Skip the MFT and GUI and just log every USN journal record
+    pub watch: bool,
+    // Where --watch writes the records, stdout if not given
+    pub output: Option<PathBuf>,
+    // Drive letter with a colon of the volume read without the GUI, e.g. D:
+    pub drive: String,
+    // Only index the files below this directory
+    pub root: Option<PathBuf>,
+    // Don't keep a lowercase copy of every filename, searches are slower
+    pub low_memory: bool,
+    // Load the MFT, print stats about the index as JSON and exit
+    pub stats: bool,
+    // Load the MFT, write every file to this SQLite database and exit
+    pub export_db: Option<PathBuf>,
+    // NTFS time from --since YYYY-MM-DD, files last modified before it aren't indexed. The
+    // journal adds files created or renamed later, but older files that are only modified stay
+    // missing until the index is rebuilt.
+    pub since: Option<u64>,
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        let mut args = Args {
+            bench: false,
+            watch: false,
+            output: None,
+            drive: "C:".to_string(),
+            root: None,
+            low_memory: false,
+            stats: false,
+            export_db: None,
+            since: None,
+        };
+
+        let mut iter = std::env::args().skip(1);
+
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--bench" => args.bench = true,
+                "--watch" => args.watch = true,
+                "--output" => args.output = iter.next().map(PathBuf::from),
+                "--drive" => {
+                    if let Some(drive) = iter.next() {
+                        // D, D: and D:\ all mean the same drive
+                        args.drive = format!("{}:", drive.trim_end_matches(['\\', ':']));
+                    }
+                }
+                "--root" => args.root = iter.next().map(PathBuf::from),
+                "--low-memory" => args.low_memory = true,
+                "--stats" => args.stats = true,
+                "--export-db" => args.export_db = iter.next().map(PathBuf::from),
+                "--since" => {
+                    args.since = iter.next().as_deref().and_then(export::parse_date);
+
+                    if args.since.is_none() {
+                        warn!("--since needs a date like 2024-01-01, indexing every file");
+                    }
+                }
+                _ => warn!("Unknown argument {arg}"),
+            }
+        }
+
+        args
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    path::Path,
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use eframe::egui;
+use windows::{
+    core::{w, PCWSTR},
+    Win32::{
+        Foundation::{HGLOBAL, HWND, LPARAM, LRESULT, WPARAM},
+        System::{
+            DataExchange::{
+                AddClipboardFormatListener, CloseClipboard, GetClipboardData, GetClipboardOwner,
+                OpenClipboard, RemoveClipboardFormatListener,
+            },
+            Memory::{GlobalLock, GlobalUnlock},
+            Ole::CF_UNICODETEXT,
+            Threading::{GetCurrentProcessId, GetCurrentThreadId},
+        },
+        UI::WindowsAndMessaging::{
+            CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetMessageW,
+            GetWindowThreadProcessId, PostThreadMessageW, RegisterClassW, HWND_MESSAGE, MSG,
+            WINDOW_EX_STYLE, WINDOW_STYLE, WM_CLIPBOARDUPDATE, WM_QUIT, WNDCLASSW,
+        },
+    },
+};
+
+// Copied text longer than this is never a path
+const MAX_PATH_LENGTH: usize = 32767;
+
+// Whether copied text looks like an absolute path, e.g. C:\Users\me\file.txt or "C:\Program Files"
+pub fn as_path(text: &str) -> Option<&str> {
+    let text = text.trim().trim_matches('"');
+
+    (text.len() <= MAX_PATH_LENGTH && !text.contains(['\n', '\r']) && Path::new(text).is_absolute())
+        .then_some(text)
+}
+
+// Receives the text of everything copied to the clipboard while it exists, dropping it removes
+// the listener and its window
+pub struct ClipboardListener {
+    pub rx: Receiver<String>,
+    // Of the thread running the message loop, None if it couldn't start listening
+    thread_id: Option<u32>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for ClipboardListener {
+    fn drop(&mut self) {
+        if let Some(thread_id) = self.thread_id {
+            // Ends the message loop
+            let _ = unsafe { PostThreadMessageW(thread_id, WM_QUIT, WPARAM(0), LPARAM(0)) };
+        }
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+pub fn spawn_listener(ctx: egui::Context) -> ClipboardListener {
+    let (tx, rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::channel();
+
+    let thread = thread::spawn(move || unsafe { listen(&ctx, &tx, &ready_tx) });
+
+    ClipboardListener {
+        rx,
+        // The sender is dropped without sending if listening failed
+        thread_id: ready_rx.recv().ok(),
+        thread: Some(thread),
+    }
+}
+
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+// Sends the id of the thread to `ready` once it is listening
+unsafe fn listen(ctx: &egui::Context, tx: &Sender<String>, ready: &Sender<u32>) {
+    let class_name = w!("FileSearchClipboardListener");
+
+    let class = WNDCLASSW {
+        lpfnWndProc: Some(window_proc),
+        lpszClassName: class_name,
+        ..Default::default()
+    };
+    RegisterClassW(&class);
+
+    // Clipboard notifications are sent to a window so create a hidden message-only one
+    let Ok(hwnd) = CreateWindowExW(
+        WINDOW_EX_STYLE::default(),
+        class_name,
+        PCWSTR::null(),
+        WINDOW_STYLE::default(),
+        0,
+        0,
+        0,
+        0,
+        Some(HWND_MESSAGE),
+        None,
+        None,
+        None,
+    ) else {
+        return;
+    };
+
+    if AddClipboardFormatListener(hwnd).is_err() {
+        let _ = DestroyWindow(hwnd);
+        return;
+    }
+
+    // The thread has a message queue now so the quit message can't be lost
+    let _ = ready.send(GetCurrentThreadId());
+
+    let mut message = MSG::default();
+
+    while GetMessageW(&mut message, None, 0, 0).as_bool() {
+        if message.message == WM_CLIPBOARDUPDATE {
+            if let Some(text) = read_text(hwnd) {
+                if tx.send(text).is_err() {
+                    break;
+                }
+
+                ctx.request_repaint();
+            }
+        }
+
+        DispatchMessageW(&message);
+    }
+
+    let _ = RemoveClipboardFormatListener(hwnd);
+    let _ = DestroyWindow(hwnd);
+}
+
+// Whether the clipboard was last set by this app, e.g. "Copy path", which shouldn't be searched
+unsafe fn copied_by_self() -> bool {
+    let Ok(owner) = GetClipboardOwner() else {
+        return false;
+    };
+
+    let mut process_id = 0;
+    GetWindowThreadProcessId(owner, Some(&mut process_id));
+
+    process_id == GetCurrentProcessId()
+}
+
+unsafe fn read_text(hwnd: HWND) -> Option<String> {
+    if copied_by_self() {
+        return None;
+    }
+
+    OpenClipboard(Some(hwnd)).ok()?;
+
+    let text = GetClipboardData(u32::from(CF_UNICODETEXT.0))
+        .ok()
+        .and_then(|handle| {
+            let global = HGLOBAL(handle.0);
+            let pointer = GlobalLock(global) as *const u16;
+
+            if pointer.is_null() {
+                return None;
+            }
+
+            let mut length = 0;
+            while *pointer.add(length) != 0 {
+                length += 1;
+            }
+
+            let text = String::from_utf16_lossy(std::slice::from_raw_parts(pointer, length));
+
+            let _ = GlobalUnlock(global);
+
+            Some(text)
+        });
+
+    let _ = CloseClipboard();
+
+    text
+}
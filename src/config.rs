@@ -0,0 +1,311 @@
+use std::path::PathBuf;
+
+use eframe::egui::KeyboardShortcut;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use windows::Win32::Storage::FileSystem::{
+    FILE_ATTRIBUTE_ARCHIVE, FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_HIDDEN,
+    FILE_ATTRIBUTE_READONLY, FILE_ATTRIBUTE_REPARSE_POINT, FILE_ATTRIBUTE_SYSTEM,
+};
+
+use crate::{
+    filesystem::{DateKind, EmptyFilter, FileOrder, SortDirection},
+    hotkeys::{self, Action},
+};
+
+// How many apps are remembered per extension for "Open with"
+const MAX_RECENT_APPS: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Terminal {
+    WindowsTerminal,
+    PowerShell,
+    Cmd,
+}
+
+// Attributes that results can be filtered by, in the same order as `Config::attribute_filters`
+pub const FILTER_ATTRIBUTES: [(u32, &str); 6] = [
+    (FILE_ATTRIBUTE_HIDDEN.0, "Hidden"),
+    (FILE_ATTRIBUTE_SYSTEM.0, "System"),
+    (FILE_ATTRIBUTE_READONLY.0, "Read-only"),
+    (FILE_ATTRIBUTE_ARCHIVE.0, "Archive"),
+    (FILE_ATTRIBUTE_DIRECTORY.0, "Directory"),
+    (FILE_ATTRIBUTE_REPARSE_POINT.0, "Reparse point"),
+];
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum AttributeFilter {
+    #[default]
+    Any,
+    Set,
+    Unset,
+}
+
+impl AttributeFilter {
+    // Order the filter checkboxes cycle through when clicked
+    pub fn next(self) -> Self {
+        match self {
+            AttributeFilter::Any => AttributeFilter::Set,
+            AttributeFilter::Set => AttributeFilter::Unset,
+            AttributeFilter::Unset => AttributeFilter::Any,
+        }
+    }
+}
+
+// Columns of the results table, in the order they are shown
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ResultColumn {
+    Name,
+    Size,
+    SizeOnDisk,
+    Date,
+    Score,
+    Owner,
+    Type,
+    Path,
+}
+
+impl ResultColumn {
+    pub const ALL: [ResultColumn; 8] = [
+        ResultColumn::Name,
+        ResultColumn::Size,
+        ResultColumn::SizeOnDisk,
+        ResultColumn::Date,
+        ResultColumn::Score,
+        ResultColumn::Owner,
+        ResultColumn::Type,
+        ResultColumn::Path,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ResultColumn::Name => "Name",
+            ResultColumn::Size => "File Size",
+            ResultColumn::SizeOnDisk => "Size on disk",
+            ResultColumn::Date => "Date",
+            ResultColumn::Score => "Score",
+            ResultColumn::Owner => "Owner",
+            ResultColumn::Type => "Type",
+            ResultColumn::Path => "Path",
+        }
+    }
+
+    // What clicking the header sorts by, None if the column can't be sorted
+    pub fn order(self) -> Option<FileOrder> {
+        match self {
+            ResultColumn::Name => Some(FileOrder::Name),
+            ResultColumn::Size => Some(FileOrder::Size),
+            ResultColumn::Date => Some(FileOrder::Date),
+            ResultColumn::Score => Some(FileOrder::Score),
+            ResultColumn::Path => Some(FileOrder::Path),
+            ResultColumn::SizeOnDisk | ResultColumn::Owner | ResultColumn::Type => None,
+        }
+    }
+}
+
+// Named columns to show with the sort that goes with them, e.g. sizes for finding what uses space
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ColumnPreset {
+    pub name: String,
+    pub hidden_columns: FxHashSet<ResultColumn>,
+    pub date_kind: DateKind,
+    pub order: FileOrder,
+    pub direction: SortDirection,
+}
+
+impl ColumnPreset {
+    fn showing(
+        name: &str,
+        visible: &[ResultColumn],
+        date_kind: DateKind,
+        order: FileOrder,
+    ) -> Self {
+        ColumnPreset {
+            name: name.to_string(),
+            hidden_columns: ResultColumn::ALL
+                .into_iter()
+                .filter(|column| !visible.contains(column))
+                .collect(),
+            date_kind,
+            order,
+            direction: SortDirection::Descending,
+        }
+    }
+}
+
+pub fn default_column_presets() -> Vec<ColumnPreset> {
+    vec![
+        ColumnPreset::showing(
+            "Space analysis",
+            &[
+                ResultColumn::Name,
+                ResultColumn::Size,
+                ResultColumn::SizeOnDisk,
+                ResultColumn::Path,
+            ],
+            DateKind::Modified,
+            FileOrder::Size,
+        ),
+        ColumnPreset::showing(
+            "Recent",
+            &[ResultColumn::Name, ResultColumn::Date, ResultColumn::Path],
+            DateKind::Modified,
+            FileOrder::Date,
+        ),
+    ]
+}
+
+impl Terminal {
+    pub const ALL: [Terminal; 3] = [
+        Terminal::WindowsTerminal,
+        Terminal::PowerShell,
+        Terminal::Cmd,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Terminal::WindowsTerminal => "Windows Terminal",
+            Terminal::PowerShell => "PowerShell",
+            Terminal::Cmd => "Command Prompt",
+        }
+    }
+}
+
+// Settings that are saved between runs by eframe
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Key: lowercase extension, most recently used app first
+    pub recent_apps: FxHashMap<String, Vec<PathBuf>>,
+    // Render the Name and Path columns in a monospace font
+    pub monospace_results: bool,
+    // Shell icons can differ between light and dark mode so re-fetch them when it changes
+    pub refresh_icons_on_theme_change: bool,
+    // Used by "Open terminal here"
+    pub terminal: Terminal,
+    // Command "Open containing folder" runs with {path} and {dir} replaced, Explorer if empty
+    pub reveal_command: String,
+    // How often the journal is read
+    pub journal_poll_interval_ms: u64,
+    // Search for paths copied to the clipboard by other apps
+    pub monitor_clipboard: bool,
+    // Show the number of files directly in a folder instead of its size
+    pub show_child_counts: bool,
+    // Show the total size of everything in a folder, counted when the folder is shown
+    pub recursive_folder_sizes: bool,
+    // Keep the 8.3 names (PROGRA~1) of files for the short: filter, they use more memory
+    pub short_names: bool,
+    // Hold changed files back from the results so rows don't move while reading them
+    pub stable_results: bool,
+    // Check whether the files of visible rows still exist and mark the ones that don't
+    pub check_missing_files: bool,
+    // Leave pagefile.sys, hiberfil.sys and swapfile.sys out of size totals
+    pub exclude_system_files: bool,
+    // Only show files with (or without) each of `FILTER_ATTRIBUTES`
+    pub attribute_filters: [AttributeFilter; FILTER_ATTRIBUTES.len()],
+    pub empty_filter: EmptyFilter,
+    // Checked in order so the first binding of a shortcut wins
+    pub hotkeys: Vec<(KeyboardShortcut, Action)>,
+    // Key: drive letter, e.g. C:, the FRNs of the files on it pinned to the top of the results
+    pub pinned_by_drive: FxHashMap<String, FxHashSet<u64>>,
+    // The Name column can't be hidden. Reading owners touches the disk so the Owner column is
+    // hidden by default.
+    pub hidden_columns: FxHashSet<ResultColumn>,
+    // Which timestamp the Date column shows
+    pub date_kind: DateKind,
+    // Which date the quick Today, 7 days and 30 days filters look at
+    pub recent_date_kind: DateKind,
+    pub column_presets: Vec<ColumnPreset>,
+    // Sort by name ignoring case, else uppercase names come before lowercase ones
+    pub case_insensitive_sort: bool,
+    // Case-insensitive for all of Unicode rather than only ASCII, off as it is slower
+    pub unicode_case: bool,
+    // Searches only match text with the same case
+    pub match_case: bool,
+    // Threads searching and sorting use, 0 for every core
+    pub search_threads: usize,
+    pub show_preview: bool,
+    // Drive letter of the indexed volume, e.g. C:
+    pub drive: String,
+    // Folder that "Relative path" copies are relative to
+    pub relative_base: Option<PathBuf>,
+    // Show paths starting with the drive's label, e.g. "Windows (C:)", instead of only its letter
+    pub show_volume_label: bool,
+    // Show only the immediate parent folder in the Path column, the full path is still shown on
+    // hover
+    pub show_parent_only: bool,
+    // Indent results by their folder depth
+    pub tree_view: bool,
+    // Enter in the search box opens the only result, or the selected (else top) one, like a launcher
+    pub enter_opens_result: bool,
+    // Keep the window above other apps
+    pub always_on_top: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            recent_apps: FxHashMap::default(),
+            monospace_results: false,
+            refresh_icons_on_theme_change: true,
+            terminal: Terminal::WindowsTerminal,
+            reveal_command: String::new(),
+            journal_poll_interval_ms: 1000,
+            monitor_clipboard: false,
+            show_child_counts: false,
+            recursive_folder_sizes: false,
+            exclude_system_files: false,
+            stable_results: false,
+            check_missing_files: false,
+            short_names: false,
+            attribute_filters: [AttributeFilter::Any; FILTER_ATTRIBUTES.len()],
+            empty_filter: EmptyFilter::All,
+            hotkeys: hotkeys::default_hotkeys(),
+            pinned_by_drive: FxHashMap::default(),
+            hidden_columns: [ResultColumn::SizeOnDisk, ResultColumn::Owner]
+                .into_iter()
+                .collect(),
+            date_kind: DateKind::Modified,
+            recent_date_kind: DateKind::Modified,
+            column_presets: default_column_presets(),
+            case_insensitive_sort: true,
+            unicode_case: false,
+            match_case: false,
+            search_threads: 0,
+            show_preview: false,
+            drive: "C:".to_string(),
+            relative_base: None,
+            show_volume_label: false,
+            show_parent_only: false,
+            tree_view: false,
+            enter_opens_result: false,
+            always_on_top: false,
+        }
+    }
+}
+
+impl Config {
+    // Attributes a file must have and attributes it mustn't have
+    pub fn attribute_masks(&self) -> (u32, u32) {
+        let mut required = 0;
+        let mut excluded = 0;
+
+        for ((attribute, _), filter) in FILTER_ATTRIBUTES.iter().zip(self.attribute_filters) {
+            match filter {
+                AttributeFilter::Any => {}
+                AttributeFilter::Set => required |= attribute,
+                AttributeFilter::Unset => excluded |= attribute,
+            }
+        }
+
+        (required, excluded)
+    }
+
+    pub fn add_recent_app(&mut self, extension: &str, app: PathBuf) {
+        let apps = self.recent_apps.entry(extension.to_owned()).or_default();
+
+        apps.retain(|recent| recent != &app);
+        apps.insert(0, app);
+        apps.truncate(MAX_RECENT_APPS);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{path::Path, time::SystemTime};
+
+use ntfs_reader::api::{ntfs_to_unix_time, EPOCH_DIFFERENCE};
+#[cfg(feature = "sqlite")]
+use rusqlite::{params, Connection};
+
+use crate::filesystem::FileSystem;
+
+// Dates are NULL if they aren't known, e.g. for files created since the index was read, and so is
+// the path if a folder above the file isn't indexed
+#[cfg(feature = "sqlite")]
+const SCHEMA: &str = "
+    DROP TABLE IF EXISTS files;
+    CREATE TABLE files (
+        frn INTEGER PRIMARY KEY,
+        parent_frn INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        path TEXT,
+        size INTEGER NOT NULL,
+        modified INTEGER,
+        created INTEGER,
+        accessed INTEGER,
+        attributes INTEGER NOT NULL
+    );
+    CREATE INDEX files_parent_frn ON files (parent_frn);
+";
+
+pub const COLUMNS: [&str; 4] = ["Name", "Size", "Modified", "Path"];
+
+// NTFS timestamps are UTC and converting to local time would need the time zone rules
+pub fn format_date(ntfs_time: u64) -> String {
+    let date = ntfs_to_unix_time(ntfs_time);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        date.year(),
+        date.month() as u8,
+        date.day(),
+        date.hour(),
+        date.minute(),
+        date.second()
+    )
+}
+
+// The exported fields of a file, sizes are in bytes so spreadsheets can sort them
+pub fn row(filesystem: &FileSystem, position: usize) -> [String; 4] {
+    [
+        filesystem.filenames[position].to_string(),
+        filesystem.filesizes[position].to_string(),
+        filesystem.modified_dates[position]
+            .map(format_date)
+            .unwrap_or_default(),
+        filesystem
+            .path(position)
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    ]
+}
+
+// Tab-separated values with a header row, for pasting into a spreadsheet. Filenames can't
+// contain tabs or newlines so nothing has to be escaped.
+pub fn to_tsv(filesystem: &FileSystem, positions: &[usize]) -> String {
+    let mut tsv = COLUMNS.join("\t");
+
+    for &position in positions {
+        tsv.push('\n');
+        tsv.push_str(&row(filesystem, position).join("\t"));
+    }
+
+    tsv
+}
+
+// YYYY-MM-DD as the NTFS time of midnight UTC that day, None if it isn't a valid date
+pub fn parse_date(text: &str) -> Option<u64> {
+    let mut parts = text.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+
+    if !(1601..=30827).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days since 1970 from the civil calendar, see http://howardhinnant.github.io/date_algorithms.html
+    let shifted_year = if month <= 2 { year - 1 } else { year };
+    let era = shifted_year.div_euclid(400);
+    let year_of_era = shifted_year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let ticks = days * 24 * 60 * 60 * 10_000_000 + EPOCH_DIFFERENCE as i64;
+
+    u64::try_from(ticks).ok()
+}
+
+// The current time as an NTFS timestamp
+pub fn ntfs_now() -> u64 {
+    let since_unix_epoch = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+
+    EPOCH_DIFFERENCE + (since_unix_epoch.as_nanos() / 100) as u64
+}
+
+// Seconds since 1970 so SQLite's date functions can use it, e.g. datetime(modified, 'unixepoch')
+#[cfg(feature = "sqlite")]
+fn unix_seconds(ntfs_time: u64) -> i64 {
+    ntfs_to_unix_time(ntfs_time).unix_timestamp()
+}
+
+// Writes every indexed file to the files table of a SQLite database, replacing the table if it
+// exists. The path column is the folder the file is in like in the other exports. Returns the
+// number of files written.
+#[cfg(feature = "sqlite")]
+pub fn to_sqlite(filesystem: &FileSystem, path: &Path) -> rusqlite::Result<usize> {
+    let mut connection = Connection::open(path)?;
+
+    // It's a one-off snapshot so a crash halfway through can just be redone
+    connection.execute_batch("PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF;")?;
+    connection.execute_batch(SCHEMA)?;
+
+    // A single transaction, otherwise every insert is committed to disk on its own
+    let transaction = connection.transaction()?;
+
+    {
+        let mut insert = transaction.prepare(
+            "INSERT INTO files
+                 (frn, parent_frn, name, path, size, modified, created, accessed, attributes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )?;
+
+        for position in 0..filesystem.filenames.len() {
+            // FRNs are 48 bits so they fit in SQLite's signed integers
+            insert.execute(params![
+                filesystem.frn_mapping[position] as i64,
+                filesystem.parent_mapping[position] as i64,
+                &*filesystem.filenames[position],
+                filesystem
+                    .path(position)
+                    .map(|path| path.to_string_lossy().to_string()),
+                filesystem.filesizes[position] as i64,
+                filesystem.modified_dates[position].map(unix_seconds),
+                filesystem.created_dates[position].map(unix_seconds),
+                filesystem.accessed_dates[position].map(unix_seconds),
+                filesystem.attributes[position],
+            ])?;
+        }
+    }
+
+    transaction.commit()?;
+
+    Ok(filesystem.filenames.len())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ScriptOperation {
+    Copy,
+    Move,
+    Delete,
+}
+
+impl ScriptOperation {
+    pub const ALL: [ScriptOperation; 3] = [
+        ScriptOperation::Copy,
+        ScriptOperation::Move,
+        ScriptOperation::Delete,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScriptOperation::Copy => "Copy to",
+            ScriptOperation::Move => "Move to",
+            ScriptOperation::Delete => "Delete",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ScriptKind {
+    PowerShell,
+    Batch,
+}
+
+impl ScriptKind {
+    pub const ALL: [ScriptKind; 2] = [ScriptKind::PowerShell, ScriptKind::Batch];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScriptKind::PowerShell => "PowerShell (.ps1)",
+            ScriptKind::Batch => "Batch (.bat)",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ScriptKind::PowerShell => "ps1",
+            ScriptKind::Batch => "bat",
+        }
+    }
+}
+
+// Single quotes don't expand anything in PowerShell, a quote inside is written twice
+fn powershell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+// Paths can't contain double quotes but % would still expand inside them in a batch file
+fn batch_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('%', "%%"))
+}
+
+// One command per file to review before running it, the destination is unused for deletes
+pub fn to_script(
+    filesystem: &FileSystem,
+    positions: &[usize],
+    kind: ScriptKind,
+    operation: ScriptOperation,
+    destination: &str,
+) -> String {
+    let destination = destination.trim().trim_end_matches('\\');
+
+    let mut script = match kind {
+        ScriptKind::PowerShell => String::new(),
+        // So non-ASCII paths in the UTF-8 file are read correctly
+        ScriptKind::Batch => "@echo off\r\nchcp 65001 > nul\r\n".to_string(),
+    };
+
+    for &position in positions {
+        // A file whose folder isn't indexed yet can't be named in the script
+        let Some(path) = filesystem.full_path(position) else {
+            continue;
+        };
+        let path = path.to_string_lossy();
+        let filename = &*filesystem.filenames[position];
+
+        let is_directory = filesystem.is_directory(position);
+
+        let line = match kind {
+            ScriptKind::PowerShell => {
+                let path = powershell_quote(&path);
+                let destination = powershell_quote(destination);
+
+                match operation {
+                    ScriptOperation::Copy => {
+                        format!("Copy-Item -LiteralPath {path} -Destination {destination} -Recurse")
+                    }
+                    ScriptOperation::Move => {
+                        format!("Move-Item -LiteralPath {path} -Destination {destination}")
+                    }
+                    ScriptOperation::Delete => format!("Remove-Item -LiteralPath {path} -Recurse"),
+                }
+            }
+            ScriptKind::Batch => {
+                let path = batch_quote(&path);
+
+                match (operation, is_directory) {
+                    // copy only copies what is in a folder so xcopy recreates the folder itself
+                    (ScriptOperation::Copy, true) => format!(
+                        "xcopy {path} {} /E /I /H /K",
+                        batch_quote(&format!("{destination}\\{filename}\\"))
+                    ),
+                    (ScriptOperation::Copy, false) => {
+                        format!("copy {path} {}", batch_quote(&format!("{destination}\\")))
+                    }
+                    (ScriptOperation::Move, _) => {
+                        format!("move {path} {}", batch_quote(&format!("{destination}\\")))
+                    }
+                    (ScriptOperation::Delete, true) => format!("rmdir /S /Q {path}"),
+                    (ScriptOperation::Delete, false) => format!("del {path}"),
+                }
+            }
+        };
+
+        script.push_str(&line);
+        script.push_str("\r\n");
+    }
+
+    script
+}
+
+// Windows PowerShell reads scripts without a byte order mark as the ANSI code page
+pub fn write_script(path: &Path, kind: ScriptKind, script: &str) -> std::io::Result<()> {
+    let mut bytes = Vec::with_capacity(script.len() + 3);
+
+    if kind == ScriptKind::PowerShell {
+        bytes.extend_from_slice(b"\xEF\xBB\xBF");
+    }
+
+    bytes.extend_from_slice(script.as_bytes());
+
+    std::fs::write(path, bytes)
+}
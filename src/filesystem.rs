@@ -1,12 +1,97 @@
-use std::path::{Path, PathBuf};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    ops::RangeInclusive,
+    os::windows::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 
-use ntfs_reader::journal::FileId;
+use log::{debug, warn};
+use ntfs_reader::{
+    api::{NtfsAttributeType, NtfsFileName, NtfsFileNamespace},
+    file::NtfsFile,
+    journal::FileId,
+    mft::Mft,
+};
 use rayon::{
-    prelude::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator},
+    prelude::{
+        IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+    },
     slice::ParallelSliceMut,
+    ThreadPool, ThreadPoolBuilder,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::MAX_PATH,
+        Storage::FileSystem::{GetDiskFreeSpaceW, GetVolumeInformationW, FILE_ATTRIBUTE_DIRECTORY},
+    },
+};
+
+use crate::{
+    owner::{self, OwnerCache},
+    query::{self, Glob, Wildcard},
 };
 
-fn file_id_to_frn(file_id: FileId) -> u64 {
+// The name given to the drive, e.g. "Windows", None if it doesn't have one
+unsafe fn volume_label(volume_path: &Path) -> Option<String> {
+    let root: Vec<u16> = volume_path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+
+    let mut label = [0u16; MAX_PATH as usize + 1];
+
+    GetVolumeInformationW(
+        PCWSTR::from_raw(root.as_ptr()),
+        Some(&mut label),
+        None,
+        None,
+        None,
+        None,
+    )
+    .ok()?;
+
+    let length = label.iter().position(|&c| c == 0).unwrap_or(label.len());
+
+    Some(String::from_utf16_lossy(&label[..length])).filter(|label| !label.is_empty())
+}
+
+// Bytes per cluster of the drive, e.g. 4096, None if it couldn't be read
+unsafe fn cluster_size(volume_path: &Path) -> Option<u64> {
+    let root: Vec<u16> = volume_path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+
+    let mut sectors_per_cluster = 0;
+    let mut bytes_per_sector = 0;
+
+    GetDiskFreeSpaceW(
+        PCWSTR::from_raw(root.as_ptr()),
+        Some(&mut sectors_per_cluster),
+        Some(&mut bytes_per_sector),
+        None,
+        None,
+    )
+    .ok()?;
+
+    Some(u64::from(sectors_per_cluster) * u64::from(bytes_per_sector)).filter(|&size| size > 0)
+}
+
+// The journal reader gives a full path, or only the name when it can't find the parent's path,
+// e.g. when the parent has been deleted since
+fn record_name(path: &Path) -> Option<Cow<'_, str>> {
+    path.file_name().map(|name| name.to_string_lossy())
+}
+
+pub fn file_id_to_frn(file_id: FileId) -> u64 {
     match file_id {
         FileId::Normal(file_id) => file_id & 0x0000_FFFF_FFFF_FFFF,
         FileId::Extended(file_id_128) => {
@@ -19,234 +104,2351 @@ fn file_id_to_frn(file_id: FileId) -> u64 {
     }
 }
 
-#[derive(PartialEq)]
+// Filenames that aren't valid UTF-16 (e.g. an unpaired surrogate) have already been lossily
+// converted with the invalid sequence replaced by U+FFFD. Both names are derived from that one
+// string so the displayed and lowercase names can't disagree.
+fn filename_pair(filename: &str, unicode_case: bool) -> (Box<str>, Box<str>) {
+    (filename.into(), lowercase(filename, unicode_case).into())
+}
+
+// ASCII only lowercasing is faster but leaves e.g. Cyrillic and Greek uppercase, names and queries
+// have to be lowercased the same way to match
+pub fn lowercase(text: &str, unicode_case: bool) -> String {
+    if unicode_case {
+        text.to_lowercase()
+    } else {
+        text.to_ascii_lowercase()
+    }
+}
+
+// Number of files searched per frame so a broad search on a huge drive doesn't freeze the UI
+const SEARCH_CHUNK_SIZE: usize = 1 << 20;
+
+// Inode #5 is the NTFS root directory
+pub const ROOT_FRN: u64 = 5;
+
+// Huge files Windows keeps in the drive root for memory, left out of sizes if excluded
+const SYSTEM_FILES: [&str; 4] = [
+    "pagefile.sys",
+    "hiberfil.sys",
+    "swapfile.sys",
+    "dumpstack.log.tmp",
+];
+
+// Windows paths are limited to 32,767 characters so any chain deeper than this must be a cycle
+const MAX_PATH_DEPTH: usize = 16384;
+
+// Shown instead of the path of a file whose folder isn't in the index (yet), < can't be in a
+// filename so it can't be mistaken for a folder. Never used as a path.
+pub const UNKNOWN_FOLDER: &str = "<folder not indexed>";
+
+// How many deleted files are remembered
+const MAX_TOMBSTONES: usize = 1000;
+
+// Limits on the files created in folders that aren't indexed when a subtree is indexed. Most are
+// outside of it and never placed, so old ones are dropped once there are too many.
+const MAX_UNPLACED_FOLDERS: usize = 1024;
+const MAX_UNPLACED_PER_FOLDER: usize = 4096;
+
+// Folders that files were created in before the folder itself, which may never be created, e.g.
+// when it was filtered out by --since
+const MAX_MISSING_PARENTS: usize = 4096;
+
+// How long files created before their folder wait for it once the limits above are reached
+const PARENT_WAIT: Duration = Duration::from_secs(60);
+
+// How the MFT is read into the index
+#[derive(Clone, Copy)]
+pub struct IndexOptions {
+    pub low_memory: bool,
+    pub unicode_case: bool,
+    // NTFS time, files last modified before it aren't indexed but directories always are
+    pub modified_since: Option<u64>,
+    // Keep the 8.3 names of files that have one for the short: filter
+    pub short_names: bool,
+}
+
+// Numbers about the index, shown in the status bar and printed by --stats
+#[derive(Serialize)]
+pub struct IndexStats {
+    pub files: usize,
+    // Reading the MFT and building the index from it
+    pub scan_ms: u64,
+    // Rough size of the index in memory
+    pub memory_bytes: usize,
+}
+
+// A file deleted while the app was running
+pub struct Tombstone {
+    pub filename: Box<str>,
+    // Path of the directory the file was in, None if it wasn't indexed
+    pub path: Option<PathBuf>,
+    pub size: u64,
+    pub deleted: SystemTime,
+}
+
+// A folder size count that ran out of time, carried on when the folder is counted again
+struct FolderSizeCount {
+    frn: u64,
+    // Folders still to be entered, and folders whose subfolders have all been counted
+    stack: Vec<(u64, bool)>,
+    // A corrupt parent chain could loop forever
+    entered: FxHashSet<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChangeKind {
+    Created,
+    Deleted,
+    Renamed,
+    Updated,
+}
+
+// A journal record that was applied to the index, passed to the listeners added with `subscribe`
+pub struct ChangeEvent {
+    pub kind: ChangeKind,
+    pub frn: u64,
+    // Full path, the new one for renames and the last one for deletes. None if a folder above the
+    // file isn't indexed yet.
+    pub path: Option<PathBuf>,
+}
+
+// Called from whichever thread applies the journal records
+type ChangeListener = Box<dyn Fn(&ChangeEvent) + Send>;
+
+// What a query is compared against
+enum Matcher {
+    Filename,
+    // A query with a \ is a path fragment which can never be in a filename
+    PathSubstring,
+    PathFuzzy,
+    PathGlob(Glob),
+    Wildcard(Wildcard),
+}
+
+// What a search looks for
+struct SearchTerm {
+    // Lowercase
+    query: String,
+    matcher: Matcher,
+    // The query as typed and its matcher, when its case has to match too
+    exact: Option<(String, Matcher)>,
+}
+
+// How the shown files were picked, so changed files can be checked against it
+enum ShownBy {
+    All,
+    Search(SearchTerm),
+    Children(u64),
+}
+
+pub struct PendingSearch {
+    term: SearchTerm,
+    // Position of the next file to search
+    next: usize,
+    start: Instant,
+}
+
+// Fuzzy score bonuses, the characters of a filename are all lowercase so word boundaries are
+// only detected after separators
+const MATCH_SCORE: u32 = 1;
+const START_BONUS: u32 = 8;
+const WORD_BOUNDARY_BONUS: u32 = 6;
+const CONTIGUOUS_BONUS: u32 = 4;
+// How much more a character matched in the filename is worth than one in the folders
+const FILENAME_WEIGHT: u32 = 3;
+
+// Scores how well the query's characters appear in order in the filename, rewarding matches at
+// the start of the filename, at the start of words and runs of consecutive matches
+fn fuzzy_score(filename: &str, query: &str) -> Option<u32> {
+    let mut query_chars = query.chars().peekable();
+
+    let mut score = 0;
+    let mut previous = None;
+    let mut previous_matched = false;
+
+    for (i, c) in filename.chars().enumerate() {
+        let Some(&query_char) = query_chars.peek() else {
+            break;
+        };
+
+        if c == query_char {
+            score += MATCH_SCORE;
+
+            if i == 0 {
+                score += START_BONUS;
+            } else if matches!(previous, Some(' ' | '_' | '-' | '.')) {
+                score += WORD_BOUNDARY_BONUS;
+            }
+
+            if previous_matched {
+                score += CONTIGUOUS_BONUS;
+            }
+
+            previous_matched = true;
+            query_chars.next();
+        } else {
+            previous_matched = false;
+        }
+
+        previous = Some(c);
+    }
+
+    query_chars.peek().is_none().then_some(score)
+}
+
+// Like `fuzzy_score` for a whole path. The query is matched from the end so its characters are
+// found in the filename where they can be, and those are worth FILENAME_WEIGHT times more, so
+// "mainrs" ranks src\main.rs above main\src\lib.rs.
+fn fuzzy_path_score(path: &str, query: &str) -> Option<u32> {
+    let chars: Vec<char> = path.chars().collect();
+    let filename_start = chars.iter().rposition(|&c| c == '\\').map_or(0, |i| i + 1);
+
+    let mut query_chars = query.chars().rev().peekable();
+    let mut matched = Vec::new();
+
+    for (i, &c) in chars.iter().enumerate().rev() {
+        let Some(&query_char) = query_chars.peek() else {
+            break;
+        };
+
+        if c == query_char {
+            matched.push(i);
+            query_chars.next();
+        }
+    }
+
+    if query_chars.peek().is_some() {
+        return None;
+    }
+
+    let mut score = 0;
+    let mut previous = None;
+
+    for &i in matched.iter().rev() {
+        let mut char_score = MATCH_SCORE;
+
+        if i == filename_start {
+            char_score += START_BONUS;
+        } else if i > 0 && matches!(chars[i - 1], '\\' | ' ' | '_' | '-' | '.') {
+            char_score += WORD_BOUNDARY_BONUS;
+        }
+
+        if i > 0 && previous == Some(i - 1) {
+            char_score += CONTIGUOUS_BONUS;
+        }
+
+        if i >= filename_start {
+            char_score *= FILENAME_WEIGHT;
+        }
+
+        score += char_score;
+        previous = Some(i);
+    }
+
+    Some(score)
+}
+
+// NTFS 3.0 added the owner and security ids after the fields ntfs_reader knows about
+fn security_id_of(standard_information: &[u8]) -> u32 {
+    standard_information
+        .get(0x34..0x38)
+        .map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+// The long name is preferred, POSIX names are long too but case sensitive, and the DOS 8.3 name
+// (PROGRA~1) is only used when there's nothing else
+fn namespace_rank(namespace: u8) -> u8 {
+    match namespace {
+        n if n == NtfsFileNamespace::Win32 as u8 || n == NtfsFileNamespace::Win32AndDos as u8 => 2,
+        n if n == NtfsFileNamespace::Posix as u8 => 1,
+        _ => 0,
+    }
+}
+
+// Records that an attribute list says hold FILE_NAME attributes
+fn file_name_records(attribute_list: &[u8]) -> Vec<u64> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+
+    // Each entry is the type (u32), the entry length (u16), … and the record at 0x10
+    while let Some(entry) = attribute_list.get(offset..offset + 0x18) {
+        let type_id = u32::from_le_bytes(entry[0..4].try_into().unwrap());
+        let length = u16::from_le_bytes(entry[4..6].try_into().unwrap()) as usize;
+        let reference = u64::from_le_bytes(entry[0x10..0x18].try_into().unwrap());
+
+        if length == 0 {
+            break;
+        }
+
+        if type_id == NtfsAttributeType::FileName as u32 {
+            records.push(reference & 0x0000_FFFF_FFFF_FFFF);
+        }
+
+        offset += length;
+    }
+
+    records.sort_unstable();
+    records.dedup();
+    records
+}
+
+// `NtfsFile::get_best_file_name` stops at the first name in an extension record, which can be the
+// DOS name, so every FILE_NAME is ranked here instead. Also returns the separate 8.3 name if the
+// file has one, files whose name already fits in 8.3 don't. `record` reads another MFT record.
+//
+// Only a resident attribute list is read. One with so many entries that it is stored outside the
+// record (a file with thousands of hard links or fragments) is skipped, leaving the names in the
+// base record.
+fn best_file_name<'a>(
+    file: &NtfsFile,
+    record: impl Fn(u64) -> Option<NtfsFile<'a>>,
+) -> Option<(NtfsFileName, Option<NtfsFileName>)> {
+    let mut best: Option<NtfsFileName> = None;
+    let mut short: Option<NtfsFileName> = None;
+    let mut extension_records = Vec::new();
+
+    let mut consider = |name: &NtfsFileName| {
+        // Junctions
+        if name.is_reparse_point() {
+            return;
+        }
+
+        if name.header.namespace == NtfsFileNamespace::Dos as u8 {
+            short = Some(*name);
+        }
+
+        if best.is_none_or(|best| {
+            namespace_rank(name.header.namespace) > namespace_rank(best.header.namespace)
+        }) {
+            best = Some(*name);
+        }
+    };
+
+    file.attributes(|att| {
+        if att.header.type_id == NtfsAttributeType::FileName as u32 {
+            consider(att.as_name());
+        } else if att.header.type_id == NtfsAttributeType::AttributeList as u32
+            && att.header.is_non_resident == 0
+        {
+            extension_records = file_name_records(att.get_resident());
+        }
+    });
+
+    for number in extension_records {
+        // The names in the base record have already been seen
+        if number == file.number() {
+            continue;
+        }
+
+        if let Some(record) = record(number) {
+            record.attributes(|att| {
+                if att.header.type_id == NtfsAttributeType::FileName as u32 {
+                    consider(att.as_name());
+                }
+            });
+        }
+    }
+
+    best.map(|best| (best, short))
+}
+
+// Whether a query is searched for in full paths rather than filenames
+pub fn is_path_query(query: &str) -> bool {
+    query.contains('\\')
+}
+
+// Whether every file matching `search` also matches `previous`, so only the files shown for
+// `previous` have to be searched. Holds when the previous search is part of the new one as
+// substring and fuzzy matches both look for the query in order, edits in the middle or deletions
+// need a full search.
+pub fn narrows(search: &str, previous: &str, unicode_case: bool, match_case: bool) -> bool {
+    !previous.is_empty()
+        // Typing the first \ switches from filenames to paths
+        && is_path_query(search) == is_path_query(previous)
+        // Compared the way they are matched, "A" and "ab" are both searches for a unless the case
+        // has to match
+        && if match_case {
+            search.contains(previous)
+        } else {
+            lowercase(search, unicode_case).contains(&lowercase(previous, unicode_case))
+        }
+}
+
+fn match_score(filename: &str, query: &str, fuzzy: bool) -> Option<u32> {
+    if fuzzy {
+        fuzzy_score(filename, query)
+    } else {
+        filename.contains(query).then_some(0)
+    }
+}
+
+// Which timestamp the Date column shows and sorts by
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum DateKind {
+    #[default]
+    Modified,
+    Created,
+    Accessed,
+}
+
+impl DateKind {
+    pub const ALL: [DateKind; 3] = [DateKind::Modified, DateKind::Created, DateKind::Accessed];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DateKind::Modified => "Modified",
+            DateKind::Created => "Created",
+            DateKind::Accessed => "Accessed",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum EmptyFilter {
+    #[default]
+    All,
+    OnlyEmpty,
+    HideEmpty,
+}
+
+impl EmptyFilter {
+    pub const ALL: [EmptyFilter; 3] = [
+        EmptyFilter::All,
+        EmptyFilter::OnlyEmpty,
+        EmptyFilter::HideEmpty,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EmptyFilter::All => "All files",
+            EmptyFilter::OnlyEmpty => "Only empty files",
+            EmptyFilter::HideEmpty => "Hide empty files",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum SortDirection {
     Ascending,
     Descending,
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum FileOrder {
     RecordNumber,
     Name,
-    ModifedDate,
+    Date,
     Size,
+    Score,
+    Path,
+}
+
+// Everything besides the search text that decides which files are shown, passed to every search
+// and checked in `passes_filters` so a new filter only has to be added here. The size, owner and
+// short name filters are taken from the query by the search.
+#[derive(Clone, Default)]
+pub struct FilterState {
+    // Attributes shown files must have and mustn't have
+    pub required_attributes: u32,
+    pub excluded_attributes: u32,
+    // Files created while running show as empty until their size is known
+    pub empty_filter: EmptyFilter,
+    // From the size: filter of the current search, directories never match
+    pub size_filter: Option<RangeInclusive<u64>>,
+    // Security ids whose owner matches the owner: filter of the current search
+    pub owner_filter: Option<FxHashSet<u32>>,
+    // Lowercase part of the 8.3 name from the short: filter of the current search
+    pub short_name_filter: Option<String>,
+    // Lowercase extensions without the dot whose files are hidden, not saved between runs
+    pub hidden_extensions: FxHashSet<Box<str>>,
+    // Which date and the NTFS time, only files with that date since then are shown. Not saved
+    // between runs.
+    pub recent_after: Option<(DateKind, u64)>,
+    // Only files at most this many folders below the root are shown, 0 for the files directly in
+    // it
+    pub max_depth: Option<usize>,
+    // The search text has to match with the same case as well
+    pub match_case: bool,
 }
 
 pub struct FileSystem {
-    // Stores the position of files in the filenames Vec with the index being the FRN
-    pub position_mapping: Vec<usize>,
+    // Stores the position of files in the filenames Vec with the index being the FRN, read with
+    // position_of as files created later can be past its end
+    position_mapping: Vec<usize>,
     // Stores the FRN of files with the index being the position in the filesnames Vec
     pub frn_mapping: Vec<u64>,
     // Stores the FRN of the parent with the index being the position in the filenames Vec
     pub parent_mapping: Vec<u64>,
     pub filesizes: Vec<u64>,
     pub modified_dates: Vec<Option<u64>>,
+    pub created_dates: Vec<Option<u64>>,
+    // Windows only updates it about once an hour by default, if at all
+    pub accessed_dates: Vec<Option<u64>>,
+    // Of the Date column
+    pub date_kind: DateKind,
+    // Sort "apple" before "Zebra" instead of by byte order
+    pub case_insensitive_sort: bool,
+    // FILE_ATTRIBUTE_* flags, e.g. hidden, system and directory
+    pub attributes: Vec<u32>,
+    // Index into $Secure, files with the same id have the same owner. 0 if unknown.
+    pub security_ids: Vec<u32>,
     pub filenames: Vec<Box<str>>,
     // Could use case insensitive regex instead but it is about 2 times slower
     // And takes about 500us to build the regex
+    // Empty in low memory mode, use `lowercase_filename` instead
     pub lowercase_filenames: Vec<Box<str>>,
+    // Filenames are lowercased while searching instead of being stored twice
+    pub low_memory: bool,
+    // Lowercase with full Unicode case mapping instead of only ASCII, see `set_unicode_case`
+    unicode_case: bool,
+    // NTFS time, files last modified before it weren't indexed
+    pub modified_since: Option<u64>,
     // Maybe use u32 instead of usize since we won't have 2 ** 64 files
     pub shown: Vec<usize>,
+    // None if the shown files were picked some other way, see `set_shown`
+    shown_by: Option<ShownBy>,
+    // Path of the indexed root, which is the drive root unless a mount point or subtree is indexed
     pub volume_path: PathBuf,
+    // Of the drive, read once when the index is built
+    pub volume_label: Option<String>,
+    pub cluster_size: Option<u64>,
+    // FRN of the directory at volume_path, paths are built by walking parents up to it
+    pub root_frn: u64,
     pub order: FileOrder,
     pub direction: SortDirection,
+    pub pending_search: Option<PendingSearch>,
+    // Match the query's characters in order instead of as a substring
+    pub fuzzy: bool,
+    // Key: position, the relevance of files matched by the last fuzzy search
+    pub scores: FxHashMap<usize, u32>,
+    // Fuzzy searches match whole paths, ranking matches in the filename higher
+    pub fuzzy_paths: bool,
+    // Match the search as a glob against whole paths instead of filenames
+    pub path_glob: bool,
+    // Whether ** in a path glob crosses directories
+    pub recursive_glob: bool,
+    // Match searches with * or ? against filenames like Windows does
+    pub wildcards: bool,
+    // Lowercase full path of every file, only built once a path search needs it
+    lowercase_paths: Option<Vec<Box<str>>>,
+    // `depth` of every file, only built once the depth filter is used
+    depths: Option<Vec<u16>>,
+    // Of the running or last search, with the filters from its query
+    filters: FilterState,
+    pub owners: OwnerCache,
+    // FRNs of files that are sorted above everything else
+    pub pinned: FxHashSet<u64>,
+    // Key: FRN of a folder that files were created in before the folder itself was, e.g. when
+    // extracting an archive, when the first one was. Their paths have an unknown ancestor until
+    // the folder is created.
+    missing_parents: FxHashMap<u64, Instant>,
+    // Key: FRN of a folder that isn't indexed, (when the first was created, (FRN, path) of the
+    // files created in it). Only used when a subtree is indexed, the files are added if the folder
+    // is created inside it later.
+    unplaced: FxHashMap<u64, (Instant, Vec<(u64, PathBuf)>)>,
+    // Most recently deleted first
+    pub tombstones: VecDeque<Tombstone>,
+    // Key: FRN of a directory, the FRNs of the files directly in it. Only built when a feature
+    // needs it as it takes a lot of memory on a big drive
+    pub children: Option<FxHashMap<u64, Vec<u64>>>,
+    // Key: FRN of a directory, the total size of everything below it. Filled in as folders are
+    // shown and removed when something below them changes.
+    folder_sizes: FxHashMap<u64, u64>,
+    folder_size_count: Option<FolderSizeCount>,
+    // Key: FRN, the lowercase 8.3 names of the files that have a separate one, if they were read
+    short_names: FxHashMap<u64, Box<str>>,
+    // Leave the page file and the like out of sizes, they are still shown
+    exclude_system_files: bool,
+    // Told about every change applied to the index
+    listeners: Vec<ChangeListener>,
+    // Searching and sorting run on this so they can be kept off some of the cores, shared with
+    // the index that replaces this one when rebuilding
+    pool: Arc<ThreadPool>,
+}
+
+// 0 uses every core
+fn build_pool(threads: usize) -> ThreadPool {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|index| format!("search-{index}"))
+        .build()
+        .expect("failed to build search thread pool")
 }
 
 impl FileSystem {
-    pub fn delete(&mut self, file_id: FileId) {
-        let file_record_number = file_id_to_frn(file_id);
+    // An index without any files, `max_record` is how many FRNs the mapping starts with
+    fn new(volume_path: PathBuf, options: IndexOptions, max_record: u64) -> Self {
+        FileSystem {
+            position_mapping: vec![usize::MAX; max_record as usize],
+            frn_mapping: Vec::new(),
+            parent_mapping: Vec::new(),
+            filesizes: Vec::new(),
+            modified_dates: Vec::new(),
+            created_dates: Vec::new(),
+            accessed_dates: Vec::new(),
+            date_kind: DateKind::Modified,
+            case_insensitive_sort: true,
+            attributes: Vec::new(),
+            security_ids: Vec::new(),
+            filenames: Vec::new(),
+            lowercase_filenames: Vec::new(),
+            low_memory: options.low_memory,
+            unicode_case: options.unicode_case,
+            modified_since: options.modified_since,
+            shown: Vec::new(),
+            shown_by: None,
+            volume_label: unsafe { volume_label(&volume_path) },
+            cluster_size: unsafe { cluster_size(&volume_path) },
+            volume_path,
+            root_frn: ROOT_FRN,
+            order: FileOrder::RecordNumber,
+            direction: SortDirection::Descending,
+            pending_search: None,
+            fuzzy: false,
+            fuzzy_paths: false,
+            scores: FxHashMap::default(),
+            path_glob: false,
+            wildcards: false,
+            recursive_glob: true,
+            lowercase_paths: None,
+            depths: None,
+            filters: FilterState::default(),
+            owners: OwnerCache::default(),
+            pinned: FxHashSet::default(),
+            missing_parents: FxHashMap::default(),
+            unplaced: FxHashMap::default(),
+            tombstones: VecDeque::new(),
+            children: None,
+            folder_sizes: FxHashMap::default(),
+            folder_size_count: None,
+            short_names: FxHashMap::default(),
+            exclude_system_files: false,
+            listeners: Vec::new(),
+            pool: Arc::new(build_pool(0)),
+        }
+    }
 
-        let filename_position = self.position_mapping[file_record_number as usize];
+    pub fn from_mft(mft: &Mft, volume_path: PathBuf, options: IndexOptions) -> Self {
+        let IndexOptions {
+            modified_since,
+            short_names,
+            ..
+        } = options;
 
-        // idk probably delted it already???
-        if filename_position == usize::MAX {
-            println!("oop");
-            return;
-        }
+        let mut filesystem = FileSystem::new(volume_path, options, mft.max_record);
 
-        if filename_position == self.filenames.len() - 1 {
-            self.filenames.pop();
-            self.lowercase_filenames.pop();
+        let mut count = 0;
 
-            self.frn_mapping.pop();
-            self.parent_mapping.pop();
+        for number in 0..mft.max_record {
+            if let Some(file) = mft.get_record(number) {
+                if file.is_used() {
+                    if let Some((filename, short_name)) =
+                        best_file_name(&file, |number| mft.get_record(number))
+                    {
+                        let mut accessed = None;
+                        let mut created = None;
+                        let mut modified = None;
+                        let mut size = 0u64;
 
-            self.position_mapping[file_record_number as usize] = usize::MAX;
-        } else {
-            self.filenames.swap_remove(filename_position);
-            self.lowercase_filenames.swap_remove(filename_position);
+                        // $STANDARD_INFORMATION doesn't have the directory attribute
+                        let mut attributes = if file.is_directory() {
+                            FILE_ATTRIBUTE_DIRECTORY.0
+                        } else {
+                            0
+                        };
 
-            // it isn't possible to have 0 files
-            let replacement_frn = self.frn_mapping.pop().unwrap();
-            self.frn_mapping[filename_position] = replacement_frn;
+                        let mut security_id = 0;
 
-            let replacement_parent_frn = self.parent_mapping.pop().unwrap();
-            self.parent_mapping[filename_position] = replacement_parent_frn;
+                        file.attributes(|att| {
+                            if att.header.type_id == NtfsAttributeType::StandardInformation as u32 {
+                                let stdinfo = att.as_standard_info();
 
-            self.position_mapping[file_record_number as usize] = usize::MAX;
-            self.position_mapping[replacement_frn as usize] = filename_position;
-        }
+                                attributes |= stdinfo.file_attributes;
+                                security_id = security_id_of(att.get_resident());
+                                accessed = Some(stdinfo.access_time);
+                                created = Some(stdinfo.creation_time);
+                                modified = Some(stdinfo.modification_time);
+                            }
 
-        if let Ok(position) = self.shown.binary_search(&filename_position) {
-            // can be very slow but we want it to still be sorted
-            self.shown.remove(position);
-        }
-    }
+                            if att.header.type_id == NtfsAttributeType::Data as u32 {
+                                if att.header.is_non_resident == 0 {
+                                    size = att.header_res.value_length as u64;
+                                } else {
+                                    size = att.header_nonres.data_size;
+                                }
+                            }
+                        });
 
-    pub fn rename(&mut self, file_id: FileId, parent_id: FileId, path: &Path) {
-        let file_record_number = file_id_to_frn(file_id);
-        let parent_record_number = file_id_to_frn(parent_id);
+                        // Directories are always kept so the paths of newer files can be built
+                        if attributes & FILE_ATTRIBUTE_DIRECTORY.0 == 0
+                            && modified_since.is_some_and(|cutoff| {
+                                modified.is_some_and(|modified| modified < cutoff)
+                            })
+                        {
+                            count += 1;
+                            continue;
+                        }
 
-        let filename_position = self.position_mapping[file_record_number as usize];
+                        let parent = filename.parent();
+                        let filename = filename.to_string();
 
-        if let Some(filename) = path.file_name() {
-            let filename = filename.to_string_lossy();
+                        filesystem.set_position(number, filesystem.filenames.len());
 
-            // should be able to remove when create/delete are implemented
-            if filename_position == usize::MAX {
-                return;
+                        filesystem.parent_mapping.push(parent);
+                        filesystem.frn_mapping.push(number);
+                        filesystem.filesizes.push(size);
+                        filesystem.modified_dates.push(modified);
+                        filesystem.created_dates.push(created);
+                        filesystem.accessed_dates.push(accessed);
+                        filesystem.attributes.push(attributes);
+                        filesystem.security_ids.push(security_id);
+
+                        filesystem.push_filename(&filename);
+
+                        if let Some(short_name) = short_name.filter(|_| short_names) {
+                            filesystem
+                                .short_names
+                                .insert(number, short_name.to_string().to_lowercase().into());
+                        }
+                    }
+                } else {
+                    count += 1;
+                }
             }
+        }
 
-            self.lowercase_filenames[filename_position] = filename.to_lowercase().into();
-            self.filenames[filename_position] = filename.into();
+        debug!("{count} of {} MFT records weren't indexed", mft.max_record);
+
+        filesystem.shown = (0..filesystem.filenames.len()).collect();
+
+        filesystem
+    }
+
+    // Carries the sort, search options and history over from the index this replaces
+    pub fn take_settings(&mut self, old: &mut FileSystem) {
+        self.order = old.order;
+        self.direction = old.direction;
+        self.exclude_system_files = old.exclude_system_files;
+        self.date_kind = old.date_kind;
+        self.case_insensitive_sort = old.case_insensitive_sort;
+        self.fuzzy = old.fuzzy;
+        self.fuzzy_paths = old.fuzzy_paths;
+        self.path_glob = old.path_glob;
+        self.wildcards = old.wildcards;
+        self.recursive_glob = old.recursive_glob;
+        self.listeners = std::mem::take(&mut old.listeners);
+        self.pool = old.pool.clone();
+        self.tombstones = std::mem::take(&mut old.tombstones);
+
+        // Security ids only mean something on the volume they came from
+        if self.volume_path.components().next() == old.volume_path.components().next() {
+            self.owners = std::mem::take(&mut old.owners);
         }
 
-        self.parent_mapping[filename_position] = parent_record_number;
+        if old.children.is_some() {
+            self.build_children();
+        }
     }
 
-    pub fn create(&mut self, file_id: FileId, parent_id: FileId, path: &Path) {
-        if let Some(filename) = path.file_name() {
-            let file_record_number = file_id_to_frn(file_id);
-            let parent_record_number = file_id_to_frn(parent_id);
+    // Keeps only the files below the directory at `root`, e.g. C:\Projects, which then becomes
+    // the root that paths are built from. Returns false if the directory isn't in the index.
+    pub fn retain_subtree(&mut self, root: &Path) -> bool {
+        let Some(root_position) = self.find_path(root) else {
+            return false;
+        };
 
-            let filename = filename.to_string_lossy();
+        let root_frn = self.frn_mapping[root_position];
 
-            let filename_position = self.filenames.len();
+        let Some(root_path) = self.full_path(root_position) else {
+            return false;
+        };
+
+        let keep: Vec<bool> = (0..self.filenames.len())
+            .into_par_iter()
+            .map(|position| self.is_descendant(position, root_frn))
+            .collect();
 
-            self.lowercase_filenames
-                .push(filename.to_lowercase().into());
-            self.filenames.push(filename.to_lowercase().into());
+        self.retain_positions(&keep);
 
-            self.frn_mapping.push(file_record_number);
-            self.parent_mapping.push(parent_record_number);
+        self.volume_path = root_path;
+        self.root_frn = root_frn;
+
+        true
+    }
+
+    fn is_descendant(&self, position: usize, ancestor_frn: u64) -> bool {
+        let mut position = position;
 
-            // expand the position mapping if necessary
-            while self.position_mapping.len() as u64 - 1 < file_record_number {
-                self.position_mapping.push(usize::MAX);
+        for _ in 0..MAX_PATH_DEPTH {
+            let parent = self.parent_mapping[position];
+
+            if parent == ancestor_frn {
+                return true;
             }
 
-            self.position_mapping[file_record_number as usize] = filename_position;
+            match self.position_of(parent) {
+                Some(parent_position) if parent_position != position => {
+                    position = parent_position;
+                }
+                _ => return false,
+            }
         }
-    }
 
-    pub fn update(&mut self, file_id: FileId, parent_id: FileId, path: &Path) {}
+        false
+    }
 
-    pub fn search(&mut self, query: &str) {
-        // let start = std::time::Instant::now();
+    // Removes every file whose position isn't marked in `keep`
+    fn retain_positions(&mut self, keep: &[bool]) {
+        fn retain<T>(vec: &mut Vec<T>, keep: &[bool]) {
+            let mut position = 0;
 
-        // self.filenames
-        //     .par_iter()
-        //     .enumerate()
-        //     .for_each(|(i, filename)| {
-        //         if filename == &query {
-        //             black_box(filename);
-        //         }
-        //     });
+            vec.retain(|_| {
+                position += 1;
+                keep[position - 1]
+            });
+        }
 
-        // println!("Full match {:?}", start.elapsed());
+        retain(&mut self.frn_mapping, keep);
+        retain(&mut self.parent_mapping, keep);
+        retain(&mut self.filesizes, keep);
+        retain(&mut self.modified_dates, keep);
+        retain(&mut self.created_dates, keep);
+        retain(&mut self.accessed_dates, keep);
+        retain(&mut self.attributes, keep);
+        retain(&mut self.security_ids, keep);
+        retain(&mut self.filenames, keep);
+        retain(&mut self.lowercase_filenames, keep);
 
-        let start = std::time::Instant::now();
+        self.frn_mapping.shrink_to_fit();
+        self.parent_mapping.shrink_to_fit();
+        self.filesizes.shrink_to_fit();
+        self.modified_dates.shrink_to_fit();
+        self.created_dates.shrink_to_fit();
+        self.accessed_dates.shrink_to_fit();
+        self.attributes.shrink_to_fit();
+        self.security_ids.shrink_to_fit();
+        self.filenames.shrink_to_fit();
+        self.lowercase_filenames.shrink_to_fit();
 
-        // Forbidden characters in filenames
-        //
-        // < (less than)
-        // > (greater than)
-        // : (colon - sometimes works, but is actually NTFS Alternate Data Streams)
-        // " (double quote)
-        // / (forward slash)
-        // \ (backslash)
-        // | (vertical bar or pipe)
-        // ? (question mark)
-        // * (asterisk)
-        //
-        // 0-31 (ASCII control characters)
-        //
-        // Filenames also cannot end in a space or dot.
+        // rebuilt on demand
+        self.children = None;
+        self.lowercase_paths = None;
+        self.depths = None;
 
-        let query = query.trim_end().to_ascii_lowercase();
+        // Only as long as the highest kept FRN needs, it grows again for new files
+        let length = self
+            .frn_mapping
+            .iter()
+            .max()
+            .map_or(0, |&frn| frn as usize + 1);
 
-        self.shown = self
-            .lowercase_filenames
-            .par_iter()
-            .enumerate()
-            .filter_map(|(i, filename)| filename.contains(&query).then_some(i))
-            .collect();
+        self.position_mapping.truncate(length);
+        self.position_mapping.shrink_to_fit();
+        self.position_mapping.fill(usize::MAX);
 
-        println!("Searching took {:?}", start.elapsed());
+        for (position, &frn) in self.frn_mapping.iter().enumerate() {
+            self.position_mapping[frn as usize] = position;
+        }
 
-        self.sort();
+        self.shown = (0..self.filenames.len()).collect();
     }
 
-    pub fn search_shown(&mut self, query: &str) {
-        let start = std::time::Instant::now();
+    // Whether a file in this directory is part of the index, which is always true unless a
+    // subtree is indexed
+    fn belongs_in_index(&self, parent_frn: u64) -> bool {
+        self.root_frn == ROOT_FRN
+            || parent_frn == self.root_frn
+            || self.position_of(parent_frn).is_some()
+    }
 
-        let query = query.trim_end().to_ascii_lowercase();
+    // Where a file is in the per-file vecs, None if it isn't indexed. Files created since the
+    // index was read can have FRNs past the end of the mapping.
+    pub fn position_of(&self, frn: u64) -> Option<usize> {
+        self.position_mapping
+            .get(frn as usize)
+            .copied()
+            .filter(|&position| position != usize::MAX)
+    }
 
-        self.shown = self
-            .shown
-            .par_iter()
-            .filter_map(|i| {
-                unsafe {
-                    // This is safe as long as `self.shown` is cleared/updated if a `self.lowercase_filenames` is updated
-                    self.lowercase_filenames
-                        .get_unchecked(*i)
-                        .contains(&query)
-                        .then_some(*i)
-                }
-            })
-            .collect();
+    // Grows the mapping for FRNs past its end, usize::MAX marks a file as not indexed
+    fn set_position(&mut self, frn: u64, position: usize) {
+        let index = frn as usize;
 
-        println!("Searching shown took {:?}", start.elapsed());
+        if index >= self.position_mapping.len() {
+            self.position_mapping.resize(index + 1, usize::MAX);
+        }
 
-        self.sort();
+        self.position_mapping[index] = position;
     }
 
-    pub fn sort(&mut self) {
-        let start = std::time::Instant::now();
+    fn push_filename(&mut self, filename: &str) {
+        if self.low_memory {
+            self.filenames.push(filename.into());
+            return;
+        }
 
-        match self.order {
-            FileOrder::RecordNumber => {
-                // since this is just the default with no button to set this there is no direction
-                self.shown.sort_unstable();
-            }
-            FileOrder::Name => {
-                self.shown.par_sort_unstable_by(|&a, &b| {
-                    let ordering = self.filenames[a].cmp(&self.filenames[b]);
+        let (filename, lowercase_filename) = filename_pair(filename, self.unicode_case);
 
-                    match self.direction {
-                        SortDirection::Ascending => ordering,
+        self.lowercase_filenames.push(lowercase_filename);
+        self.filenames.push(filename);
+    }
+
+    // Lowercases every filename again and drops the lowercase paths so they are rebuilt the same way
+    pub fn set_unicode_case(&mut self, unicode_case: bool) {
+        if self.unicode_case == unicode_case {
+            return;
+        }
+
+        let start = Instant::now();
+
+        self.unicode_case = unicode_case;
+
+        if !self.low_memory {
+            self.lowercase_filenames = self
+                .filenames
+                .par_iter()
+                .map(|filename| lowercase(filename, unicode_case).into())
+                .collect();
+        }
+
+        self.lowercase_paths = None;
+
+        debug!("Lowercasing filenames took {:?}", start.elapsed());
+    }
+
+    // The size rounded up to whole clusters like Explorer's "Size on disk". Only approximate as
+    // small files fit in their MFT record and compressed or sparse files use fewer clusters.
+    pub fn size_on_disk(&self, position: usize) -> Option<u64> {
+        let cluster_size = self.cluster_size?;
+
+        Some(self.filesizes[position].div_ceil(cluster_size) * cluster_size)
+    }
+
+    pub fn lowercase_filename(&self, position: usize) -> Cow<'_, str> {
+        if !self.low_memory {
+            return Cow::Borrowed(&self.lowercase_filenames[position]);
+        }
+
+        let filename = &self.filenames[position];
+
+        // Most filenames are mixed case but this saves an allocation for the rest
+        let has_uppercase = if self.unicode_case {
+            filename.chars().any(char::is_uppercase)
+        } else {
+            filename.bytes().any(|byte| byte.is_ascii_uppercase())
+        };
+
+        if has_uppercase {
+            Cow::Owned(lowercase(filename, self.unicode_case))
+        } else {
+            Cow::Borrowed(filename)
+        }
+    }
+
+    // Roughly how much memory the lowercase filenames take, or would take in low memory mode
+    pub fn lowercase_filenames_size(&self) -> usize {
+        self.filenames
+            .par_iter()
+            .map(|filename| filename.len() + std::mem::size_of::<Box<str>>())
+            .sum()
+    }
+
+    // Counts the per file vecs and the filenames but not the search state or caches
+    pub fn memory_size(&self) -> usize {
+        let per_file = std::mem::size_of::<u64>() * 3 // frn, parent and size
+            + std::mem::size_of::<Option<u64>>() * 3 // modified, created and accessed
+            + std::mem::size_of::<u32>() * 2 // attributes and security id
+            + std::mem::size_of::<Box<str>>();
+
+        let filenames: usize = self
+            .filenames
+            .par_iter()
+            .map(|filename| filename.len())
+            .sum();
+
+        let lowercase_filenames = if self.low_memory {
+            0
+        } else {
+            self.lowercase_filenames_size()
+        };
+
+        // 8.3 names are at most 12 bytes
+        let short_names = self.short_names.len() * (std::mem::size_of::<(u64, Box<str>)>() + 12);
+
+        self.position_mapping.len() * std::mem::size_of::<usize>()
+            + self.filenames.len() * per_file
+            + filenames
+            + lowercase_filenames
+            + short_names
+    }
+
+    pub fn stats(&self, scan_time: Duration) -> IndexStats {
+        IndexStats {
+            files: self.filenames.len(),
+            scan_ms: scan_time.as_millis() as u64,
+            memory_bytes: self.memory_size(),
+        }
+    }
+
+    pub fn delete(&mut self, file_id: FileId) {
+        let file_record_number = file_id_to_frn(file_id);
+
+        // The delete record can be read twice, e.g. after the journal overflowed
+        let Some(filename_position) = self.position_of(file_record_number) else {
+            for (_, files) in self.unplaced.values_mut() {
+                files.retain(|&(frn, _)| frn != file_record_number);
+            }
+
+            warn!("Deleted FRN {file_record_number} isn't in the index");
+            return;
+        };
+
+        // While the path can still be built
+        self.notify(ChangeKind::Deleted, filename_position);
+
+        // FRNs are reused for new files
+        self.short_names.remove(&file_record_number);
+
+        if self.tombstones.len() == MAX_TOMBSTONES {
+            self.tombstones.pop_back();
+        }
+
+        let parent_frn = self.parent_mapping[filename_position];
+        self.remove_child(parent_frn, file_record_number);
+        // A folder is only deleted once it is empty, and its FRN can be reused for a file
+        if let Some(children) = &mut self.children {
+            children.remove(&file_record_number);
+        }
+        self.folder_sizes.remove(&file_record_number);
+        self.invalidate_folder_sizes(parent_frn);
+
+        self.tombstones.push_front(Tombstone {
+            filename: self.filenames[filename_position].clone(),
+            path: self.path(filename_position),
+            size: self.filesizes[filename_position],
+            deleted: SystemTime::now(),
+        });
+
+        if filename_position == self.filenames.len() - 1 {
+            self.filenames.pop();
+            self.lowercase_filenames.pop();
+            self.filesizes.pop();
+            self.modified_dates.pop();
+            self.created_dates.pop();
+            self.accessed_dates.pop();
+            self.attributes.pop();
+            self.security_ids.pop();
+
+            if let Some(paths) = &mut self.lowercase_paths {
+                paths.pop();
+            }
+
+            if let Some(depths) = &mut self.depths {
+                depths.pop();
+            }
+
+            self.frn_mapping.pop();
+            self.parent_mapping.pop();
+
+            self.set_position(file_record_number, usize::MAX);
+        } else {
+            self.filenames.swap_remove(filename_position);
+            if !self.low_memory {
+                self.lowercase_filenames.swap_remove(filename_position);
+            }
+            self.filesizes.swap_remove(filename_position);
+            self.modified_dates.swap_remove(filename_position);
+            self.created_dates.swap_remove(filename_position);
+            self.accessed_dates.swap_remove(filename_position);
+            self.attributes.swap_remove(filename_position);
+            self.security_ids.swap_remove(filename_position);
+
+            if let Some(paths) = &mut self.lowercase_paths {
+                paths.swap_remove(filename_position);
+            }
+
+            if let Some(depths) = &mut self.depths {
+                depths.swap_remove(filename_position);
+            }
+
+            // it isn't possible to have 0 files
+            let replacement_frn = self.frn_mapping.pop().unwrap();
+            self.frn_mapping[filename_position] = replacement_frn;
+
+            let replacement_parent_frn = self.parent_mapping.pop().unwrap();
+            self.parent_mapping[filename_position] = replacement_parent_frn;
+
+            self.set_position(file_record_number, usize::MAX);
+            self.set_position(replacement_frn, filename_position);
+        }
+
+        // can be very slow but the other rows stay where they are
+        if let Some(row) = self
+            .shown
+            .iter()
+            .position(|&shown| shown == filename_position)
+        {
+            self.shown.remove(row);
+        }
+
+        // The last file was moved into the deleted one's position
+        let moved_from = self.filenames.len();
+        for shown in &mut self.shown {
+            if *shown == moved_from {
+                *shown = filename_position;
+            }
+        }
+
+        self.scores.remove(&filename_position);
+        if let Some(score) = self.scores.remove(&moved_from) {
+            self.scores.insert(filename_position, score);
+        }
+    }
+
+    pub fn rename(&mut self, file_id: FileId, parent_id: FileId, path: &Path) {
+        let file_record_number = file_id_to_frn(file_id);
+        let parent_record_number = file_id_to_frn(parent_id);
+
+        let filename_position = self.position_of(file_record_number);
+
+        if !self.belongs_in_index(parent_record_number) {
+            // Moved out of the indexed subtree
+            if filename_position.is_some() {
+                self.delete(file_id);
+            }
+            return;
+        }
+
+        let Some(filename_position) = filename_position else {
+            if self.root_frn != ROOT_FRN {
+                // Moved into the indexed subtree, the contents of a moved directory won't be
+                // indexed until they change
+                self.create(file_id, parent_id, path);
+            } else {
+                debug!("Renamed FRN {file_record_number} isn't in the index");
+            }
+            return;
+        };
+
+        // The name and parent are updated together, a path without a name would leave the entry
+        // half moved
+        let Some(filename) = record_name(path) else {
+            warn!(
+                "Rename of FRN {file_record_number} has no file name: {}",
+                path.display()
+            );
+            return;
+        };
+
+        if self.low_memory {
+            self.filenames[filename_position] = filename.into();
+        } else {
+            let (filename, lowercase_filename) = filename_pair(&filename, self.unicode_case);
+
+            self.lowercase_filenames[filename_position] = lowercase_filename;
+            self.filenames[filename_position] = filename;
+        }
+
+        // The journal doesn't say what the new 8.3 name is
+        self.short_names.remove(&file_record_number);
+
+        let previous_parent_frn = self.parent_mapping[filename_position];
+
+        if previous_parent_frn != parent_record_number {
+            self.remove_child(previous_parent_frn, file_record_number);
+            self.invalidate_folder_sizes(previous_parent_frn);
+            self.invalidate_folder_sizes(parent_record_number);
+
+            if let Some(children) = &mut self.children {
+                children
+                    .entry(parent_record_number)
+                    .or_default()
+                    .push(file_record_number);
+            }
+        }
+
+        self.parent_mapping[filename_position] = parent_record_number;
+
+        if self.depths.is_some() {
+            if self.is_directory(filename_position) {
+                // everything below the directory moved with it
+                self.depths = None;
+            } else {
+                let depth = self.depth(filename_position) as u16;
+
+                if let Some(depths) = &mut self.depths {
+                    depths[filename_position] = depth;
+                }
+            }
+        }
+
+        if self.lowercase_paths.is_some() {
+            if self.is_directory(filename_position) {
+                // every path below the directory changed
+                self.lowercase_paths = None;
+            } else {
+                let lowercase_path = self.lowercase_path(filename_position);
+
+                if let Some(paths) = &mut self.lowercase_paths {
+                    paths[filename_position] = lowercase_path;
+                }
+            }
+        }
+
+        self.notify(ChangeKind::Renamed, filename_position);
+    }
+
+    fn remove_child(&mut self, parent_frn: u64, frn: u64) {
+        if let Some(siblings) = self
+            .children
+            .as_mut()
+            .and_then(|children| children.get_mut(&parent_frn))
+        {
+            siblings.retain(|&sibling| sibling != frn);
+        }
+    }
+
+    // Builds the directory -> children index, which is kept up to date once built
+    pub fn build_children(&mut self) {
+        if self.children.is_some() {
+            return;
+        }
+
+        let start = Instant::now();
+
+        let mut children: FxHashMap<u64, Vec<u64>> = FxHashMap::default();
+
+        for (&frn, &parent) in self.frn_mapping.iter().zip(&self.parent_mapping) {
+            // the root is its own parent
+            if frn != parent {
+                children.entry(parent).or_default().push(frn);
+            }
+        }
+
+        debug!("Building children index took {:?}", start.elapsed());
+
+        self.children = Some(children);
+    }
+
+    // Number of files directly in a directory, None if the children index hasn't been built
+    pub fn child_count(&self, position: usize) -> Option<usize> {
+        let children = self.children.as_ref()?;
+
+        Some(
+            children
+                .get(&self.frn_mapping[position])
+                .map_or(0, Vec::len),
+        )
+    }
+
+    // Shows everything directly in a folder that passes the filters, like browsing it in Explorer
+    pub fn show_children(&mut self, frn: u64, filters: &FilterState) {
+        if self.children.is_none() {
+            self.build_children();
+        }
+
+        self.pending_search = None;
+        self.scores.clear();
+        self.filters = filters.clone();
+        self.build_depths();
+
+        let children = self
+            .children
+            .as_ref()
+            .and_then(|children| children.get(&frn));
+
+        self.shown = children
+            .into_iter()
+            .flatten()
+            .filter_map(|&child| self.position_of(child))
+            .filter(|&position| self.passes_filters(position))
+            .collect();
+        self.shown_by = Some(ShownBy::Children(frn));
+
+        self.sort();
+    }
+
+    // The FRNs and names of the folders from below the root down to `frn`, for a breadcrumb
+    pub fn folder_chain(&self, frn: u64) -> Vec<(u64, &str)> {
+        let mut chain = Vec::new();
+        let mut frn = frn;
+
+        while frn != self.root_frn && chain.len() <= MAX_PATH_DEPTH {
+            let Some(position) = self.position_of(frn) else {
+                break;
+            };
+
+            chain.push((frn, &*self.filenames[position]));
+            frn = self.parent_mapping[position];
+        }
+
+        chain.reverse();
+        chain
+    }
+
+    // Whether the file is one of the page, swap or hibernation files in the drive root
+    pub fn is_system_file(&self, position: usize) -> bool {
+        self.parent_mapping[position] == ROOT_FRN
+            && SYSTEM_FILES
+                .iter()
+                .any(|name| self.filenames[position].eq_ignore_ascii_case(name))
+    }
+
+    // The size of a file as it counts towards totals
+    fn counted_size(&self, position: usize) -> u64 {
+        if self.exclude_system_files && self.is_system_file(position) {
+            0
+        } else {
+            self.filesizes[position]
+        }
+    }
+
+    pub fn set_exclude_system_files(&mut self, exclude: bool) {
+        if self.exclude_system_files != exclude {
+            self.exclude_system_files = exclude;
+            // The drive root's total is counted differently now
+            self.folder_sizes.clear();
+            self.folder_size_count = None;
+        }
+    }
+
+    // Total size of the shown files, without folders as their sizes aren't their contents
+    pub fn shown_size(&self) -> u64 {
+        self.shown
+            .iter()
+            .filter(|&&position| !self.is_directory(position))
+            .map(|&position| self.counted_size(position))
+            .sum()
+    }
+
+    // Total size of everything below a directory, None if it hasn't been counted yet
+    pub fn folder_size(&self, frn: u64) -> Option<u64> {
+        self.folder_sizes.get(&frn).copied()
+    }
+
+    // Counts the total size of a directory and of every folder below it, needs the children index.
+    // None if the deadline passed first, counting the same folder again carries on where it
+    // stopped.
+    pub fn count_folder_size(&mut self, frn: u64, deadline: Instant) -> Option<u64> {
+        if let Some(&size) = self.folder_sizes.get(&frn) {
+            return Some(size);
+        }
+
+        let Some(children) = &self.children else {
+            return Some(0);
+        };
+
+        let mut count = match self.folder_size_count.take() {
+            Some(count) if count.frn == frn => count,
+            // Folders the other count finished are cached, the rest is redone when it's shown again
+            _ => FolderSizeCount {
+                frn,
+                stack: vec![(frn, false)],
+                entered: FxHashSet::default(),
+            },
+        };
+
+        let mut steps = 0;
+
+        // Folders are counted after the folders in them so every folder below gets cached
+        while let Some((folder, counted_below)) = count.stack.pop() {
+            // At least one folder is done each time so the count always gets somewhere
+            if steps > 0 && Instant::now() >= deadline {
+                count.stack.push((folder, counted_below));
+                self.folder_size_count = Some(count);
+                return None;
+            }
+
+            steps += 1;
+
+            if self.folder_sizes.contains_key(&folder)
+                || (!counted_below && !count.entered.insert(folder))
+            {
+                continue;
+            }
+
+            let positions = children
+                .get(&folder)
+                .into_iter()
+                .flatten()
+                .filter_map(|&child| self.position_of(child).map(|position| (child, position)));
+
+            if counted_below {
+                let total = positions
+                    .map(|(child, position)| {
+                        if self.attributes[position] & FILE_ATTRIBUTE_DIRECTORY.0 != 0 {
+                            self.folder_sizes.get(&child).copied().unwrap_or(0)
+                        } else {
+                            self.counted_size(position)
+                        }
+                    })
+                    .sum();
+
+                self.folder_sizes.insert(folder, total);
+            } else {
+                let subfolders: Vec<u64> = positions
+                    .filter(|&(child, position)| {
+                        self.attributes[position] & FILE_ATTRIBUTE_DIRECTORY.0 != 0
+                            && !self.folder_sizes.contains_key(&child)
+                    })
+                    .map(|(child, _)| child)
+                    .collect();
+
+                count.stack.push((folder, true));
+                count
+                    .stack
+                    .extend(subfolders.into_iter().map(|child| (child, false)));
+            }
+        }
+
+        Some(self.folder_sizes.get(&frn).copied().unwrap_or(0))
+    }
+
+    // Something in the folder changed so its total and the totals of the folders above are wrong
+    fn invalidate_folder_sizes(&mut self, frn: u64) {
+        // Its stack can hold folders that have moved or whose totals are now wrong
+        self.folder_size_count = None;
+
+        let mut frn = frn;
+
+        for _ in 0..=MAX_PATH_DEPTH {
+            if self.folder_sizes.is_empty() {
+                return;
+            }
+
+            self.folder_sizes.remove(&frn);
+
+            let Some(position) = self.position_of(frn) else {
+                return;
+            };
+
+            let parent = self.parent_mapping[position];
+
+            if parent == frn {
+                return;
+            }
+
+            frn = parent;
+        }
+    }
+
+    // Full path of an indexed folder, None if it isn't in the index
+    fn folder_path(&self, frn: u64) -> Option<PathBuf> {
+        if frn == self.root_frn {
+            return Some(self.volume_path.clone());
+        }
+
+        let position = self.position_of(frn)?;
+
+        self.full_path(position)
+    }
+
+    // NTFS times of every file, None for files created since the index was loaded
+    pub fn dates(&self, kind: DateKind) -> &[Option<u64>] {
+        match kind {
+            DateKind::Modified => &self.modified_dates,
+            DateKind::Created => &self.created_dates,
+            DateKind::Accessed => &self.accessed_dates,
+        }
+    }
+
+    pub fn is_directory(&self, position: usize) -> bool {
+        self.attributes[position] & FILE_ATTRIBUTE_DIRECTORY.0 != 0
+    }
+
+    pub fn create(&mut self, file_id: FileId, parent_id: FileId, path: &Path) {
+        if let Some(filename) = record_name(path) {
+            let file_record_number = file_id_to_frn(file_id);
+            let parent_record_number = file_id_to_frn(parent_id);
+
+            // Records from while the MFT was read are applied again, it may already have the file
+            if self.position_of(file_record_number).is_some() {
+                debug!("Created FRN {file_record_number} is already in the index");
+                return;
+            }
+
+            if !self.belongs_in_index(parent_record_number) {
+                self.defer_create(file_record_number, parent_record_number, path);
+                return;
+            }
+
+            // A bare name would be read relative to the working directory
+            let full_path = if path.is_absolute() {
+                Some(path.to_path_buf())
+            } else {
+                self.folder_path(parent_record_number)
+                    .map(|folder| folder.join(&*filename))
+            };
+
+            let filename_position = self.filenames.len();
+
+            self.push_filename(&filename);
+
+            // the size and dates aren't in the USN record
+            self.filesizes.push(0);
+            self.modified_dates.push(None);
+            self.created_dates.push(None);
+            self.accessed_dates.push(None);
+            // the attributes are read from the file instead
+            self.attributes.push(
+                full_path
+                    .and_then(|path| std::fs::symlink_metadata(path).ok())
+                    .map_or(0, |metadata| metadata.file_attributes()),
+            );
+            // not in the USN record either
+            self.security_ids.push(0);
+
+            if let Some(children) = &mut self.children {
+                children
+                    .entry(parent_record_number)
+                    .or_default()
+                    .push(file_record_number);
+            }
+
+            self.invalidate_folder_sizes(parent_record_number);
+
+            self.frn_mapping.push(file_record_number);
+            self.parent_mapping.push(parent_record_number);
+
+            self.set_position(file_record_number, filename_position);
+
+            if parent_record_number != self.root_frn
+                && self.position_of(parent_record_number).is_none()
+            {
+                self.add_missing_parent(parent_record_number);
+            }
+
+            // Files below this folder were created first so their paths were built without it
+            if self.missing_parents.remove(&file_record_number).is_some() {
+                self.lowercase_paths = None;
+                self.depths = None;
+            }
+
+            if self.depths.is_some() {
+                let depth = self.depth(filename_position) as u16;
+
+                if let Some(depths) = &mut self.depths {
+                    depths.push(depth);
+                }
+            }
+
+            if self.lowercase_paths.is_some() {
+                let lowercase_path = self.lowercase_path(filename_position);
+
+                if let Some(paths) = &mut self.lowercase_paths {
+                    paths.push(lowercase_path);
+                }
+            }
+
+            self.notify(ChangeKind::Created, filename_position);
+
+            // Files in this folder were created before it, outside of the indexed subtree so far
+            if let Some((_, files)) = self.unplaced.remove(&file_record_number) {
+                for (frn, path) in files {
+                    self.create(
+                        FileId::Normal(frn),
+                        FileId::Normal(file_record_number),
+                        &path,
+                    );
+                }
+            }
+        }
+    }
+
+    // If it's dropped, the cached lowercase paths of the files in it keep missing the folder until
+    // they are rebuilt
+    fn add_missing_parent(&mut self, frn: u64) {
+        if self.missing_parents.len() >= MAX_MISSING_PARENTS
+            && !self.missing_parents.contains_key(&frn)
+        {
+            self.missing_parents
+                .retain(|_, created| created.elapsed() < PARENT_WAIT);
+
+            if self.missing_parents.len() >= MAX_MISSING_PARENTS {
+                return;
+            }
+        }
+
+        self.missing_parents.entry(frn).or_insert_with(Instant::now);
+    }
+
+    // Keeps a file created in a folder that isn't indexed when a subtree is, the folder may be
+    // created inside the subtree afterwards, e.g. when extracting an archive
+    fn defer_create(&mut self, frn: u64, parent_frn: u64, path: &Path) {
+        if self.unplaced.len() >= MAX_UNPLACED_FOLDERS && !self.unplaced.contains_key(&parent_frn) {
+            self.unplaced
+                .retain(|_, (created, _)| created.elapsed() < PARENT_WAIT);
+
+            if self.unplaced.len() >= MAX_UNPLACED_FOLDERS {
+                return;
+            }
+        }
+
+        let (_, files) = self
+            .unplaced
+            .entry(parent_frn)
+            .or_insert_with(|| (Instant::now(), Vec::new()));
+
+        if files.len() < MAX_UNPLACED_PER_FOLDER {
+            files.push((frn, path.to_path_buf()));
+        }
+    }
+
+    // The USN record has no sizes or dates so there is nothing to change in the index yet
+    pub fn update(&mut self, file_id: FileId, _parent_id: FileId, _path: &Path) {
+        if let Some(position) = self.position_of(file_id_to_frn(file_id)) {
+            self.notify(ChangeKind::Updated, position);
+        }
+    }
+
+    // Calls `listener` with every change applied to the index from now on, including after it is
+    // rebuilt
+    pub fn subscribe(&mut self, listener: impl Fn(&ChangeEvent) + Send + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    fn notify(&self, kind: ChangeKind, position: usize) {
+        if self.listeners.is_empty() {
+            return;
+        }
+
+        let event = ChangeEvent {
+            kind,
+            frn: self.frn_mapping[position],
+            path: self.full_path(position),
+        };
+
+        for listener in &self.listeners {
+            listener(&event);
+        }
+    }
+
+    // The number of threads searching and sorting use, 0 for every core
+    pub fn set_threads(&mut self, threads: usize) {
+        self.pool = Arc::new(build_pool(threads));
+    }
+
+    pub fn search(&mut self, query: &str, filters: &FilterState) {
+        self.start_search(query, filters);
+
+        while !self.continue_search() {}
+    }
+
+    // Starts a search that is run a chunk at a time by `continue_search` so the results and their
+    // count can be shown while a broad search is still running
+    pub fn start_search(&mut self, query: &str, filters: &FilterState) {
+        // let start = std::time::Instant::now();
+
+        // self.filenames
+        //     .par_iter()
+        //     .enumerate()
+        //     .for_each(|(i, filename)| {
+        //         if filename == &query {
+        //             black_box(filename);
+        //         }
+        //     });
+
+        // println!("Full match {:?}", start.elapsed());
+
+        // Forbidden characters in filenames
+        //
+        // < (less than)
+        // > (greater than)
+        // : (colon - sometimes works, but is actually NTFS Alternate Data Streams)
+        // " (double quote)
+        // / (forward slash)
+        // \ (backslash)
+        // | (vertical bar or pipe)
+        // ? (question mark)
+        // * (asterisk)
+        //
+        // 0-31 (ASCII control characters)
+        //
+        // Filenames also cannot end in a space or dot.
+
+        let text = self.parse_query(query, filters);
+        let term = self.search_term(text);
+
+        self.shown.clear();
+        self.scores.clear();
+        self.pending_search = Some(PendingSearch {
+            term,
+            next: 0,
+            start: Instant::now(),
+        });
+    }
+
+    // Searches the next chunk of files, returns true once the search has finished
+    pub fn continue_search(&mut self) -> bool {
+        let Some(pending) = &self.pending_search else {
+            return true;
+        };
+
+        let start = pending.next;
+        let end = (start + SEARCH_CHUNK_SIZE).min(self.filenames.len());
+
+        let matches: Vec<(usize, u32)> = self.pool.install(|| {
+            (start..end)
+                .into_par_iter()
+                .filter_map(|i| {
+                    self.position_score(i, &pending.term)
+                        .map(|score| (i, score))
+                })
+                .collect()
+        });
+
+        let search_start = pending.start;
+
+        if self.fuzzy {
+            self.scores.extend(matches.iter().copied());
+        }
+
+        self.shown.extend(matches.into_iter().map(|(i, _)| i));
+
+        if end < self.filenames.len() {
+            if let Some(pending) = &mut self.pending_search {
+                pending.next = end;
+            }
+            return false;
+        }
+
+        debug!("Searching took {:?}", search_start.elapsed());
+
+        self.shown_by = self
+            .pending_search
+            .take()
+            .map(|pending| ShownBy::Search(pending.term));
+        self.sort();
+
+        true
+    }
+
+    // None if the file doesn't match, otherwise its fuzzy score or 0
+    fn position_score(&self, position: usize, term: &SearchTerm) -> Option<u32> {
+        if !self.passes_filters(position) {
+            return None;
+        }
+
+        let query = &*term.query;
+
+        // Matches filenames if the paths couldn't be built
+        let score = match (&term.matcher, &self.lowercase_paths) {
+            (Matcher::PathGlob(glob), Some(paths)) => glob.is_match(&paths[position]).then_some(0),
+            (Matcher::PathSubstring, Some(paths)) => paths[position].contains(query).then_some(0),
+            (Matcher::PathFuzzy, Some(paths)) => fuzzy_path_score(&paths[position], query),
+            (Matcher::Wildcard(wildcard), _) => wildcard
+                .is_match(&self.lowercase_filename(position))
+                .then_some(0),
+            _ => match_score(&self.lowercase_filename(position), query, self.fuzzy),
+        }?;
+
+        term.exact
+            .as_ref()
+            .is_none_or(|(text, matcher)| self.matches_case(position, text, matcher))
+            .then_some(score)
+    }
+
+    // Checked after the lowercase match so the path is only built for the files that matched
+    fn matches_case(&self, position: usize, text: &str, matcher: &Matcher) -> bool {
+        let filename = &*self.filenames[position];
+
+        let path = || {
+            self.full_path(position).map_or_else(
+                || filename.to_string(),
+                |path| path.to_string_lossy().into_owned(),
+            )
+        };
+
+        match (matcher, &self.lowercase_paths) {
+            (Matcher::PathGlob(glob), Some(_)) => glob.is_match(&path()),
+            (Matcher::PathSubstring, Some(_)) => path().contains(text),
+            (Matcher::PathFuzzy, Some(_)) => fuzzy_path_score(&path(), text).is_some(),
+            (Matcher::Wildcard(wildcard), _) => wildcard.is_match(filename),
+            _ => match_score(filename, text, self.fuzzy).is_some(),
+        }
+    }
+
+    fn search_term(&mut self, text: String) -> SearchTerm {
+        let query = lowercase(&text, self.unicode_case);
+        let matcher = self.matcher(&query);
+
+        SearchTerm {
+            exact: self.filters.match_case.then(|| {
+                let matcher = self.matcher(&text);
+                (text, matcher)
+            }),
+            query,
+            matcher,
+        }
+    }
+
+    // Builds the paths first if the query needs them
+    fn matcher(&mut self, query: &str) -> Matcher {
+        let matcher = if self.path_glob {
+            Matcher::PathGlob(Glob::new(query, self.recursive_glob))
+        } else if self.fuzzy && self.fuzzy_paths {
+            Matcher::PathFuzzy
+        } else if is_path_query(query) {
+            Matcher::PathSubstring
+        } else if self.wildcards && query::has_wildcards(query) {
+            return Matcher::Wildcard(Wildcard::new(query));
+        } else {
+            return Matcher::Filename;
+        };
+
+        self.build_lowercase_paths();
+
+        matcher
+    }
+
+    fn passes_filters(&self, position: usize) -> bool {
+        let attributes = self.attributes[position];
+
+        if attributes & self.filters.required_attributes != self.filters.required_attributes
+            || attributes & self.filters.excluded_attributes != 0
+        {
+            return false;
+        }
+
+        // Directories don't have a size so they are never empty files
+        let is_empty = !self.is_directory(position) && self.filesizes[position] == 0;
+
+        let passes_empty_filter = match self.filters.empty_filter {
+            EmptyFilter::All => true,
+            EmptyFilter::OnlyEmpty => is_empty,
+            EmptyFilter::HideEmpty => !is_empty,
+        };
+
+        passes_empty_filter
+            && !self.has_hidden_extension(position)
+            && self.filters.size_filter.as_ref().is_none_or(|size| {
+                !self.is_directory(position) && size.contains(&self.filesizes[position])
+            })
+            && self
+                .filters
+                .owner_filter
+                .as_ref()
+                .is_none_or(|security_ids| security_ids.contains(&self.security_ids[position]))
+            && self
+                .filters
+                .short_name_filter
+                .as_ref()
+                .is_none_or(|short_name| self.short_name_contains(position, short_name))
+            && self.filters.max_depth.is_none_or(|max_depth| {
+                // Filters are only checked once the depths are built
+                self.depths
+                    .as_ref()
+                    .is_none_or(|depths| usize::from(depths[position]) <= max_depth)
+            })
+            && self.filters.recent_after.is_none_or(|(kind, cutoff)| {
+                // Files created since the index was loaded have no date but are almost always
+                // newer than the cutoff
+                self.dates(kind)[position].is_none_or(|date| date >= cutoff)
+            })
+    }
+
+    // A file without a separate 8.3 name is matched by its name, which already fits in 8.3
+    fn short_name_contains(&self, position: usize, text: &str) -> bool {
+        match self.short_names.get(&self.frn_mapping[position]) {
+            Some(short_name) => short_name.contains(text),
+            None => self.lowercase_filename(position).contains(text),
+        }
+    }
+
+    fn has_hidden_extension(&self, position: usize) -> bool {
+        !self.filters.hidden_extensions.is_empty()
+            && self
+                .extension(position)
+                .is_some_and(|extension| self.filters.hidden_extensions.contains(&*extension))
+    }
+
+    // Lowercase extension without the dot, None for directories and files without one
+    pub fn extension(&self, position: usize) -> Option<Box<str>> {
+        if self.is_directory(position) {
+            return None;
+        }
+
+        self.lowercase_filename(position)
+            .rsplit_once('.')
+            .map(|(_, extension)| extension)
+            .filter(|extension| !extension.is_empty())
+            .map(Box::from)
+    }
+
+    // How many shown files have each extension, most common first
+    pub fn extension_counts(&self) -> Vec<(Box<str>, usize)> {
+        let counts = self.pool.install(|| {
+            self.shown
+                .par_iter()
+                .filter_map(|&position| self.extension(position))
+                .fold(FxHashMap::default, |mut counts, extension| {
+                    *counts.entry(extension).or_insert(0) += 1;
+                    counts
+                })
+                .reduce(FxHashMap::default, |mut counts, other| {
+                    for (extension, count) in other {
+                        *counts.entry(extension).or_insert(0) += count;
+                    }
+                    counts
+                })
+        });
+
+        let mut counts: Vec<(Box<str>, usize)> = counts.into_iter().collect();
+        counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    // Of the security ids whose owner has been resolved, the others are queued with one file each.
+    // The search is run again once they are, see `poll_owners`.
+    fn security_ids_owned_by(&mut self, owner: &str) -> FxHashSet<u32> {
+        let mut examples: FxHashMap<u32, usize> = FxHashMap::default();
+
+        for (position, &security_id) in self.security_ids.iter().enumerate() {
+            if security_id != 0 && !self.owners.is_resolved(security_id) {
+                examples.entry(security_id).or_insert(position);
+            }
+        }
+
+        for (security_id, position) in examples {
+            let path = self.full_path(position);
+            self.owners.lookup(security_id, || path);
+        }
+
+        let mut security_ids: FxHashSet<u32> = self.security_ids.iter().copied().collect();
+
+        security_ids.retain(|&security_id| owner::matches(self.owners.name(security_id), owner));
+
+        security_ids
+    }
+
+    // Takes in newly resolved owners, returns true once every queued one is resolved while the
+    // owner: filter is used so the search can be run again with them
+    pub fn poll_owners(&mut self) -> bool {
+        self.owners.poll() && !self.owners.is_resolving() && self.filters.owner_filter.is_some()
+    }
+
+    // Whether the results of the owner: filter are still missing the owners being read
+    pub fn is_reading_owners(&self) -> bool {
+        self.filters.owner_filter.is_some() && self.owners.is_resolving()
+    }
+
+    // Combines the filters with the ones from the query, returning the text to search for
+    fn parse_query(&mut self, query: &str, filters: &FilterState) -> String {
+        let query = query::parse(query);
+
+        self.filters = filters.clone();
+        self.filters.size_filter = query.size;
+        self.filters.owner_filter = query.owner.map(|owner| self.security_ids_owned_by(&owner));
+        self.filters.short_name_filter = query.short_name;
+        self.build_depths();
+
+        query.text.trim().to_string()
+    }
+
+    // Shows every file that passes the filters, for when there is no search
+    pub fn show_all(&mut self, filters: &FilterState) {
+        self.filters = filters.clone();
+        self.build_depths();
+        self.shown = self.pool.install(|| {
+            (0..self.filenames.len())
+                .into_par_iter()
+                .filter(|&position| self.passes_filters(position))
+                .collect()
+        });
+        self.shown_by = Some(ShownBy::All);
+
+        self.scores.clear();
+        self.move_pinned_to_top();
+    }
+
+    // Just the filename if a folder above it isn't indexed yet, the paths are rebuilt once it is
+    fn lowercase_path(&self, position: usize) -> Box<str> {
+        match self.full_path(position) {
+            Some(path) => lowercase(&path.to_string_lossy(), self.unicode_case).into(),
+            None => self.lowercase_filename(position).into(),
+        }
+    }
+
+    // Only needed while the depth filter is used
+    fn build_depths(&mut self) {
+        if self.filters.max_depth.is_none() || self.depths.is_some() {
+            return;
+        }
+
+        let start = Instant::now();
+
+        let depths = self.pool.install(|| {
+            (0..self.filenames.len())
+                .into_par_iter()
+                .map(|position| self.depth(position) as u16)
+                .collect()
+        });
+
+        debug!("Building depths took {:?}", start.elapsed());
+
+        self.depths = Some(depths);
+    }
+
+    fn build_lowercase_paths(&mut self) {
+        if self.lowercase_paths.is_some() {
+            return;
+        }
+
+        let start = Instant::now();
+
+        let paths = self.pool.install(|| {
+            (0..self.filenames.len())
+                .into_par_iter()
+                .map(|position| self.lowercase_path(position))
+                .collect()
+        });
+
+        debug!("Building paths took {:?}", start.elapsed());
+
+        self.lowercase_paths = Some(paths);
+    }
+
+    // A path with its drive letter replaced by the drive's label, e.g. "Windows (C:)\Users" for
+    // C:\Users, for showing paths. Unchanged if the drive has no label.
+    pub fn labelled_path<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        let (Some(label), Some(drive)) = (
+            &self.volume_label,
+            path.get(..2).filter(|drive| drive.ends_with(':')),
+        ) else {
+            return Cow::Borrowed(path);
+        };
+
+        Cow::Owned(format!("{label} ({drive}){}", &path[2..]))
+    }
+
+    // Relevance of a file in the last fuzzy search
+    pub fn score(&self, position: usize) -> u32 {
+        self.scores.get(&position).copied().unwrap_or(0)
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.pending_search.is_some()
+    }
+
+    // How much of the index the running search has gone through, from 0 to 1
+    pub fn search_progress(&self) -> Option<f32> {
+        let pending = self.pending_search.as_ref()?;
+
+        Some(pending.next as f32 / self.filenames.len().max(1) as f32)
+    }
+
+    pub fn search_shown(&mut self, query: &str, filters: &FilterState) {
+        let start = std::time::Instant::now();
+
+        let text = self.parse_query(query, filters);
+        let term = self.search_term(text);
+
+        let matches: Vec<(usize, u32)> = self.pool.install(|| {
+            self.shown
+                .par_iter()
+                .filter_map(|&i| self.position_score(i, &term).map(|score| (i, score)))
+                .collect()
+        });
+
+        self.scores.clear();
+
+        if self.fuzzy {
+            self.scores.extend(matches.iter().copied());
+        }
+
+        self.shown = matches.into_iter().map(|(i, _)| i).collect();
+        self.shown_by = Some(ShownBy::Search(term));
+
+        debug!("Searching shown took {:?}", start.elapsed());
+
+        self.sort();
+    }
+
+    // Shows files picked without a search, e.g. only some of the results. Changed files aren't
+    // added to them.
+    pub fn set_shown(&mut self, shown: Vec<usize>) {
+        self.shown = shown;
+        self.shown_by = None;
+    }
+
+    // Whether a file would be shown if the results were picked again now
+    pub fn belongs_in_shown(&self, position: usize) -> bool {
+        match &self.shown_by {
+            Some(ShownBy::All) => self.passes_filters(position),
+            Some(ShownBy::Search(term)) => self.position_score(position, term).is_some(),
+            Some(ShownBy::Children(frn)) => {
+                self.parent_mapping[position] == *frn && self.passes_filters(position)
+            }
+            None => false,
+        }
+    }
+
+    // Adds the changed files that now belong in the results and removes the ones that don't,
+    // without searching again
+    pub fn apply_changes(&mut self, frns: impl IntoIterator<Item = u64>) {
+        let needs_paths = matches!(
+            &self.shown_by,
+            Some(ShownBy::Search(term))
+                if !matches!(term.matcher, Matcher::Filename | Matcher::Wildcard(_))
+        );
+
+        if needs_paths {
+            self.build_lowercase_paths();
+        }
+
+        self.build_depths();
+
+        let mut shown: FxHashSet<usize> = self.shown.iter().copied().collect();
+        let mut added = Vec::new();
+        let mut removed = FxHashSet::default();
+
+        for frn in frns {
+            // Deleted files were already taken out of the results
+            let Some(position) = self.position_of(frn) else {
+                continue;
+            };
+
+            let score = match &self.shown_by {
+                Some(ShownBy::Search(term)) => self.position_score(position, term),
+                _ => self.belongs_in_shown(position).then_some(0),
+            };
+
+            match score {
+                Some(score) if shown.insert(position) => {
+                    if self.fuzzy {
+                        self.scores.insert(position, score);
+                    }
+                    added.push(position);
+                }
+                None if shown.remove(&position) => {
+                    removed.insert(position);
+                }
+                _ => {}
+            }
+        }
+
+        if added.is_empty() && removed.is_empty() {
+            return;
+        }
+
+        self.shown.retain(|position| !removed.contains(position));
+        self.shown.extend(added);
+        self.sort();
+    }
+
+    pub fn sort(&mut self) {
+        let start = std::time::Instant::now();
+
+        let pool = self.pool.clone();
+
+        pool.install(|| match self.order {
+            FileOrder::RecordNumber => {
+                // since this is just the default with no button to set this there is no direction
+                self.shown.sort_unstable();
+            }
+            FileOrder::Name => {
+                // `self.lowercase_filename` would borrow all of self while `shown` is borrowed
+                let filenames = &self.filenames;
+                let lowercase_filenames = &self.lowercase_filenames;
+                let unicode_case = self.unicode_case;
+
+                if self.case_insensitive_sort && self.low_memory {
+                    // Lowercased once per file rather than twice per comparison, names differing
+                    // only in case still get a stable order
+                    self.shown.par_sort_by_cached_key(|&position| {
+                        let filename = &*filenames[position];
+                        (lowercase(filename, unicode_case), filename)
+                    });
+
+                    if self.direction == SortDirection::Descending {
+                        self.shown.reverse();
+                    }
+                } else {
+                    self.shown.par_sort_unstable_by(|&a, &b| {
+                        let ordering = if self.case_insensitive_sort {
+                            lowercase_filenames[a]
+                                .cmp(&lowercase_filenames[b])
+                                .then_with(|| filenames[a].cmp(&filenames[b]))
+                        } else {
+                            filenames[a].cmp(&filenames[b])
+                        };
+
+                        match self.direction {
+                            SortDirection::Ascending => ordering,
+                            SortDirection::Descending => ordering.reverse(),
+                        }
+                    });
+                }
+            }
+            FileOrder::Date => {
+                // `self.dates` would borrow all of self while `shown` is borrowed
+                let dates = match self.date_kind {
+                    DateKind::Modified => &self.modified_dates,
+                    DateKind::Created => &self.created_dates,
+                    DateKind::Accessed => &self.accessed_dates,
+                };
+
+                // Files without a date (created since the index was loaded) sort as the oldest
+                self.shown.par_sort_unstable_by(|&a, &b| {
+                    let ordering = dates[a].cmp(&dates[b]);
+
+                    match self.direction {
+                        SortDirection::Ascending => ordering,
+                        SortDirection::Descending => ordering.reverse(),
+                    }
+                });
+            }
+            FileOrder::Score => {
+                // `self.score` would borrow all of self while `shown` is borrowed
+                let scores = &self.scores;
+                let score = |position: &usize| scores.get(position).copied().unwrap_or(0);
+
+                self.shown.par_sort_unstable_by(|a, b| {
+                    let ordering = score(a).cmp(&score(b));
+
+                    match self.direction {
+                        SortDirection::Ascending => ordering,
+                        SortDirection::Descending => ordering.reverse(),
+                    }
+                });
+            }
+            FileOrder::Size => {
+                self.shown.par_sort_unstable_by(|&a, &b| {
+                    let ordering = self.filesizes[a].cmp(&self.filesizes[b]);
+
+                    match self.direction {
+                        SortDirection::Ascending => ordering,
                         SortDirection::Descending => ordering.reverse(),
                     }
                 });
             }
-            FileOrder::ModifedDate => todo!(),
-            FileOrder::Size => {
-                self.shown.par_sort_unstable_by(|&a, &b| {
-                    let ordering = self.filesizes[a].cmp(&self.filesizes[b]);
+            FileOrder::Path => {
+                // Building a path per comparison would be far too slow so build them all once
+                let mut keyed: Vec<(Box<str>, usize)> = self
+                    .shown
+                    .par_iter()
+                    .map(|&position| {
+                        let path = match &self.lowercase_paths {
+                            Some(paths) => paths[position].clone(),
+                            None => self.lowercase_path(position),
+                        };
+
+                        (path, position)
+                    })
+                    .collect();
+
+                keyed.par_sort_unstable_by(|(a, _), (b, _)| {
+                    let ordering = a.cmp(b);
 
                     match self.direction {
                         SortDirection::Ascending => ordering,
                         SortDirection::Descending => ordering.reverse(),
                     }
                 });
+
+                self.shown = keyed.into_iter().map(|(_, position)| position).collect();
+            }
+        });
+
+        self.move_pinned_to_top();
+
+        debug!("Sorting took {:?}", start.elapsed());
+    }
+
+    // Flips the sort direction without a full sort, the pinned files stay at the top
+    pub fn reverse(&mut self) {
+        self.direction = if self.direction == SortDirection::Ascending {
+            SortDirection::Descending
+        } else {
+            SortDirection::Ascending
+        };
+
+        // The pinned files are at the start so each group can be reversed in place
+        let pinned_count = self
+            .shown
+            .iter()
+            .take_while(|&&position| self.is_pinned(position))
+            .count();
+
+        let (pinned, unpinned) = self.shown.split_at_mut(pinned_count);
+        pinned.reverse();
+        unpinned.reverse();
+    }
+
+    // Keeps the order within the pinned and unpinned files
+    fn move_pinned_to_top(&mut self) {
+        if self.pinned.is_empty() {
+            return;
+        }
+
+        let (mut pinned, unpinned): (Vec<usize>, Vec<usize>) = self
+            .shown
+            .iter()
+            .partition(|&&position| self.is_pinned(position));
+
+        pinned.extend(unpinned);
+        self.shown = pinned;
+    }
+
+    pub fn is_pinned(&self, position: usize) -> bool {
+        self.pinned.contains(&self.frn_mapping[position])
+    }
+
+    // Finds the position of an absolute path such as C:\Users\me\file.txt if it is in the index
+    pub fn find_path(&self, path: &Path) -> Option<usize> {
+        let volume_path = lowercase(&self.volume_path.to_string_lossy(), self.unicode_case);
+        let path = lowercase(&path.to_string_lossy(), self.unicode_case);
+
+        let relative = path.strip_prefix(&volume_path)?.trim_end_matches('\\');
+
+        if relative.is_empty() {
+            return None;
+        }
+
+        let (parent, filename) = relative.rsplit_once('\\').unwrap_or(("", relative));
+        let parent = format!("{volume_path}{parent}");
+
+        // Several files can share a name so check the parent of each candidate
+        self.pool.install(|| {
+            (0..self.filenames.len())
+                .into_par_iter()
+                .filter(|&i| self.lowercase_filename(i) == filename)
+                .find_any(|&i| {
+                    self.path(i).is_some_and(|path| {
+                        lowercase(&path.to_string_lossy(), self.unicode_case) == parent
+                    })
+                })
+        })
+    }
+
+    // Number of folders between the file and the root, stops at the same places as `path`
+    pub fn depth(&self, position: usize) -> usize {
+        let mut position = position;
+        let mut depth = 0;
+
+        while depth <= MAX_PATH_DEPTH {
+            let parent = self.parent_mapping[position];
+
+            if parent == self.root_frn {
+                break;
+            }
+
+            match self.position_of(parent) {
+                Some(parent_position) if parent_position != position => {
+                    position = parent_position;
+                    depth += 1;
+                }
+                _ => break,
             }
         }
 
-        println!("Sorting took: {:?}", start.elapsed());
+        depth
     }
 
-    pub fn path(&self, position: usize) -> PathBuf {
+    // Path of the folder the file is in, None if a folder above it isn't indexed, e.g. its create
+    // hasn't arrived from the journal yet, or the parent chain is corrupt
+    pub fn path(&self, position: usize) -> Option<PathBuf> {
         let mut filename_position = position;
 
         let mut components = Vec::new();
@@ -254,12 +2456,18 @@ impl FileSystem {
         loop {
             let parent = self.parent_mapping[filename_position];
 
-            // Inode #5 is the NTFS root directory
-            if parent == 5 {
+            if parent == self.root_frn {
                 break;
             }
 
-            filename_position = self.position_mapping[parent as usize];
+            let parent_position = self.position_of(parent)?;
+
+            // A corrupt parent chain could loop forever
+            if parent_position == filename_position || components.len() > MAX_PATH_DEPTH {
+                return None;
+            }
+
+            filename_position = parent_position;
 
             // Not worth using .get_unchecked
             let parent_filename = &self.filenames[filename_position];
@@ -272,6 +2480,852 @@ impl FileSystem {
             path.push(&***comp);
         }
 
-        path
+        Some(path)
+    }
+
+    pub fn full_path(&self, position: usize) -> Option<PathBuf> {
+        Some(self.path(position)?.join(&*self.filenames[position]))
+    }
+
+    // The folder's path for showing, with a placeholder if it can't be built
+    pub fn display_path(&self, position: usize) -> String {
+        self.path(position).map_or_else(
+            || UNKNOWN_FOLDER.to_string(),
+            |path| path.to_string_lossy().into_owned(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, os::windows::ffi::OsStringExt};
+
+    use windows::Win32::Storage::FileSystem::{FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_READONLY};
+
+    use super::*;
+
+    // Not a real drive so reading the attributes of created files always fails
+    const VOLUME: &str = r"Q:\";
+
+    fn options() -> IndexOptions {
+        IndexOptions {
+            low_memory: false,
+            unicode_case: false,
+            modified_since: None,
+            short_names: false,
+        }
+    }
+
+    fn empty_index() -> FileSystem {
+        FileSystem::new(PathBuf::from(VOLUME), options(), 16)
+    }
+
+    fn add(filesystem: &mut FileSystem, frn: u64, parent: u64, name: &str) -> usize {
+        filesystem.create(FileId::Normal(frn), FileId::Normal(parent), Path::new(name));
+        filesystem
+            .position_of(frn)
+            .expect("created file isn't indexed")
+    }
+
+    fn add_folder(filesystem: &mut FileSystem, frn: u64, parent: u64, name: &str) -> usize {
+        let position = add(filesystem, frn, parent, name);
+        filesystem.attributes[position] |= FILE_ATTRIBUTE_DIRECTORY.0;
+        position
+    }
+
+    fn shown_names(filesystem: &FileSystem) -> Vec<&str> {
+        let mut names: Vec<&str> = filesystem
+            .shown
+            .iter()
+            .map(|&position| &*filesystem.filenames[position])
+            .collect();
+
+        names.sort_unstable();
+        names
+    }
+
+    fn assert_vecs_in_step(filesystem: &FileSystem) {
+        let files = filesystem.filenames.len();
+
+        assert_eq!(filesystem.lowercase_filenames.len(), files);
+        assert_eq!(filesystem.frn_mapping.len(), files);
+        assert_eq!(filesystem.parent_mapping.len(), files);
+        assert_eq!(filesystem.filesizes.len(), files);
+        assert_eq!(filesystem.modified_dates.len(), files);
+        assert_eq!(filesystem.created_dates.len(), files);
+        assert_eq!(filesystem.accessed_dates.len(), files);
+        assert_eq!(filesystem.attributes.len(), files);
+        assert_eq!(filesystem.security_ids.len(), files);
+    }
+
+    const RECORD_SIZE: usize = 1024;
+
+    // A FILE record with resident attributes, each given as (type, value)
+    fn mft_record(attributes: &[(NtfsAttributeType, Vec<u8>)]) -> Vec<u8> {
+        let mut record = vec![0u8; RECORD_SIZE];
+        record[0..4].copy_from_slice(b"FILE");
+        // Update sequence array at 0x30 with one entry per 512 byte sector and the number
+        record[4..6].copy_from_slice(&0x30u16.to_le_bytes());
+        record[6..8].copy_from_slice(&3u16.to_le_bytes());
+        record[0x14..0x16].copy_from_slice(&0x38u16.to_le_bytes());
+        record[0x16..0x18].copy_from_slice(&1u16.to_le_bytes());
+
+        let mut offset = 0x38;
+
+        for (type_id, value) in attributes {
+            // Header, value and padding to 8 bytes
+            let length = (0x18 + value.len()).next_multiple_of(8);
+
+            record[offset..offset + 4].copy_from_slice(&(*type_id as u32).to_le_bytes());
+            record[offset + 4..offset + 8].copy_from_slice(&(length as u32).to_le_bytes());
+            record[offset + 0x10..offset + 0x14]
+                .copy_from_slice(&(value.len() as u32).to_le_bytes());
+            record[offset + 0x14..offset + 0x16].copy_from_slice(&0x18u16.to_le_bytes());
+            record[offset + 0x18..offset + 0x18 + value.len()].copy_from_slice(value);
+
+            offset += length;
+        }
+
+        record[offset..offset + 4].copy_from_slice(&(NtfsAttributeType::End as u32).to_le_bytes());
+        record[0x18..0x1C].copy_from_slice(&(offset as u32 + 8).to_le_bytes());
+        record[0x1C..0x20].copy_from_slice(&(RECORD_SIZE as u32).to_le_bytes());
+
+        record
+    }
+
+    fn file_name_value(name: &str, namespace: NtfsFileNamespace) -> Vec<u8> {
+        let name: Vec<u16> = name.encode_utf16().collect();
+
+        let mut value = vec![0u8; 0x42];
+        value[0..8].copy_from_slice(&ROOT_FRN.to_le_bytes());
+        value[0x40] = name.len() as u8;
+        value[0x41] = namespace as u8;
+        value.extend(name.iter().flat_map(|c| c.to_le_bytes()));
+
+        value
+    }
+
+    fn attribute_list_entry(type_id: NtfsAttributeType, record: u64) -> Vec<u8> {
+        let mut entry = vec![0u8; 0x20];
+        entry[0..4].copy_from_slice(&(type_id as u32).to_le_bytes());
+        entry[4..6].copy_from_slice(&0x20u16.to_le_bytes());
+        entry[0x10..0x18].copy_from_slice(&record.to_le_bytes());
+        entry
+    }
+
+    #[test]
+    fn win32_name_in_an_extension_record_beats_the_dos_name() {
+        const BASE: u64 = 40;
+        const EXTENSION: u64 = 41;
+
+        let mut attribute_list = attribute_list_entry(NtfsAttributeType::FileName, BASE);
+        attribute_list.extend(attribute_list_entry(NtfsAttributeType::FileName, EXTENSION));
+
+        let base = mft_record(&[
+            (
+                NtfsAttributeType::FileName,
+                file_name_value("LONGFI~1.TXT", NtfsFileNamespace::Dos),
+            ),
+            (NtfsAttributeType::AttributeList, attribute_list),
+        ]);
+        let extension = mft_record(&[(
+            NtfsAttributeType::FileName,
+            file_name_value("Long file name.txt", NtfsFileNamespace::Win32),
+        )]);
+
+        let file = NtfsFile::new(BASE, &base);
+        let (name, short_name) = best_file_name(&file, |number| {
+            (number == EXTENSION).then(|| NtfsFile::new(EXTENSION, &extension))
+        })
+        .expect("has a name");
+
+        assert_eq!(name.to_string(), "Long file name.txt");
+        assert_eq!(
+            short_name.map(|name| name.to_string()).as_deref(),
+            Some("LONGFI~1.TXT")
+        );
+    }
+
+    #[test]
+    fn unpaired_surrogate_is_replaced_the_same_way_in_both_names() {
+        let mut filesystem = empty_index();
+
+        // "Ab" with a lone high surrogate between the letters
+        let name = OsString::from_wide(&[0x41, 0xD800, 0x62]);
+        filesystem.create(
+            FileId::Normal(20),
+            FileId::Normal(ROOT_FRN),
+            Path::new(&name),
+        );
+
+        let position = filesystem.position_of(20).unwrap();
+        assert_eq!(&*filesystem.filenames[position], "A\u{FFFD}b");
+        assert_eq!(filesystem.lowercase_filename(position), "a\u{FFFD}b");
+
+        filesystem.search("\u{FFFD}B", &FilterState::default());
+        assert_eq!(filesystem.shown, [position]);
+
+        filesystem.set_unicode_case(true);
+        assert_eq!(filesystem.lowercase_filename(position), "a\u{FFFD}b");
+    }
+
+    #[test]
+    fn rename_without_a_file_name_leaves_the_entry_alone() {
+        let mut filesystem = empty_index();
+        filesystem.build_children();
+
+        add_folder(&mut filesystem, 20, ROOT_FRN, "src");
+        add_folder(&mut filesystem, 21, ROOT_FRN, "docs");
+        let file = add(&mut filesystem, 22, 20, "main.rs");
+
+        for path in [r"Q:\", ".."] {
+            filesystem.rename(FileId::Normal(22), FileId::Normal(21), Path::new(path));
+
+            assert_eq!(&*filesystem.filenames[file], "main.rs");
+            assert_eq!(filesystem.parent_mapping[file], 20);
+            assert_eq!(
+                filesystem.full_path(file),
+                Some(PathBuf::from(r"Q:\src\main.rs"))
+            );
+
+            let children = filesystem.children.as_ref().unwrap();
+            assert_eq!(children[&20], [22]);
+            assert!(!children
+                .get(&21)
+                .is_some_and(|children| children.contains(&22)));
+        }
+
+        // A name is enough to move it
+        filesystem.rename(FileId::Normal(22), FileId::Normal(21), Path::new("lib.rs"));
+        assert_eq!(
+            filesystem.full_path(file),
+            Some(PathBuf::from(r"Q:\docs\lib.rs"))
+        );
+    }
+
+    #[test]
+    fn only_searches_containing_the_previous_one_narrow() {
+        for unicode_case in [false, true] {
+            assert!(narrows("foo", "fo", unicode_case, false));
+            assert!(narrows("xfoo", "foo", unicode_case, false));
+            // An edit in the middle or a deletion can match files the previous search didn't
+            assert!(!narrows("fxoo", "foo", unicode_case, false));
+            assert!(!narrows("fo", "foo", unicode_case, false));
+            assert!(!narrows("foo", "", unicode_case, false));
+        }
+    }
+
+    #[test]
+    fn narrowing_compares_searches_case_insensitively() {
+        for unicode_case in [false, true] {
+            assert!(narrows("ab", "A", unicode_case, false));
+            assert!(narrows("AB", "a", unicode_case, false));
+        }
+
+        // Without Unicode case only ASCII letters are folded
+        assert!(narrows("ÄB", "ä", true, false));
+        assert!(!narrows("ÄB", "ä", false, false));
+    }
+
+    #[test]
+    fn matching_case_only_narrows_the_same_case() {
+        assert!(narrows("Abc", "Ab", false, true));
+        assert!(!narrows("ab", "A", false, true));
+        assert!(!narrows("aBc", "Ab", false, true));
+    }
+
+    #[test]
+    fn typing_the_first_backslash_doesnt_narrow() {
+        assert!(!narrows(r"src\", "src", false, false));
+        assert!(!narrows("src", r"src\", false, false));
+        assert!(narrows(r"src\main", r"src\", false, false));
+    }
+
+    #[test]
+    fn folder_size_count_carries_on_after_the_deadline() {
+        let mut filesystem = empty_index();
+        filesystem.build_children();
+
+        let mut parent = ROOT_FRN;
+
+        for frn in 20..30 {
+            add_folder(&mut filesystem, frn, parent, "folder");
+            let file = add(&mut filesystem, frn + 100, frn, "file.bin");
+            filesystem.filesizes[file] = 10;
+            parent = frn;
+        }
+
+        // Every call is past the deadline so each one only counts a folder
+        let mut calls = 1;
+        let total = loop {
+            match filesystem.count_folder_size(20, Instant::now()) {
+                Some(total) => break total,
+                None => calls += 1,
+            }
+        };
+
+        assert_eq!(total, 100);
+        assert!(calls > 1);
+        assert_eq!(filesystem.folder_size(25), Some(50));
+        assert!(filesystem.folder_size_count.is_none());
+    }
+
+    #[test]
+    fn changes_restart_an_unfinished_folder_size_count() {
+        let mut filesystem = empty_index();
+        filesystem.build_children();
+
+        add_folder(&mut filesystem, 20, ROOT_FRN, "outer");
+        add_folder(&mut filesystem, 21, 20, "inner");
+        let file = add(&mut filesystem, 22, 21, "file.bin");
+        filesystem.filesizes[file] = 10;
+
+        assert_eq!(filesystem.count_folder_size(20, Instant::now()), None);
+
+        let added = add(&mut filesystem, 23, 21, "added.bin");
+        filesystem.filesizes[added] = 5;
+        assert!(filesystem.folder_size_count.is_none());
+
+        let far_off = Instant::now() + Duration::from_secs(60);
+        assert_eq!(filesystem.count_folder_size(20, far_off), Some(15));
+    }
+
+    #[test]
+    fn frns_past_the_end_of_the_mapping() {
+        // The mapping only has room for 16 FRNs
+        let mut filesystem = empty_index();
+        filesystem.build_children();
+
+        // Unknown FRNs past the end are ignored
+        assert_eq!(filesystem.position_of(5_000), None);
+        filesystem.rename(
+            FileId::Normal(5_000),
+            FileId::Normal(ROOT_FRN),
+            Path::new("ghost.txt"),
+        );
+        filesystem.delete(FileId::Normal(5_000));
+        assert_eq!(filesystem.position_of(5_000), None);
+
+        add_folder(&mut filesystem, 1_000, ROOT_FRN, "far");
+        let file = add(&mut filesystem, 2_000, 1_000, "away.txt");
+        assert_vecs_in_step(&filesystem);
+        assert_eq!(
+            filesystem.full_path(file),
+            Some(PathBuf::from(r"Q:\far\away.txt"))
+        );
+
+        filesystem.rename(
+            FileId::Normal(2_000),
+            FileId::Normal(ROOT_FRN),
+            Path::new("moved.txt"),
+        );
+        assert_eq!(filesystem.position_of(2_000), Some(file));
+        assert_eq!(
+            filesystem.full_path(file),
+            Some(PathBuf::from(r"Q:\moved.txt"))
+        );
+
+        filesystem.delete(FileId::Normal(2_000));
+        filesystem.delete(FileId::Normal(1_000));
+        assert_eq!(filesystem.position_of(2_000), None);
+        assert_eq!(filesystem.position_of(1_000), None);
+        assert!(filesystem.filenames.is_empty());
+        assert_vecs_in_step(&filesystem);
+    }
+
+    #[test]
+    fn record_name_is_the_leaf_of_a_name_or_a_full_path() {
+        assert_eq!(
+            record_name(Path::new("main.rs")).as_deref(),
+            Some("main.rs")
+        );
+        assert_eq!(
+            record_name(Path::new(r"Q:\src\main.rs")).as_deref(),
+            Some("main.rs")
+        );
+        assert_eq!(record_name(Path::new(r"Q:\")), None);
+
+        let mut filesystem = empty_index();
+        add_folder(&mut filesystem, 20, ROOT_FRN, "src");
+
+        let from_name = add(&mut filesystem, 21, 20, "main.rs");
+        let from_path = add(&mut filesystem, 22, 20, r"Q:\src\lib.rs");
+
+        assert_eq!(&*filesystem.filenames[from_name], "main.rs");
+        assert_eq!(&*filesystem.filenames[from_path], "lib.rs");
+        assert_eq!(
+            filesystem.full_path(from_name),
+            Some(PathBuf::from(r"Q:\src\main.rs"))
+        );
+        assert_eq!(
+            filesystem.full_path(from_path),
+            Some(PathBuf::from(r"Q:\src\lib.rs"))
+        );
+    }
+
+    #[test]
+    fn bare_names_are_placed_in_their_folders_path() {
+        let mut filesystem = empty_index();
+        filesystem.volume_path = PathBuf::from(r"Q:\Projects");
+        filesystem.root_frn = 40;
+
+        add_folder(&mut filesystem, 41, 40, "app");
+        add_folder(&mut filesystem, 42, 41, "src");
+
+        assert_eq!(
+            filesystem.folder_path(40),
+            Some(PathBuf::from(r"Q:\Projects"))
+        );
+        assert_eq!(
+            filesystem.folder_path(42),
+            Some(PathBuf::from(r"Q:\Projects\app\src"))
+        );
+        // Not indexed, so the attributes of a file created in it aren't read
+        assert_eq!(filesystem.folder_path(43), None);
+    }
+
+    fn filtered_index() -> FileSystem {
+        let mut filesystem = empty_index();
+
+        for (frn, name, size, attributes) in [
+            (20, "report.txt", 100, 0),
+            (21, "Report.md", 200, 0),
+            (22, "report.log", 0, 0),
+            (23, "old report.txt", 300, FILE_ATTRIBUTE_HIDDEN.0),
+            (24, "notes.txt", 400, 0),
+            (25, "REPORT.txt", 500, FILE_ATTRIBUTE_READONLY.0),
+        ] {
+            let position = add(&mut filesystem, frn, ROOT_FRN, name);
+            filesystem.filesizes[position] = size;
+            filesystem.attributes[position] = attributes;
+        }
+
+        filesystem
+    }
+
+    #[test]
+    fn filters_combine_with_the_search_text() {
+        let mut filesystem = filtered_index();
+
+        let mut filters = FilterState {
+            excluded_attributes: FILE_ATTRIBUTE_HIDDEN.0,
+            empty_filter: EmptyFilter::HideEmpty,
+            ..FilterState::default()
+        };
+        filters.hidden_extensions.insert("md".into());
+
+        filesystem.search("report", &filters);
+        assert_eq!(shown_names(&filesystem), ["REPORT.txt", "report.txt"]);
+
+        // The filters stay when only the text changes
+        filesystem.search("report.t", &filters);
+        assert_eq!(shown_names(&filesystem), ["REPORT.txt", "report.txt"]);
+
+        filesystem.search("report size:>200", &filters);
+        assert_eq!(shown_names(&filesystem), ["REPORT.txt"]);
+
+        filesystem.search("report", &FilterState::default());
+        assert_eq!(
+            shown_names(&filesystem),
+            [
+                "REPORT.txt",
+                "Report.md",
+                "old report.txt",
+                "report.log",
+                "report.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn narrowed_searches_keep_the_filters() {
+        let mut filesystem = filtered_index();
+
+        let filters = FilterState {
+            required_attributes: FILE_ATTRIBUTE_READONLY.0,
+            empty_filter: EmptyFilter::HideEmpty,
+            match_case: true,
+            ..FilterState::default()
+        };
+
+        filesystem.search("REP", &FilterState::default());
+        filesystem.search_shown("REPORT", &filters);
+        assert_eq!(shown_names(&filesystem), ["REPORT.txt"]);
+    }
+
+    #[test]
+    fn matching_case_applies_to_every_matcher() {
+        let mut filesystem = filtered_index();
+
+        let filters = FilterState {
+            excluded_attributes: FILE_ATTRIBUTE_HIDDEN.0,
+            match_case: true,
+            ..FilterState::default()
+        };
+
+        filesystem.search("Report", &filters);
+        assert_eq!(shown_names(&filesystem), ["Report.md"]);
+
+        filesystem.search(r"Q:\rep", &filters);
+        assert_eq!(shown_names(&filesystem), ["report.log", "report.txt"]);
+
+        filesystem.wildcards = true;
+        filesystem.search("REPORT.*", &filters);
+        assert_eq!(shown_names(&filesystem), ["REPORT.txt"]);
+
+        filesystem.wildcards = false;
+        filesystem.fuzzy = true;
+        filesystem.search("rpt", &filters);
+        assert_eq!(shown_names(&filesystem), ["report.log", "report.txt"]);
+    }
+
+    #[test]
+    fn names_sort_the_same_way_in_low_memory_mode() {
+        for low_memory in [false, true] {
+            let mut filesystem = FileSystem::new(
+                PathBuf::from(VOLUME),
+                IndexOptions {
+                    low_memory,
+                    ..options()
+                },
+                16,
+            );
+
+            for (frn, name) in [(20, "banana"), (21, "apple"), (22, "Cherry"), (23, "Apple")] {
+                add(&mut filesystem, frn, ROOT_FRN, name);
+            }
+
+            let sorted_names = |filesystem: &FileSystem| -> Vec<String> {
+                filesystem
+                    .shown
+                    .iter()
+                    .map(|&position| filesystem.filenames[position].to_string())
+                    .collect()
+            };
+
+            filesystem.shown = (0..4).collect();
+            filesystem.order = FileOrder::Name;
+            filesystem.direction = SortDirection::Ascending;
+            filesystem.sort();
+            assert_eq!(
+                sorted_names(&filesystem),
+                ["Apple", "apple", "banana", "Cherry"]
+            );
+
+            filesystem.direction = SortDirection::Descending;
+            filesystem.sort();
+            assert_eq!(
+                sorted_names(&filesystem),
+                ["Cherry", "banana", "apple", "Apple"]
+            );
+        }
+    }
+
+    #[test]
+    fn changed_files_are_held_back_until_applied() {
+        let mut filesystem = filtered_index();
+        let filters = FilterState {
+            empty_filter: EmptyFilter::HideEmpty,
+            ..FilterState::default()
+        };
+
+        filesystem.search("notes", &filters);
+        assert_eq!(shown_names(&filesystem), ["notes.txt"]);
+
+        let created = add(&mut filesystem, 30, ROOT_FRN, "notes.md");
+        filesystem.filesizes[created] = 10;
+        filesystem.rename(
+            FileId::Normal(24),
+            FileId::Normal(ROOT_FRN),
+            Path::new("todo.txt"),
+        );
+        assert_eq!(shown_names(&filesystem), ["todo.txt"]);
+
+        assert!(filesystem.belongs_in_shown(created));
+        assert!(!filesystem.belongs_in_shown(filesystem.position_of(24).unwrap()));
+        // Doesn't match the search
+        assert!(!filesystem.belongs_in_shown(filesystem.position_of(20).unwrap()));
+
+        filesystem.apply_changes([30, 24]);
+        assert_eq!(shown_names(&filesystem), ["notes.md"]);
+
+        // The filters are checked too
+        let empty = add(&mut filesystem, 31, ROOT_FRN, "notes.log");
+        assert!(!filesystem.belongs_in_shown(empty));
+        filesystem.apply_changes([31]);
+        assert_eq!(shown_names(&filesystem), ["notes.md"]);
+    }
+
+    #[test]
+    fn changed_files_arent_added_to_files_picked_by_hand() {
+        let mut filesystem = filtered_index();
+
+        filesystem.search("report", &FilterState::default());
+        let first = filesystem.shown[0];
+        filesystem.set_shown(vec![first]);
+
+        let created = add(&mut filesystem, 30, ROOT_FRN, "report.new");
+        assert!(!filesystem.belongs_in_shown(created));
+
+        filesystem.apply_changes([30]);
+        assert_eq!(filesystem.shown, [first]);
+    }
+
+    #[test]
+    fn changed_files_in_a_browsed_folder() {
+        let mut filesystem = empty_index();
+
+        add_folder(&mut filesystem, 20, ROOT_FRN, "src");
+        add(&mut filesystem, 21, 20, "main.rs");
+        filesystem.show_children(20, &FilterState::default());
+
+        let inside = add(&mut filesystem, 22, 20, "lib.rs");
+        let outside = add(&mut filesystem, 23, ROOT_FRN, "lib.rs");
+        assert!(filesystem.belongs_in_shown(inside));
+        assert!(!filesystem.belongs_in_shown(outside));
+
+        filesystem.apply_changes([22, 23]);
+        assert_eq!(shown_names(&filesystem), ["lib.rs", "main.rs"]);
+    }
+
+    #[test]
+    fn sizes_and_dates_follow_their_files_on_delete() {
+        let mut filesystem = empty_index();
+
+        add(&mut filesystem, 20, ROOT_FRN, "first.txt");
+        add(&mut filesystem, 21, ROOT_FRN, "second.txt");
+        let last = add(&mut filesystem, 22, ROOT_FRN, "third.txt");
+
+        filesystem.filesizes[last] = 100;
+        filesystem.modified_dates[last] = Some(42);
+
+        // The last file is moved into the deleted one's position
+        filesystem.delete(FileId::Normal(20));
+        assert_vecs_in_step(&filesystem);
+
+        let moved = filesystem.position_of(22).unwrap();
+        assert_eq!(&*filesystem.filenames[moved], "third.txt");
+        assert_eq!(filesystem.filesizes[moved], 100);
+        assert_eq!(filesystem.modified_dates[moved], Some(42));
+
+        filesystem.delete(FileId::Normal(22));
+        assert_vecs_in_step(&filesystem);
+        assert_eq!(filesystem.position_of(22), None);
+    }
+
+    #[test]
+    fn scores_follow_their_files_on_delete() {
+        let mut filesystem = empty_index();
+
+        add(&mut filesystem, 20, ROOT_FRN, "first.txt");
+        add(&mut filesystem, 21, ROOT_FRN, "second.txt");
+        add(&mut filesystem, 22, ROOT_FRN, "third.txt");
+
+        filesystem.scores.extend([(0, 10), (1, 20), (2, 30)]);
+
+        // The last file is moved into the deleted one's position
+        filesystem.delete(FileId::Normal(20));
+
+        let moved = filesystem.position_of(22).unwrap();
+        let kept = filesystem.position_of(21).unwrap();
+        assert_eq!(filesystem.score(moved), 30);
+        assert_eq!(filesystem.score(kept), 20);
+        assert_eq!(filesystem.scores.len(), 2);
+
+        filesystem.delete(FileId::Normal(22));
+        assert_eq!(filesystem.scores.len(), 1);
+        assert_eq!(filesystem.score(filesystem.position_of(21).unwrap()), 20);
+
+        // The last file itself, nothing is moved
+        filesystem.delete(FileId::Normal(21));
+        assert!(filesystem.scores.is_empty());
+    }
+
+    #[test]
+    fn selection_by_frn_survives_deleting_another_file() {
+        let mut filesystem = empty_index();
+
+        add(&mut filesystem, 20, ROOT_FRN, "first.txt");
+        add(&mut filesystem, 21, ROOT_FRN, "second.txt");
+        let selected_position = add(&mut filesystem, 22, ROOT_FRN, "selected.txt");
+        let selected = filesystem.frn_mapping[selected_position];
+
+        // The selected file is the last one so it's moved into the deleted one's position
+        filesystem.delete(FileId::Normal(20));
+
+        let position = filesystem.position_of(selected).unwrap();
+        assert_ne!(position, selected_position);
+        assert_eq!(&*filesystem.filenames[position], "selected.txt");
+        assert_eq!(
+            filesystem.full_path(position),
+            Some(PathBuf::from(r"Q:\selected.txt"))
+        );
+
+        // Its old position is now past the end
+        assert!(selected_position >= filesystem.filenames.len());
+
+        filesystem.delete(FileId::Normal(21));
+        let position = filesystem.position_of(selected).unwrap();
+        assert_eq!(&*filesystem.filenames[position], "selected.txt");
+
+        filesystem.delete(FileId::Normal(22));
+        assert_eq!(filesystem.position_of(selected), None);
+    }
+
+    #[test]
+    fn paths_are_built_up_to_a_root_that_isnt_the_drive_root() {
+        let mut filesystem = empty_index();
+        filesystem.volume_path = PathBuf::from(r"Q:\Projects");
+        filesystem.root_frn = 40;
+
+        add_folder(&mut filesystem, 41, 40, "app");
+        let file = add(&mut filesystem, 42, 41, "main.rs");
+
+        assert_eq!(
+            filesystem.path(file),
+            Some(PathBuf::from(r"Q:\Projects\app"))
+        );
+        assert_eq!(
+            filesystem.full_path(file),
+            Some(PathBuf::from(r"Q:\Projects\app\main.rs"))
+        );
+        assert_eq!(filesystem.depth(file), 1);
+    }
+
+    #[test]
+    fn no_path_without_an_indexed_parent() {
+        let mut filesystem = empty_index();
+
+        // FRN 60 was never created
+        let orphan = add(&mut filesystem, 50, 60, "orphan.txt");
+        assert_eq!(filesystem.path(orphan), None);
+        assert_eq!(filesystem.full_path(orphan), None);
+        assert_eq!(filesystem.display_path(orphan), UNKNOWN_FOLDER);
+
+        // A folder that is its own parent
+        let looped = add(&mut filesystem, 51, ROOT_FRN, "looped");
+        filesystem.parent_mapping[looped] = 51;
+        assert_eq!(filesystem.path(looped), None);
+    }
+
+    #[test]
+    fn files_created_before_their_folder_get_its_path() {
+        let mut filesystem = empty_index();
+        filesystem.build_lowercase_paths();
+
+        let file = add(&mut filesystem, 31, 30, "Main.rs");
+        assert_eq!(filesystem.full_path(file), None);
+        assert_eq!(&*filesystem.lowercase_path(file), "main.rs");
+
+        add_folder(&mut filesystem, 30, ROOT_FRN, "src");
+
+        assert_eq!(
+            filesystem.full_path(file),
+            Some(PathBuf::from(r"Q:\src\Main.rs"))
+        );
+        // The cached paths were built without the folder
+        assert!(filesystem.lowercase_paths.is_none());
+        assert!(filesystem.missing_parents.is_empty());
+
+        filesystem.search(r"q:\src\", &FilterState::default());
+        assert_eq!(shown_names(&filesystem), ["Main.rs"]);
+    }
+
+    #[test]
+    fn missing_parents_are_capped() {
+        let mut filesystem = empty_index();
+
+        for frn in 0..MAX_MISSING_PARENTS as u64 + 10 {
+            add(&mut filesystem, 100_000 + frn, 200_000 + frn, "orphan");
+        }
+
+        assert_eq!(filesystem.missing_parents.len(), MAX_MISSING_PARENTS);
+    }
+
+    #[test]
+    fn deleted_folder_has_no_children_entry() {
+        let mut filesystem = empty_index();
+        filesystem.build_children();
+
+        add_folder(&mut filesystem, 20, ROOT_FRN, "old");
+        add(&mut filesystem, 21, 20, "file.txt");
+
+        filesystem.delete(FileId::Normal(21));
+        filesystem.delete(FileId::Normal(20));
+
+        let children = filesystem.children.as_ref().unwrap();
+        assert!(!children.contains_key(&20));
+        assert_eq!(children[&ROOT_FRN], Vec::<u64>::new());
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_ignored() {
+        let mut filesystem = empty_index();
+        add(&mut filesystem, 20, ROOT_FRN, "foo.txt");
+        add(&mut filesystem, 21, ROOT_FRN, "food");
+        add(&mut filesystem, 22, ROOT_FRN, "bar foo");
+        add(&mut filesystem, 23, ROOT_FRN, "bar");
+
+        filesystem.search("  ", &FilterState::default());
+        assert_eq!(
+            shown_names(&filesystem),
+            ["bar", "bar foo", "foo.txt", "food"]
+        );
+
+        filesystem.search(" foo", &FilterState::default());
+        assert_eq!(shown_names(&filesystem), ["bar foo", "foo.txt", "food"]);
+
+        filesystem.search("foo ", &FilterState::default());
+        assert_eq!(shown_names(&filesystem), ["bar foo", "foo.txt", "food"]);
+    }
+
+    #[test]
+    fn reversing_keeps_pinned_files_on_top() {
+        let mut filesystem = empty_index();
+
+        for (frn, name) in [(20, "a"), (21, "b"), (22, "c"), (23, "d"), (24, "e")] {
+            add(&mut filesystem, frn, ROOT_FRN, name);
+        }
+
+        filesystem.pinned.extend([21, 23]);
+        filesystem.shown = (0..5).collect();
+        filesystem.move_pinned_to_top();
+        assert_eq!(filesystem.shown, [1, 3, 0, 2, 4]);
+
+        filesystem.reverse();
+        assert_eq!(filesystem.shown, [3, 1, 4, 2, 0]);
+        assert!(filesystem.direction == SortDirection::Ascending);
+
+        filesystem.reverse();
+        assert_eq!(filesystem.shown, [1, 3, 0, 2, 4]);
+        assert!(filesystem.direction == SortDirection::Descending);
+    }
+
+    #[test]
+    fn subtree_keeps_files_created_before_their_folder() {
+        let mut filesystem = empty_index();
+        filesystem.volume_path = PathBuf::from(r"Q:\Projects");
+        filesystem.root_frn = 20;
+
+        // Created before its folder
+        filesystem.create(FileId::Normal(31), FileId::Normal(30), Path::new("main.rs"));
+        // In a folder outside of the subtree
+        filesystem.create(
+            FileId::Normal(41),
+            FileId::Normal(40),
+            Path::new("elsewhere.txt"),
+        );
+        assert_eq!(filesystem.position_of(31), None);
+        assert_eq!(filesystem.position_of(41), None);
+
+        add_folder(&mut filesystem, 30, 20, "src");
+
+        let file = filesystem.position_of(31).expect("placed with its folder");
+        assert_eq!(
+            filesystem.full_path(file),
+            Some(PathBuf::from(r"Q:\Projects\src\main.rs"))
+        );
+        assert_eq!(filesystem.position_of(41), None);
+        assert_vecs_in_step(&filesystem);
     }
 }
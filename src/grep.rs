@@ -0,0 +1,156 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+};
+
+use eframe::egui;
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+
+// Content search reads every file so it needs the filename search to narrow things down first
+pub const MAX_FILES: usize = 100_000;
+
+// Bigger files are skipped, they are rarely text
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+// A NUL byte this close to the start means the file is binary
+const BINARY_CHECK_SIZE: usize = 8 * 1024;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+// Few threads as the disk is the bottleneck and the UI still needs the rest
+const THREADS: usize = 4;
+
+// Searches the contents of files on worker threads
+pub struct ContentSearch {
+    pub total: usize,
+    searched: Arc<AtomicUsize>,
+    cancelled: Arc<AtomicBool>,
+    rx: Receiver<u64>,
+    // FRNs of the files containing the text
+    matches: Vec<u64>,
+}
+
+impl ContentSearch {
+    // `files` are FRNs with their full paths, positions can change while this runs
+    pub fn start(ctx: egui::Context, files: Vec<(u64, PathBuf)>, text: &str) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        let searched = Arc::new(AtomicUsize::new(0));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let total = files.len();
+        let needle = text.to_ascii_lowercase().into_bytes();
+
+        {
+            let searched = searched.clone();
+            let cancelled = cancelled.clone();
+
+            std::thread::spawn(move || {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(THREADS)
+                    .build()
+                    .expect("failed to build content search thread pool");
+
+                pool.install(|| {
+                    files.par_iter().for_each_with(tx, |tx, (frn, path)| {
+                        if cancelled.load(Ordering::Relaxed) {
+                            return;
+                        }
+
+                        // Unreadable files (no permission, in use, directories) just don't match
+                        if file_contains(path, &needle).unwrap_or(false) {
+                            let _ = tx.send(*frn);
+                        }
+
+                        if searched.fetch_add(1, Ordering::Relaxed) % 256 == 0 {
+                            ctx.request_repaint();
+                        }
+                    });
+                });
+
+                // The sender has been dropped so the next poll sees that it finished
+                ctx.request_repaint();
+            });
+        }
+
+        ContentSearch {
+            total,
+            searched,
+            cancelled,
+            rx,
+            matches: Vec::new(),
+        }
+    }
+
+    pub fn searched(&self) -> usize {
+        self.searched.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    // Returns the FRNs of the matching files once every file has been searched
+    pub fn poll(&mut self) -> Option<Vec<u64>> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(frn) => self.matches.push(frn),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => return Some(std::mem::take(&mut self.matches)),
+            }
+        }
+    }
+}
+
+// Case-insensitive for ASCII, reads in chunks so big files don't have to be loaded at once
+fn file_contains(path: &Path, needle: &[u8]) -> io::Result<bool> {
+    let mut file = File::open(path)?;
+
+    if file.metadata()?.len() > MAX_FILE_SIZE {
+        return Ok(false);
+    }
+
+    if needle.is_empty() {
+        return Ok(true);
+    }
+
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    // The end of the previous chunk is kept so matches across chunks aren't missed
+    let mut kept = 0;
+    let mut first_chunk = true;
+
+    loop {
+        let read = file.read(&mut buffer[kept..])?;
+
+        if read == 0 {
+            return Ok(false);
+        }
+
+        let filled = kept + read;
+
+        if first_chunk {
+            if buffer[..filled.min(BINARY_CHECK_SIZE)].contains(&0) {
+                return Ok(false);
+            }
+            first_chunk = false;
+        }
+
+        buffer[kept..filled].make_ascii_lowercase();
+
+        if buffer[..filled]
+            .windows(needle.len())
+            .any(|window| window == needle)
+        {
+            return Ok(true);
+        }
+
+        kept = (needle.len() - 1).min(filled);
+        buffer.copy_within(filled - kept..filled, 0);
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    fmt::Write,
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+};
+
+use eframe::egui;
+use windows::{
+    core::PCWSTR,
+    Win32::Security::Cryptography::{
+        BCryptCloseAlgorithmProvider, BCryptCreateHash, BCryptDestroyHash, BCryptFinishHash,
+        BCryptHashData, BCryptOpenAlgorithmProvider, BCRYPT_ALG_HANDLE, BCRYPT_HASH_HANDLE,
+        BCRYPT_MD5_ALGORITHM, BCRYPT_OPEN_ALGORITHM_PROVIDER_FLAGS, BCRYPT_SHA1_ALGORITHM,
+        BCRYPT_SHA256_ALGORITHM,
+    },
+};
+
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+#[derive(Clone, Copy)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha1,
+    Md5,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [HashAlgorithm; 3] = [
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha1,
+        HashAlgorithm::Md5,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "SHA-256",
+            HashAlgorithm::Sha1 => "SHA-1",
+            HashAlgorithm::Md5 => "MD5",
+        }
+    }
+
+    fn id(self) -> PCWSTR {
+        match self {
+            HashAlgorithm::Sha256 => BCRYPT_SHA256_ALGORITHM,
+            HashAlgorithm::Sha1 => BCRYPT_SHA1_ALGORITHM,
+            HashAlgorithm::Md5 => BCRYPT_MD5_ALGORITHM,
+        }
+    }
+
+    // In bytes
+    fn length(self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => 32,
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Md5 => 16,
+        }
+    }
+}
+
+// Hashes a file on a worker thread so a big file doesn't freeze the UI
+pub struct FileHash {
+    pub algorithm: HashAlgorithm,
+    pub filename: String,
+    // Size of the file, 0 if it isn't known
+    pub total: u64,
+    hashed: Arc<AtomicU64>,
+    cancelled: Arc<AtomicBool>,
+    rx: Receiver<io::Result<String>>,
+}
+
+impl FileHash {
+    pub fn start(
+        ctx: egui::Context,
+        path: PathBuf,
+        filename: String,
+        total: u64,
+        algorithm: HashAlgorithm,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        let hashed = Arc::new(AtomicU64::new(0));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        {
+            let hashed = hashed.clone();
+            let cancelled = cancelled.clone();
+
+            std::thread::spawn(move || {
+                let result = hash_file(&path, algorithm, &hashed, &cancelled, &ctx);
+
+                // The receiver is gone if it was cancelled
+                let _ = tx.send(result);
+                ctx.request_repaint();
+            });
+        }
+
+        FileHash {
+            algorithm,
+            filename,
+            total,
+            hashed,
+            cancelled,
+            rx,
+        }
+    }
+
+    pub fn hashed(&self) -> u64 {
+        self.hashed.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    // The lowercase hex digest once the whole file has been read
+    pub fn poll(&self) -> Option<io::Result<String>> {
+        self.rx.try_recv().ok()
+    }
+}
+
+fn hash_file(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    hashed: &AtomicU64,
+    cancelled: &AtomicBool,
+    ctx: &egui::Context,
+) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = unsafe { Hasher::new(algorithm) }.map_err(io::Error::other)?;
+
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+
+    loop {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+
+        let read = file.read(&mut buffer)?;
+
+        if read == 0 {
+            break;
+        }
+
+        unsafe { hasher.update(&buffer[..read]) }.map_err(io::Error::other)?;
+
+        hashed.fetch_add(read as u64, Ordering::Relaxed);
+        ctx.request_repaint();
+    }
+
+    let digest = unsafe { hasher.finish() }.map_err(io::Error::other)?;
+
+    let mut hex = String::with_capacity(digest.len() * 2);
+    for byte in digest {
+        let _ = write!(hex, "{byte:02x}");
+    }
+
+    Ok(hex)
+}
+
+// A CNG hash, the handles are closed when dropped
+struct Hasher {
+    algorithm: HashAlgorithm,
+    algorithm_handle: BCRYPT_ALG_HANDLE,
+    hash_handle: BCRYPT_HASH_HANDLE,
+}
+
+impl Hasher {
+    unsafe fn new(algorithm: HashAlgorithm) -> windows::core::Result<Self> {
+        let mut algorithm_handle = BCRYPT_ALG_HANDLE::default();
+
+        BCryptOpenAlgorithmProvider(
+            &mut algorithm_handle,
+            algorithm.id(),
+            PCWSTR::null(),
+            BCRYPT_OPEN_ALGORITHM_PROVIDER_FLAGS(0),
+        )
+        .ok()?;
+
+        let mut hasher = Hasher {
+            algorithm,
+            algorithm_handle,
+            hash_handle: BCRYPT_HASH_HANDLE::default(),
+        };
+
+        // Without a hash object buffer CNG allocates it
+        BCryptCreateHash(algorithm_handle, &mut hasher.hash_handle, None, None, 0).ok()?;
+
+        Ok(hasher)
+    }
+
+    unsafe fn update(&mut self, data: &[u8]) -> windows::core::Result<()> {
+        BCryptHashData(self.hash_handle, data, 0).ok()
+    }
+
+    unsafe fn finish(&mut self) -> windows::core::Result<Vec<u8>> {
+        let mut digest = vec![0u8; self.algorithm.length()];
+
+        BCryptFinishHash(self.hash_handle, &mut digest, 0).ok()?;
+
+        Ok(digest)
+    }
+}
+
+impl Drop for Hasher {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.hash_handle.is_invalid() {
+                let _ = BCryptDestroyHash(self.hash_handle);
+            }
+
+            let _ = BCryptCloseAlgorithmProvider(self.algorithm_handle, 0);
+        }
+    }
+}
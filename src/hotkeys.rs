@@ -0,0 +1,78 @@
+use eframe::egui::{Event, Key, KeyboardShortcut, Modifiers};
+use serde::{Deserialize, Serialize};
+
+// Something that can be done to the selected file from the keyboard
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Open,
+    Reveal,
+    CopyPath,
+    Rename,
+    Recycle,
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [
+        Action::Open,
+        Action::Reveal,
+        Action::CopyPath,
+        Action::Rename,
+        Action::Recycle,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Open => "Open",
+            Action::Reveal => "Open containing folder",
+            Action::CopyPath => "Copy full path",
+            Action::Rename => "Rename",
+            Action::Recycle => "Move to Recycle Bin",
+        }
+    }
+}
+
+pub fn default_hotkeys() -> Vec<(KeyboardShortcut, Action)> {
+    vec![
+        (
+            KeyboardShortcut::new(Modifiers::NONE, Key::Enter),
+            Action::Open,
+        ),
+        (
+            KeyboardShortcut::new(Modifiers::CTRL | Modifiers::SHIFT, Key::Enter),
+            Action::Reveal,
+        ),
+        (
+            KeyboardShortcut::new(Modifiers::CTRL, Key::C),
+            Action::CopyPath,
+        ),
+        (
+            KeyboardShortcut::new(Modifiers::NONE, Key::F2),
+            Action::Rename,
+        ),
+        (
+            KeyboardShortcut::new(Modifiers::NONE, Key::Delete),
+            Action::Recycle,
+        ),
+    ]
+}
+
+// Ctrl+C, Ctrl+X and Ctrl+V arrive as clipboard events instead of key presses
+pub fn clipboard_key(event: &Event) -> Option<Key> {
+    match event {
+        Event::Copy => Some(Key::C),
+        Event::Cut => Some(Key::X),
+        Event::Paste(_) => Some(Key::V),
+        _ => None,
+    }
+}
+
+// The shortcut bound to an action, for showing next to it in menus
+pub fn shortcut_for(
+    hotkeys: &[(KeyboardShortcut, Action)],
+    action: Action,
+) -> Option<&KeyboardShortcut> {
+    hotkeys
+        .iter()
+        .find(|(_, bound)| *bound == action)
+        .map(|(shortcut, _)| shortcut)
+}
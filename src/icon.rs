@@ -9,18 +9,50 @@ use windows::{
         },
         Storage::FileSystem::FILE_FLAGS_AND_ATTRIBUTES,
         UI::{
+            Controls::{IImageList, ILD_TRANSPARENT},
             Shell::{
-                SHGetFileInfoW, SHFILEINFOW, SHGFI_ICON, SHGFI_SMALLICON, SHGFI_USEFILEATTRIBUTES,
+                SHGetFileInfoW, SHGetImageList, SHFILEINFOW, SHGFI_ICON, SHGFI_SMALLICON,
+                SHGFI_SYSICONINDEX, SHGFI_USEFILEATTRIBUTES, SHIL_EXTRALARGE, SHIL_JUMBO,
+                SHIL_LARGE, SHIL_SMALL,
             },
             WindowsAndMessaging::{DestroyIcon, GetIconInfo, HICON, ICONINFO},
         },
     },
 };
 
+// The system image lists from smallest to largest, 16, 32, 48 and 256 pixels at 100% scaling
+const IMAGE_LISTS: [u32; 4] = [SHIL_SMALL, SHIL_LARGE, SHIL_EXTRALARGE, SHIL_JUMBO];
+
+// The icon from the smallest system image list at least `size` pixels wide, or the largest
+unsafe fn image_list_icon(index: i32, size: u32) -> Option<HICON> {
+    let mut largest = None;
+
+    for list in IMAGE_LISTS {
+        let Ok(image_list) = SHGetImageList::<IImageList>(list as i32) else {
+            continue;
+        };
+
+        let (mut width, mut height) = (0, 0);
+        if image_list.GetIconSize(&mut width, &mut height).is_err() {
+            continue;
+        }
+
+        largest = Some(image_list.clone());
+
+        if width >= size as i32 {
+            break;
+        }
+    }
+
+    largest?.GetIcon(index, ILD_TRANSPARENT.0).ok()
+}
+
+// `size` is in physical pixels so icons stay sharp with display scaling, e.g. 24 at 150%
 pub unsafe fn fetch_and_convert_icon(
     ctx: &egui::Context,
     path: &Path,
     attribute_flag: u32, // use FILE_ATTRIBUTE_DIRECTORY or FILE_ATTRIBUTE_NORMAL
+    size: u32,
 ) -> Option<TextureHandle> {
     let mut path_utf16: Vec<u16> = path.as_os_str().encode_wide().collect();
     path_utf16.push(0); // null-terminate
@@ -28,7 +60,7 @@ pub unsafe fn fetch_and_convert_icon(
 
     let mut shfi: SHFILEINFOW = std::mem::zeroed();
     // use SHGFI_USEFILEATTRIBUTES so Windows doesn't need to access the file/dir itself
-    let flags = SHGFI_ICON | SHGFI_SMALLICON | SHGFI_USEFILEATTRIBUTES;
+    let flags = SHGFI_SYSICONINDEX | SHGFI_USEFILEATTRIBUTES;
 
     SHGetFileInfoW(
         path_pcwstr,
@@ -38,10 +70,25 @@ pub unsafe fn fetch_and_convert_icon(
         flags,
     );
 
-    if shfi.hIcon.is_invalid() {
+    let h_icon = match image_list_icon(shfi.iIcon, size) {
+        Some(h_icon) => h_icon,
+        None => {
+            // Only the small icon, which can be blurry when scaled up
+            SHGetFileInfoW(
+                path_pcwstr,
+                FILE_FLAGS_AND_ATTRIBUTES(attribute_flag),
+                Some(&mut shfi),
+                std::mem::size_of::<SHFILEINFOW>() as u32,
+                SHGFI_ICON | SHGFI_SMALLICON | SHGFI_USEFILEATTRIBUTES,
+            );
+
+            shfi.hIcon
+        }
+    };
+
+    if h_icon.is_invalid() {
         return None;
     }
-    let h_icon: HICON = shfi.hIcon;
 
     let mut icon_info: ICONINFO = std::mem::zeroed();
     if GetIconInfo(h_icon, &mut icon_info).is_err() {
@@ -51,14 +98,85 @@ pub unsafe fn fetch_and_convert_icon(
     // need to clean up icon_info.hbmColor and icon_info.hbmMask later
 
     let h_bitmap: HBITMAP = icon_info.hbmColor;
-    if h_bitmap.is_invalid() {
-        if !icon_info.hbmMask.is_invalid() {
-            let _ = DeleteObject(icon_info.hbmMask.into());
-        }
-        let _ = DestroyIcon(h_icon);
+    let color_image = if h_bitmap.is_invalid() {
+        None
+    } else {
+        bitmap_to_image(h_bitmap, Some(icon_info.hbmMask))
+    };
+
+    // delete GDI objects obtained from GetIconInfo before destroying the icon
+    if !h_bitmap.is_invalid() {
+        let _ = DeleteObject(h_bitmap.into()); // hbmColor
+    }
+    if !icon_info.hbmMask.is_invalid() {
+        let _ = DeleteObject(icon_info.hbmMask.into()); // hbmMask
+    }
+    // destroy the icon obtained from SHGetFileInfoW
+    let _ = DestroyIcon(h_icon);
+
+    let color_image = color_image?;
+
+    let [width, height] = color_image.size;
+    if width > 256 || height > 256 {
+        // basic validation
         return None;
     }
 
+    let texture_name = format!(
+        "icon_{}",
+        path.extension()
+            .and_then(OsStr::to_str)
+            .map_or_else(|| "<NO_EXT>".to_string(), str::to_lowercase)
+    );
+
+    let handle = ctx.load_texture(
+        texture_name,
+        ImageData::Color(color_image.into()), // Use ImageData enum
+        TextureOptions::LINEAR,               // Use enum variant
+    );
+
+    Some(handle)
+}
+
+// Copies the pixels of a bitmap as RGBA, the caller still owns and has to delete the bitmaps.
+// `mask` is the AND mask of an icon, used for the transparency of icons without an alpha channel.
+pub unsafe fn bitmap_to_image(h_bitmap: HBITMAP, mask: Option<HBITMAP>) -> Option<ColorImage> {
+    let (width, height, pixels_bgra) = bitmap_bgra(h_bitmap)?;
+
+    // bitmaps without transparency (e.g. photo thumbnails and old icons) often have an alpha of 0
+    // everywhere
+    let opaque = pixels_bgra.chunks_exact(4).all(|bgra| bgra[3] == 0);
+
+    // Set bits in the mask are transparent, they read as white
+    let mask = mask
+        .filter(|mask| opaque && !mask.is_invalid())
+        .and_then(|mask| bitmap_bgra(mask))
+        .filter(|&(mask_width, mask_height, _)| (mask_width, mask_height) == (width, height))
+        .map(|(_, _, mask)| mask);
+
+    // convert BGRA to RGBA Vec<Color32>
+    let pixels_rgba: Vec<egui::Color32> = pixels_bgra
+        .chunks_exact(4)
+        .enumerate()
+        .map(|(i, bgra)| {
+            let alpha = match &mask {
+                Some(mask) if mask[i * 4] != 0 => 0,
+                _ if opaque => 255,
+                _ => bgra[3],
+            };
+            egui::Color32::from_rgba_unmultiplied(bgra[2], bgra[1], bgra[0], alpha)
+            // Use unmultiplied
+        })
+        .collect();
+
+    Some(ColorImage {
+        size: [width, height],
+        pixels: pixels_rgba,
+    })
+}
+
+// The width, height and 32-bit BGRA pixels of a bitmap, monochrome bitmaps are black and white
+unsafe fn bitmap_bgra(h_bitmap: HBITMAP) -> Option<(usize, usize, Vec<u8>)> {
     let mut bmp: BITMAP = std::mem::zeroed();
     let obj_size = std::mem::size_of::<BITMAP>() as i32;
     if GetObjectW(
@@ -67,23 +185,12 @@ pub unsafe fn fetch_and_convert_icon(
         Some((&raw mut bmp).cast::<std::ffi::c_void>()),
     ) == 0
     {
-        let _ = DeleteObject(h_bitmap.into());
-        if !icon_info.hbmMask.is_invalid() {
-            let _ = DeleteObject(icon_info.hbmMask.into());
-        }
-        let _ = DestroyIcon(h_icon);
         return None;
     }
 
     let width = bmp.bmWidth as usize;
     let height = bmp.bmHeight as usize;
-    if width == 0 || height == 0 || width > 128 || height > 128 {
-        // basic validation
-        let _ = DeleteObject(h_bitmap.into());
-        if !icon_info.hbmMask.is_invalid() {
-            let _ = DeleteObject(icon_info.hbmMask.into());
-        }
-        let _ = DestroyIcon(h_icon);
+    if width == 0 || height == 0 {
         return None;
     }
 
@@ -103,12 +210,6 @@ pub unsafe fn fetch_and_convert_icon(
 
     let hdc_screen = GetDC(None);
     if hdc_screen.is_invalid() {
-        // clean up allocated resources before returning
-        let _ = DeleteObject(h_bitmap.into());
-        if !icon_info.hbmMask.is_invalid() {
-            let _ = DeleteObject(icon_info.hbmMask.into());
-        }
-        let _ = DestroyIcon(h_icon);
         return None;
     }
 
@@ -124,45 +225,9 @@ pub unsafe fn fetch_and_convert_icon(
 
     let _ = ReleaseDC(None, hdc_screen); // Release DC *after* use
 
-    // delete GDI objects obtained from GetIconInfo before destroying the icon
-    let _ = DeleteObject(h_bitmap.into()); // hbmColor
-    if !icon_info.hbmMask.is_invalid() {
-        let _ = DeleteObject(icon_info.hbmMask.into()); // hbmMask
-    }
-    // destroy the icon obtained from SHGetFileInfoW
-    let _ = DestroyIcon(h_icon);
-
     if result == 0 {
         return None; // GetDIBits failed
     }
 
-    // convert BGRA to RGBA Vec<Color32>
-    let pixels_rgba: Vec<egui::Color32> = pixels_bgra
-        .chunks_exact(4)
-        .map(|bgra| egui::Color32::from_rgba_unmultiplied(bgra[2], bgra[1], bgra[0], bgra[3])) // Use unmultiplied
-        .collect();
-
-    if pixels_rgba.len() != width * height {
-        return None; // should not happen if GetDIBits succeeded
-    }
-
-    let color_image = ColorImage {
-        size: [width, height],
-        pixels: pixels_rgba,
-    };
-
-    let texture_name = format!(
-        "icon_{}",
-        path.extension()
-            .and_then(OsStr::to_str)
-            .map_or_else(|| "<NO_EXT>".to_string(), str::to_lowercase)
-    );
-
-    let handle = ctx.load_texture(
-        texture_name,
-        ImageData::Color(color_image.into()), // Use ImageData enum
-        TextureOptions::LINEAR,               // Use enum variant
-    );
-
-    Some(handle)
+    Some((width, height, pixels_bgra))
 }
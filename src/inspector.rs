@@ -0,0 +1,167 @@
+use std::fmt::Write;
+
+use eframe::egui;
+use ntfs_reader::{
+    api::{ntfs_to_unix_time, NtfsAttributeType, NtfsFileFlags},
+    file::NtfsFile,
+};
+
+use crate::record::RecordReader;
+
+// Debug window for dumping raw MFT records, toggled with Ctrl+Shift+I
+#[derive(Default)]
+pub struct Inspector {
+    pub open: bool,
+    frn: String,
+    output: String,
+}
+
+impl Inspector {
+    pub fn show(&mut self, ctx: &egui::Context, records: Option<&mut RecordReader>) {
+        egui::Window::new("MFT record inspector")
+            .open(&mut self.open)
+            .default_size([500.0, 400.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("FRN");
+
+                    let resp = ui.text_edit_singleline(&mut self.frn);
+
+                    let submitted =
+                        resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                    if ui.button("Inspect").clicked() || submitted {
+                        self.output = match (self.frn.trim().parse::<u64>(), records) {
+                            (Ok(frn), Some(records)) => {
+                                records.with_record(frn, dump_record).unwrap_or_else(|| {
+                                    format!(
+                                        "Record {frn} doesn't exist or isn't a valid file record"
+                                    )
+                                })
+                            }
+                            (Ok(_), None) => "The volume couldn't be opened".to_string(),
+                            (Err(err), _) => format!("Invalid FRN: {err}"),
+                        };
+                    }
+                });
+
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.monospace(&self.output);
+                });
+            });
+    }
+}
+
+fn attribute_name(type_id: u32) -> &'static str {
+    match type_id {
+        0x10 => "$STANDARD_INFORMATION",
+        0x20 => "$ATTRIBUTE_LIST",
+        0x30 => "$FILE_NAME",
+        0x40 => "$OBJECT_ID",
+        0x50 => "$SECURITY_DESCRIPTOR",
+        0x60 => "$VOLUME_NAME",
+        0x70 => "$VOLUME_INFORMATION",
+        0x80 => "$DATA",
+        0x90 => "$INDEX_ROOT",
+        0xA0 => "$INDEX_ALLOCATION",
+        0xB0 => "$BITMAP",
+        0xC0 => "$REPARSE_POINT",
+        0x100 => "$LOGGED_UTILITY_STREAM",
+        _ => "unknown",
+    }
+}
+
+// Dumps everything useful in an MFT record for diagnosing indexing bugs
+pub fn dump_record(file: &NtfsFile) -> String {
+    let frn = file.number();
+
+    let mut output = String::new();
+
+    // The header is packed so the fields have to be copied out before formatting
+    let flags = file.header.flags;
+    let link_count = file.header.link_count;
+    let sequence = file.header.sequence_value;
+    let base_reference = file.header.base_reference;
+
+    let _ = writeln!(output, "Record {frn}");
+    let _ = writeln!(
+        output,
+        "Flags: {flags:#06x} (in use: {}, directory: {})",
+        flags & NtfsFileFlags::InUse as u16 != 0,
+        flags & NtfsFileFlags::IsDirectory as u16 != 0
+    );
+    let _ = writeln!(output, "Sequence: {sequence}, links: {link_count}");
+    let _ = writeln!(
+        output,
+        "Base record: {}",
+        base_reference & 0x0000_FFFF_FFFF_FFFF
+    );
+
+    file.attributes(|att| {
+        let type_id = att.header.type_id;
+        let non_resident = att.header.is_non_resident != 0;
+
+        let _ = writeln!(output);
+        let _ = writeln!(
+            output,
+            "{} ({type_id:#x}), {}",
+            attribute_name(type_id),
+            if non_resident {
+                "nonresident"
+            } else {
+                "resident"
+            }
+        );
+
+        if type_id == NtfsAttributeType::StandardInformation as u32 {
+            let stdinfo = att.as_standard_info();
+
+            let created = stdinfo.creation_time;
+            let modified = stdinfo.modification_time;
+            let record_modified = stdinfo.mft_record_modification_time;
+            let accessed = stdinfo.access_time;
+            let attributes = stdinfo.file_attributes;
+
+            let _ = writeln!(output, "  Created: {}", ntfs_to_unix_time(created));
+            let _ = writeln!(output, "  Modified: {}", ntfs_to_unix_time(modified));
+            let _ = writeln!(
+                output,
+                "  Record modified: {}",
+                ntfs_to_unix_time(record_modified)
+            );
+            let _ = writeln!(output, "  Accessed: {}", ntfs_to_unix_time(accessed));
+            let _ = writeln!(output, "  Attributes: {attributes:#010x}");
+        } else if type_id == NtfsAttributeType::FileName as u32 {
+            let name = att.as_name();
+
+            let namespace = name.header.namespace;
+            let attributes = name.header.file_attributes;
+            let real_size = name.header.real_size;
+
+            let _ = writeln!(output, "  Name: {name}");
+            let _ = writeln!(output, "  Parent: {}", name.parent());
+            let _ = writeln!(output, "  Namespace: {namespace}");
+            let _ = writeln!(output, "  Attributes: {attributes:#010x}");
+            // Only updated when the name changes so usually stale
+            let _ = writeln!(output, "  Size: {real_size}");
+        } else if type_id == NtfsAttributeType::Data as u32 {
+            if non_resident {
+                let data_size = att.header_nonres.data_size;
+                let allocated_size = att.header_nonres.allocated_size;
+                let initialized_size = att.header_nonres.initialized_size;
+
+                let _ = writeln!(output, "  Size: {data_size}");
+                let _ = writeln!(output, "  Allocated: {allocated_size}");
+                let _ = writeln!(output, "  Initialized: {initialized_size}");
+            } else {
+                let value_length = att.header_res.value_length;
+
+                let _ = writeln!(output, "  Size: {value_length}");
+            }
+        }
+    });
+
+    output
+}
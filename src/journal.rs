@@ -0,0 +1,233 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    sync::mpsc::Receiver,
+    thread,
+    time::Duration,
+};
+
+use ntfs_reader::{
+    journal::{HistorySize, Journal, JournalOptions, NextUsn, UsnRecord},
+    volume::Volume,
+};
+use windows::Win32::{
+    Foundation::{
+        ERROR_JOURNAL_DELETE_IN_PROGRESS, ERROR_JOURNAL_ENTRY_DELETED, ERROR_JOURNAL_NOT_ACTIVE,
+    },
+    System::Ioctl,
+};
+
+use crate::filesystem::file_id_to_frn;
+
+// ntfs-reader keeps this many records around to resolve the old paths of renamed files
+const HISTORY_SIZE: usize = 4096;
+
+// ntfs-reader reads 4 KiB at a time by default, a few dozen records. The buffer is on the stack.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+// Reads per poll are capped so a constantly busy drive can't flood the UI, 16 MiB of records
+const MAX_READS_PER_POLL: usize = 256;
+
+// https://learn.microsoft.com/en-us/windows/win32/api/winioctl/ns-winioctl-usn_record_v2
+const REASONS: [(u32, &str); 24] = [
+    (Ioctl::USN_REASON_DATA_OVERWRITE, "DATA_OVERWRITE"),
+    (Ioctl::USN_REASON_DATA_EXTEND, "DATA_EXTEND"),
+    (Ioctl::USN_REASON_DATA_TRUNCATION, "DATA_TRUNCATION"),
+    (
+        Ioctl::USN_REASON_NAMED_DATA_OVERWRITE,
+        "NAMED_DATA_OVERWRITE",
+    ),
+    (Ioctl::USN_REASON_NAMED_DATA_EXTEND, "NAMED_DATA_EXTEND"),
+    (
+        Ioctl::USN_REASON_NAMED_DATA_TRUNCATION,
+        "NAMED_DATA_TRUNCATION",
+    ),
+    (Ioctl::USN_REASON_FILE_CREATE, "FILE_CREATE"),
+    (Ioctl::USN_REASON_FILE_DELETE, "FILE_DELETE"),
+    (Ioctl::USN_REASON_EA_CHANGE, "EA_CHANGE"),
+    (Ioctl::USN_REASON_SECURITY_CHANGE, "SECURITY_CHANGE"),
+    (Ioctl::USN_REASON_RENAME_OLD_NAME, "RENAME_OLD_NAME"),
+    (Ioctl::USN_REASON_RENAME_NEW_NAME, "RENAME_NEW_NAME"),
+    (Ioctl::USN_REASON_INDEXABLE_CHANGE, "INDEXABLE_CHANGE"),
+    (Ioctl::USN_REASON_BASIC_INFO_CHANGE, "BASIC_INFO_CHANGE"),
+    (Ioctl::USN_REASON_HARD_LINK_CHANGE, "HARD_LINK_CHANGE"),
+    (Ioctl::USN_REASON_COMPRESSION_CHANGE, "COMPRESSION_CHANGE"),
+    (Ioctl::USN_REASON_ENCRYPTION_CHANGE, "ENCRYPTION_CHANGE"),
+    (Ioctl::USN_REASON_OBJECT_ID_CHANGE, "OBJECT_ID_CHANGE"),
+    (
+        Ioctl::USN_REASON_REPARSE_POINT_CHANGE,
+        "REPARSE_POINT_CHANGE",
+    ),
+    (Ioctl::USN_REASON_STREAM_CHANGE, "STREAM_CHANGE"),
+    (Ioctl::USN_REASON_TRANSACTED_CHANGE, "TRANSACTED_CHANGE"),
+    (Ioctl::USN_REASON_INTEGRITY_CHANGE, "INTEGRITY_CHANGE"),
+    (
+        Ioctl::USN_REASON_DESIRED_STORAGE_CLASS_CHANGE,
+        "DESIRED_STORAGE_CLASS_CHANGE",
+    ),
+    (Ioctl::USN_REASON_CLOSE, "CLOSE"),
+];
+
+// Turns a reason bitmask into its flag names, e.g. "FILE_CREATE | CLOSE"
+pub fn format_reason(reason: u32) -> String {
+    REASONS
+        .iter()
+        .filter(|(flag, _)| reason & flag != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+pub enum JournalMessage {
+    Record(UsnRecord),
+    // Records were lost, the journal wrapped past the last read or was deleted, and the reader
+    // carries on from the newest record
+    Gap,
+}
+
+// `drive` is a drive letter with a colon, e.g. C:
+fn open(drive: &str, next_usn: NextUsn) -> Journal {
+    let volume = Volume::new(format!(r"\\.\{drive}")).expect("failed to open volume");
+
+    Journal::new(
+        volume,
+        JournalOptions {
+            reason_mask: 0xFFFFFFFF,
+            next_usn,
+            max_history_size: HistorySize::Limited(HISTORY_SIZE),
+            version_range: (2, 3),
+        },
+    )
+    .expect("failed to open journal")
+}
+
+// Where the journal is up to, read before the MFT so the changes made while it is read aren't
+// missed. None if the journal can't be opened.
+pub fn next_usn(drive: &str) -> Option<i64> {
+    let volume = Volume::new(format!(r"\\.\{drive}")).ok()?;
+
+    Journal::new(volume, JournalOptions::default())
+        .ok()
+        .map(|journal| journal.get_next_usn())
+}
+
+// The read failed because the records after the last read are gone
+fn is_gap(err: &io::Error) -> bool {
+    [
+        ERROR_JOURNAL_ENTRY_DELETED,
+        ERROR_JOURNAL_NOT_ACTIVE,
+        ERROR_JOURNAL_DELETE_IN_PROGRESS,
+    ]
+    .iter()
+    .any(|code| err.raw_os_error() == Some(code.0 as i32))
+}
+
+// Every batch of records until the journal has nothing newer, so a burst of changes isn't spread
+// over several polls. Stops early if `handle` returns false. Returns false if records were lost,
+// the journal is reopened at the newest record then.
+fn drain(
+    journal: &mut Journal,
+    drive: &str,
+    mut handle: impl FnMut(Vec<UsnRecord>) -> bool,
+) -> bool {
+    for _ in 0..MAX_READS_PER_POLL {
+        match journal.read_sized::<READ_BUFFER_SIZE>() {
+            Ok(records) if !records.is_empty() => {
+                if !handle(records) {
+                    return true;
+                }
+            }
+            Ok(_) => return true,
+            Err(err) if is_gap(&err) => {
+                *journal = open(drive, NextUsn::Next);
+                return false;
+            }
+            // Retried on the next poll
+            Err(_) => return true,
+        }
+    }
+
+    true
+}
+
+// Reads from `start`, see `next_usn`, or from the newest record if None
+pub fn spawn_reader(
+    drive: String,
+    start: Option<i64>,
+    poll_interval: Duration,
+) -> Receiver<JournalMessage> {
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    thread::spawn(move || {
+        let mut journal = open(&drive, start.map_or(NextUsn::Next, NextUsn::Custom));
+
+        loop {
+            // let start = std::time::Instant::now();
+
+            let mut connected = true;
+
+            let complete = drain(&mut journal, &drive, |records| {
+                // The receiver is dropped when the index is rebuilt with a new reader
+                connected = records
+                    .into_iter()
+                    .all(|record| tx.send(JournalMessage::Record(record)).is_ok());
+
+                connected
+            });
+
+            if !complete {
+                connected = tx.send(JournalMessage::Gap).is_ok();
+            }
+            // println!("{:?}", start.elapsed());
+
+            if !connected {
+                return;
+            }
+
+            thread::sleep(poll_interval);
+        }
+    });
+
+    rx
+}
+
+// Logs every journal record to the output file (or stdout) until the process is killed
+pub fn watch(drive: &str, output: Option<&Path>) -> io::Result<()> {
+    let mut output: Box<dyn Write> = match output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+
+    let mut journal = open(drive, NextUsn::Next);
+
+    loop {
+        let mut result = Ok(());
+
+        let complete = drain(&mut journal, drive, |records| {
+            result = records.iter().try_for_each(|record| {
+                writeln!(
+                    output,
+                    "{} frn={} parent={} {} [{}]",
+                    record.usn,
+                    file_id_to_frn(record.file_id),
+                    file_id_to_frn(record.parent_id),
+                    record.path.display(),
+                    format_reason(record.reason)
+                )
+            });
+
+            result.is_ok()
+        });
+
+        result?;
+
+        if !complete {
+            writeln!(output, "records were lost, continuing from the newest one")?;
+        }
+
+        output.flush()?;
+
+        thread::sleep(Duration::from_millis(1000));
+    }
+}
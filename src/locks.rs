@@ -0,0 +1,85 @@
+use std::{os::windows::ffi::OsStrExt, path::Path};
+
+use windows::{
+    core::{Result, PCWSTR, PWSTR},
+    Win32::{
+        Foundation::ERROR_MORE_DATA,
+        System::RestartManager::{
+            RmEndSession, RmGetList, RmRegisterResources, RmStartSession, CCH_RM_SESSION_KEY,
+            RM_PROCESS_INFO,
+        },
+    },
+};
+
+// The processes that have the file open, e.g. "Microsoft Word (PID 1234)". Asks the Restart
+// Manager so it works for files opened without sharing, which a test open can't tell apart from
+// a permission problem.
+pub unsafe fn processes_using(path: &Path) -> Result<Vec<String>> {
+    let mut session = 0;
+    let mut session_key = [0u16; CCH_RM_SESSION_KEY as usize + 1];
+
+    RmStartSession(
+        &mut session,
+        None,
+        PWSTR::from_raw(session_key.as_mut_ptr()),
+    )
+    .ok()?;
+
+    let processes = session_processes(session, path);
+
+    let _ = RmEndSession(session);
+
+    processes
+}
+
+unsafe fn session_processes(session: u32, path: &Path) -> Result<Vec<String>> {
+    let path_utf16: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+
+    let files = [PCWSTR::from_raw(path_utf16.as_ptr())];
+
+    RmRegisterResources(session, Some(files.as_slice()), None, None).ok()?;
+
+    let mut processes: Vec<RM_PROCESS_INFO> = Vec::new();
+
+    // More processes can open the file between asking for the count and the list
+    loop {
+        let mut needed = 0;
+        let mut count = processes.len() as u32;
+        let mut reboot_reasons = 0;
+
+        let error = RmGetList(
+            session,
+            &mut needed,
+            &mut count,
+            (!processes.is_empty()).then_some(processes.as_mut_ptr()),
+            &mut reboot_reasons,
+        );
+
+        if error == ERROR_MORE_DATA {
+            processes.resize_with(needed as usize, RM_PROCESS_INFO::default);
+            continue;
+        }
+
+        error.ok()?;
+        processes.truncate(count as usize);
+        break;
+    }
+
+    Ok(processes
+        .iter()
+        .map(|process| {
+            let name = &process.strAppName;
+            let length = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+
+            format!(
+                "{} (PID {})",
+                String::from_utf16_lossy(&name[..length]),
+                process.Process.dwProcessId
+            )
+        })
+        .collect())
+}
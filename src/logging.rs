@@ -0,0 +1,101 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use windows::Win32::System::SystemInformation::GetLocalTime;
+
+// Set to error, warn, info, debug, trace or off. Timings are logged at debug.
+const LEVEL_VARIABLE: &str = "SEARCH_LOG";
+
+// Kept small as it is appended to on every run
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+// Logs to stderr and to a file, the console is hidden when built with the windows subsystem
+struct Logger {
+    file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let time = unsafe { GetLocalTime() };
+
+        let line = format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03} {:<5} {}",
+            time.wYear,
+            time.wMonth,
+            time.wDay,
+            time.wHour,
+            time.wMinute,
+            time.wSecond,
+            time.wMilliseconds,
+            record.level(),
+            record.args()
+        );
+
+        eprintln!("{line}");
+
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = writeln!(file, "{line}");
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+// search.log next to the saved config
+fn log_path(app_name: &str) -> Option<PathBuf> {
+    Some(eframe::storage_dir(app_name)?.join("search.log"))
+}
+
+// Info by default, logging still goes to stderr if the file can't be opened
+pub fn init(app_name: &str) {
+    let level = std::env::var(LEVEL_VARIABLE)
+        .ok()
+        .and_then(|level| level.parse().ok())
+        .unwrap_or(LevelFilter::Info);
+
+    let file = log_path(app_name).and_then(|path| {
+        std::fs::create_dir_all(path.parent()?).ok()?;
+
+        // Start over rather than growing forever
+        let truncate = path
+            .metadata()
+            .is_ok_and(|metadata| metadata.len() > MAX_LOG_SIZE);
+
+        OpenOptions::new()
+            .create(true)
+            .append(!truncate)
+            .write(true)
+            .truncate(truncate)
+            .open(path)
+            .ok()
+    });
+
+    let logger = Logger {
+        file: file.map(Mutex::new),
+    };
+
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level);
+    }
+}
@@ -1,38 +1,79 @@
 // #![windows_subsystem = "windows"]
 
-use std::{ffi::OsStr, path::Path, sync::mpsc::Receiver, thread, time::Duration};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+    time::Duration,
+};
 
 use eframe::{
     egui::{
-        self, Button, ColorImage, FontDefinitions, FontFamily, ImageData, Label, RichText, Sense,
-        TextureHandle, TextureOptions,
+        self, Button, ColorImage, FontDefinitions, FontFamily, ImageData, Label,
+        PopupCloseBehavior, RichText, Sense, TextureHandle, TextureOptions,
     },
     epaint::text::{FontInsert, InsertFontFamily},
 };
 use egui_extras::{Column, TableBuilder};
 
-use filesystem::{FileOrder, FileSystem, SortDirection};
+use cli::Args;
+use clipboard::ClipboardListener;
+use config::{AttributeFilter, ColumnPreset, Config, ResultColumn, Terminal, FILTER_ATTRIBUTES};
+use export::{format_date, ScriptKind, ScriptOperation};
+use filesystem::{
+    file_id_to_frn, DateKind, EmptyFilter, FileOrder, FileSystem, FilterState, IndexOptions,
+    IndexStats, SortDirection, UNKNOWN_FOLDER,
+};
+use grep::ContentSearch;
+use hash::{FileHash, HashAlgorithm};
+use hotkeys::Action;
 
 use icon::fetch_and_convert_icon;
-use ntfs_reader::{
-    api::{ntfs_to_unix_time, NtfsAttributeType},
-    journal::{HistorySize, Journal, JournalOptions, NextUsn, UsnRecord},
-    mft::Mft,
-    volume::Volume,
-};
-use rustc_hash::FxHashMap;
+use inspector::Inspector;
+use journal::JournalMessage;
+use log::{error, info, trace, warn};
+use missing::MissingCheck;
+use ntfs_reader::{errors::NtfsReaderError, mft::Mft, volume::Volume};
+use preview::Preview;
+use rebuild::Rebuild;
+use record::RecordReader;
+use recycle::RecycledFile;
+use rustc_hash::{FxHashMap, FxHashSet};
+use signature::SignatureScan;
 use windows::{
-    core::PCSTR,
+    core::{PCSTR, PCWSTR},
     Win32::{
         Storage::FileSystem::{
-            GetDriveTypeA, GetLogicalDrives, FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_NORMAL,
+            GetDriveTypeA, GetLogicalDrives, GetVolumeInformationW, FILE_ATTRIBUTE_DIRECTORY,
+            FILE_ATTRIBUTE_NORMAL, FILE_ATTRIBUTE_REPARSE_POINT,
         },
-        System::Ioctl,
+        System::{Ioctl, SystemInformation::GetLocalTime},
     },
 };
 
+mod bench;
+mod cli;
+mod clipboard;
+mod config;
+mod export;
 mod filesystem;
+mod grep;
+mod hash;
+mod hotkeys;
 mod icon;
+mod inspector;
+mod journal;
+mod locks;
+mod logging;
+mod missing;
+mod owner;
+mod preview;
+mod query;
+mod rebuild;
+mod record;
+mod recycle;
+mod shell;
+mod signature;
 
 unsafe fn get_drives() -> Vec<String> {
     let mut drives = Vec::new();
@@ -57,6 +98,105 @@ unsafe fn get_drives() -> Vec<String> {
     drives
 }
 
+// `drive` is a drive letter with a colon, e.g. C:. Only NTFS volumes have an MFT to read.
+unsafe fn is_ntfs(drive: &str) -> bool {
+    let root: Vec<u16> = format!("{drive}\\")
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+
+    let mut filesystem_name = [0u16; 16];
+
+    if GetVolumeInformationW(
+        PCWSTR::from_raw(root.as_ptr()),
+        None,
+        None,
+        None,
+        None,
+        Some(&mut filesystem_name),
+    )
+    .is_err()
+    {
+        return false;
+    }
+
+    let length = filesystem_name
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(filesystem_name.len());
+
+    String::from_utf16_lossy(&filesystem_name[..length]) == "NTFS"
+}
+
+// Drives that can be indexed, e.g. C:
+fn ntfs_drives() -> Vec<String> {
+    unsafe { get_drives() }
+        .into_iter()
+        .map(|drive| drive.trim_end_matches('\\').to_string())
+        .filter(|drive| unsafe { is_ntfs(drive) })
+        .collect()
+}
+
+// Of the file icons in the results, in points
+const ICON_SIZE: f32 = 16.0;
+
+// Icons are fetched at the size they are drawn in physical pixels so display scaling keeps them sharp
+fn icon_pixels(ctx: &egui::Context) -> u32 {
+    (ICON_SIZE * ctx.pixels_per_point()).round() as u32
+}
+
+// Width per folder level when results are shown as a tree
+const TREE_INDENT: f32 = 12.0;
+
+// Quick date filters and how many days back they go, None is since midnight
+const RECENT_FILTERS: [(&str, Option<u64>); 3] =
+    [("Today", None), ("7 days", Some(7)), ("30 days", Some(30))];
+
+// 100ns intervals, the unit of NTFS timestamps
+const NTFS_TICKS_PER_SECOND: u64 = 10_000_000;
+
+// NTFS time of the start of the period a quick filter shows
+fn recent_cutoff(days: Option<u64>) -> u64 {
+    let now = export::ntfs_now();
+
+    let seconds = match days {
+        Some(days) => days * 24 * 60 * 60,
+        None => {
+            let time = unsafe { GetLocalTime() };
+            u64::from(time.wHour) * 60 * 60 + u64::from(time.wMinute) * 60 + u64::from(time.wSecond)
+        }
+    };
+
+    now.saturating_sub(seconds * NTFS_TICKS_PER_SECOND)
+}
+
+// Seconds between key presses for them to be typed into the same type-ahead prefix
+const TYPE_AHEAD_TIMEOUT: f64 = 1.0;
+
+// Files whose last journal reasons are kept for the hover, the older half is forgotten past this
+const MAX_LAST_REASONS: usize = 10_000;
+
+// Drops the older half of the reasons, by the USN of their records
+fn forget_oldest_reasons(reasons: &mut FxHashMap<u64, (u32, i64)>) {
+    let mut usns: Vec<i64> = reasons.values().map(|&(_, usn)| usn).collect();
+    let middle = usns.len() / 2;
+    let cutoff = *usns.select_nth_unstable(middle).1;
+
+    reasons.retain(|_, &mut (_, usn)| usn >= cutoff);
+}
+
+// Time per frame spent counting the sizes of shown folders so a big one doesn't freeze the UI
+const FOLDER_SIZE_BUDGET: Duration = Duration::from_millis(8);
+
+// Deepest the depth filter can be set to, anything deeper is rare enough to not need a limit
+const MAX_DEPTH_FILTER: usize = 64;
+
+// How many of the most common extensions in the results are listed
+const MAX_FACETS: usize = 8;
+
+// Also names the folder the config and log are saved in
+const APP_NAME: &str = "File Search";
+
 fn format_size(bytes: u64) -> String {
     if bytes < 1024 {
         format!("{bytes} B")
@@ -69,118 +209,194 @@ fn format_size(bytes: u64) -> String {
     }
 }
 
-fn main() -> Result<(), eframe::Error> {
-    let start = std::time::Instant::now();
+// 1234567 -> 1,234,567
+fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
 
-    let volume = Volume::new(r"\\.\C:").expect("failed to open volume");
-    let mft = Mft::new(volume).expect("failed to open mft");
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+
+    formatted
+}
 
-    // possible to miss changes between reading mft and opening journal
+fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
 
-    let (tx, rx) = std::sync::mpsc::channel();
+    if seconds < 60 {
+        format!("{seconds}s ago")
+    } else if seconds < 60 * 60 {
+        format!("{}m ago", seconds / 60)
+    } else if seconds < 60 * 60 * 24 {
+        format!("{}h ago", seconds / (60 * 60))
+    } else {
+        format!("{}d ago", seconds / (60 * 60 * 24))
+    }
+}
 
-    thread::spawn(move || {
-        let volume = Volume::new(r"\\.\C:").expect("failed to open volume");
+// Filenames can't contain double quotes but they are doubled anyway, which both cmd and PowerShell
+// treat as an escaped quote
+fn quote_path(path: &str) -> String {
+    format!("\"{}\"", path.replace('"', "\"\""))
+}
 
-        let mut journal = Journal::new(
-            volume,
-            JournalOptions {
-                reason_mask: 0xFFFFFFFF,
-                next_usn: NextUsn::Next,
-                max_history_size: HistorySize::Limited(4096),
-                version_range: (2, 3),
-            },
-        )
-        .expect("failed to open journal");
+fn forward_slash_path(path: &str) -> String {
+    path.replace('\\', "/")
+}
 
-        loop {
-            // let start = std::time::Instant::now();
+// file:///C:/My%20Files/a.txt for C:\My Files\a.txt, or file://server/share/a.txt for a UNC path
+fn file_url(path: &str) -> String {
+    let (prefix, path) = match path.strip_prefix(r"\\") {
+        Some(unc) => ("file://", unc),
+        None => ("file:///", path),
+    };
 
-            if let Ok(records) = journal.read() {
-                for record in records {
-                    tx.send(record).expect("no receiver");
-                }
+    let mut url = prefix.to_string();
+
+    for byte in path.bytes() {
+        match byte {
+            b'\\' => url.push('/'),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                url.push(byte as char);
             }
-            // println!("{:?}", start.elapsed());
+            _ => url.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    url
+}
+
+// [name](file:///…) with the characters that would end the link text escaped
+fn markdown_link(filename: &str, path: &str) -> String {
+    let mut text = String::with_capacity(filename.len());
 
-            thread::sleep(Duration::from_millis(1000));
+    for c in filename.chars() {
+        if matches!(c, '[' | ']' | '\\') {
+            text.push('\\');
         }
-    });
+        text.push(c);
+    }
+
+    format!("[{text}]({})", file_url(path))
+}
+
+// The absolute path if the file isn't below `base`
+fn relative_path(full_path: &Path, base: &Path) -> String {
+    full_path
+        .strip_prefix(base)
+        .unwrap_or(full_path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn show_syntax_help(ui: &mut egui::Ui) {
+    ui.set_min_width(500.0);
+
+    egui::Grid::new("syntax_help_grid")
+        .num_columns(3)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.strong("Syntax");
+            ui.strong("Matches");
+            ui.strong("Example");
+            ui.end_row();
+
+            for (syntax, description, example) in query::syntax_help() {
+                ui.label(syntax);
+                ui.label(description);
+                ui.monospace(example);
+                ui.end_row();
+            }
+        });
+}
+
+// Reads the index without the journal or config, for the modes that don't open the GUI
+fn read_index(args: &Args) -> (FileSystem, IndexStats) {
+    let start = std::time::Instant::now();
+
+    let volume = Volume::new(format!(r"\\.\{}", args.drive)).expect("failed to open volume");
+    let mft = Mft::new(volume).expect("failed to open mft");
 
-    let mut filesystem = FileSystem {
-        position_mapping: vec![usize::MAX; mft.max_record as usize],
-        frn_mapping: Vec::new(),
-        parent_mapping: Vec::new(),
-        filesizes: Vec::new(),
-        modified_dates: Vec::new(),
-        filenames: Vec::new(),
-        lowercase_filenames: Vec::new(),
-        shown: Vec::new(),
-        volume_path: r"C:\".into(),
-        order: FileOrder::RecordNumber,
-        direction: SortDirection::Descending,
+    let options = IndexOptions {
+        low_memory: args.low_memory,
+        unicode_case: false,
+        modified_since: args.since,
+        short_names: false,
     };
 
-    let mut count = 0;
+    let mut filesystem = FileSystem::from_mft(&mft, format!(r"{}\", args.drive).into(), options);
+    drop(mft);
 
-    for number in 0..mft.max_record {
-        if let Some(file) = mft.get_record(number) {
-            if file.is_used() {
-                if let Some(filename) = file.get_best_file_name(&mft) {
-                    let parent = filename.parent();
-                    let filename = filename.to_string();
+    if let Some(root) = &args.root {
+        if !filesystem.retain_subtree(root) {
+            warn!(
+                "{} isn't an indexed directory, indexing the whole volume",
+                root.display()
+            );
+        }
+    }
 
-                    filesystem.position_mapping[number as usize] = filesystem.filenames.len();
+    let stats = filesystem.stats(start.elapsed());
 
-                    filesystem.parent_mapping.push(parent);
-                    filesystem.frn_mapping.push(number);
+    (filesystem, stats)
+}
 
-                    let mut accessed = None;
-                    let mut created = None;
-                    let mut modified = None;
-                    let mut size = 0u64;
+fn main() -> Result<(), eframe::Error> {
+    logging::init(APP_NAME);
 
-                    file.attributes(|att| {
-                        if att.header.type_id == NtfsAttributeType::StandardInformation as u32 {
-                            let stdinfo = att.as_standard_info();
+    let args = Args::parse();
 
-                            accessed = Some(stdinfo.access_time);
-                            created = Some(stdinfo.creation_time);
-                            modified = Some(stdinfo.modification_time);
-                        }
+    if args.watch {
+        if let Err(err) = journal::watch(&args.drive, args.output.as_deref()) {
+            error!("Failed to write journal records: {err}");
+        }
 
-                        if att.header.type_id == NtfsAttributeType::Data as u32 {
-                            if att.header.is_non_resident == 0 {
-                                size = att.header_res.value_length as u64;
-                            } else {
-                                size = att.header_nonres.data_size;
-                            }
-                        }
-                    });
+        return Ok(());
+    }
 
-                    filesystem.filesizes.push(size);
-                    filesystem.modified_dates.push(modified);
+    if args.bench {
+        let (mut filesystem, stats) = read_index(&args);
 
-                    filesystem
-                        .lowercase_filenames
-                        .push(filename.to_lowercase().into());
-                    filesystem.filenames.push(filename.into());
-                }
-            } else {
-                count += 1;
-            }
+        println!("Took {}ms to read MFT", stats.scan_ms);
+
+        bench::run(&mut filesystem);
+
+        return Ok(());
+    }
+
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = &args.export_db {
+        let (filesystem, _) = read_index(&args);
+
+        match export::to_sqlite(&filesystem, path) {
+            Ok(files) => info!("Exported {files} files to {}", path.display()),
+            Err(err) => error!("Failed to export to {}: {err}", path.display()),
         }
+
+        return Ok(());
     }
 
-    println!("{} {}", count, mft.max_record);
+    #[cfg(not(feature = "sqlite"))]
+    if args.export_db.is_some() {
+        error!("--export-db needs the sqlite feature, which this build doesn't have");
+
+        return Ok(());
+    }
 
-    filesystem.shown = (0..filesystem.filenames.len()).collect();
+    if args.stats {
+        let (_, stats) = read_index(&args);
 
-    // manually drop mft as otherwise it will hog memory
-    drop(mft);
+        println!(
+            "{}",
+            serde_json::to_string(&stats).expect("failed to serialize stats")
+        );
 
-    println!("Took {:?} to read MFT", start.elapsed());
-    println!("{} files", filesystem.filenames.len());
+        return Ok(());
+    }
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -191,9 +407,9 @@ fn main() -> Result<(), eframe::Error> {
     };
 
     eframe::run_native(
-        "File Search",
+        APP_NAME,
         options,
-        Box::new(|cc| {
+        Box::new(move |cc| {
             cc.egui_ctx.add_font(FontInsert::new(
                 "Segoe UI Regular",
                 egui::FontData::from_static(include_bytes!(r"C:\Windows\Fonts\segoeui.ttf")),
@@ -209,28 +425,373 @@ fn main() -> Result<(), eframe::Error> {
                 ],
             ));
 
+            // The index is loaded here rather than before the window is created so that it can
+            // use the saved config
+            let mut config: Config = cc
+                .storage
+                .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+                .unwrap_or_default();
+
+            if config.always_on_top {
+                cc.egui_ctx
+                    .send_viewport_cmd(egui::ViewportCommand::WindowLevel(
+                        egui::WindowLevel::AlwaysOnTop,
+                    ));
+            }
+
+            let start = std::time::Instant::now();
+
+            // The drive could have been removed or reformatted since the last run
+            if !unsafe { is_ntfs(&config.drive) } {
+                let Some(drive) = ntfs_drives().into_iter().next() else {
+                    error!("No NTFS volumes to index");
+
+                    return Ok(Box::new(Unindexable {
+                        message: "No NTFS volumes available to index".to_string(),
+                    }));
+                };
+
+                warn!(
+                    "{} is missing or isn't NTFS, indexing {drive} instead",
+                    config.drive
+                );
+                config.drive = drive;
+            }
+
+            let volume = match Volume::new(format!(r"\\.\{}", config.drive)) {
+                Ok(volume) => volume,
+                Err(err) => {
+                    error!("Failed to open {}: {err}", config.drive);
+
+                    return Ok(Box::new(Unindexable::from_error(&config.drive, &err)));
+                }
+            };
+
+            // Kept for reading single records once the MFT has been dropped
+            let records = RecordReader::new(volume.clone())
+                .inspect_err(|err| error!("Failed to open the volume for record reads: {err}"))
+                .ok();
+
+            // Changes made while the MFT is read are applied from the journal afterwards
+            let start_usn = journal::next_usn(&config.drive);
+
+            let mft = match Mft::new(volume) {
+                Ok(mft) => mft,
+                Err(err) => {
+                    error!("Failed to read the MFT of {}: {err}", config.drive);
+
+                    return Ok(Box::new(Unindexable::from_error(&config.drive, &err)));
+                }
+            };
+
+            let rx = journal::spawn_reader(
+                config.drive.clone(),
+                start_usn,
+                Duration::from_millis(config.journal_poll_interval_ms),
+            );
+
+            let mut filesystem = FileSystem::from_mft(
+                &mft,
+                format!(r"{}\", config.drive).into(),
+                IndexOptions {
+                    low_memory: args.low_memory,
+                    unicode_case: config.unicode_case,
+                    modified_since: args.since,
+                    short_names: config.short_names,
+                },
+            );
+
+            // manually drop mft as otherwise it will hog memory, `records` can re-read single
+            // records from the disk when needed
+            drop(mft);
+
+            if let Some(root) = &args.root {
+                if !filesystem.retain_subtree(root) {
+                    warn!(
+                        "{} isn't an indexed directory, indexing the whole volume",
+                        root.display()
+                    );
+                }
+            }
+
+            let stats = filesystem.stats(start.elapsed());
+
+            info!(
+                "Took {}ms to read MFT, {} files",
+                stats.scan_ms, stats.files
+            );
+
+            let low_memory_saved = filesystem
+                .low_memory
+                .then(|| filesystem.lowercase_filenames_size());
+
+            if config.show_child_counts || config.recursive_folder_sizes {
+                filesystem.build_children();
+            }
+
+            let mut filters = FilterState::default();
+            (filters.required_attributes, filters.excluded_attributes) = config.attribute_masks();
+            filters.empty_filter = config.empty_filter;
+            filters.match_case = config.match_case;
+
+            filesystem.date_kind = config.date_kind;
+            filesystem.set_exclude_system_files(config.exclude_system_files);
+            filesystem.case_insensitive_sort = config.case_insensitive_sort;
+            if let Some(pinned) = config.pinned_by_drive.get(&config.drive) {
+                filesystem.pinned.clone_from(pinned);
+            }
+            filesystem.set_threads(config.search_threads);
+            filesystem.subscribe(|event| {
+                trace!(
+                    "{:?} FRN {} {}",
+                    event.kind,
+                    event.frn,
+                    event
+                        .path
+                        .as_deref()
+                        .unwrap_or(Path::new(UNKNOWN_FOLDER))
+                        .display()
+                );
+            });
+            filesystem.show_all(&filters);
+
+            // The tree view is only readable sorted by path
+            if config.tree_view {
+                filesystem.order = FileOrder::Path;
+                filesystem.direction = SortDirection::Ascending;
+                filesystem.sort();
+            }
+
+            let clipboard_listener = config
+                .monitor_clipboard
+                .then(|| clipboard::spawn_listener(cc.egui_ctx.clone()));
+
+            let indexed_drive = config.drive.clone();
+
             Ok(Box::new(FileSearch {
                 filesystem,
+                filters,
+                config,
+                indexed_drive,
+                drives: None,
                 search: String::new(),
                 previous_search: String::new(),
+                last_search: String::new(),
+                pending_changes: FxHashSet::default(),
                 record_rx: rx,
                 icon_cache: FxHashMap::default(),
                 default_icon: None,
                 folder_icon: None,
+                selected: None,
+                last_reasons: FxHashMap::default(),
+                show_last_reason: false,
+                theme: cc.egui_ctx.theme(),
+                icon_pixels_per_point: cc.egui_ctx.pixels_per_point(),
+                journal_gap: false,
+                frozen: None,
+                frozen_records: Vec::new(),
+                show_deleted: false,
+                inspector: Inspector::default(),
+                records,
+                renaming: None,
+                capturing_hotkey: None,
+                low_memory_saved,
+                content_query: String::new(),
+                content_search: None,
+                signature_scan: None,
+                detected_types: FxHashMap::default(),
+                reparse_targets: FxHashMap::default(),
+                missing_check: None,
+                index_root: args.root.clone(),
+                rebuild: None,
+                rebuild_error: None,
+                preview: None,
+                stats,
+                hash: None,
+                hash_message: None,
+                in_use: None,
+                recycle_bin: None,
+                script_dialog: None,
+                preset_name: String::new(),
+                compare_first: None,
+                comparison: None,
+                clipboard_listener,
+                show_settings: false,
+                max_depth: 2,
+                recent_filter: None,
+                browsing: None,
+                type_ahead: String::new(),
+                type_ahead_time: 0.0,
+                uncounted_folders: Vec::new(),
+                extension_facets: Vec::new(),
+                facets_key: None,
+                scroll_to_row: None,
             }))
         }),
     )
 }
 
+// Two files shown side by side, by FRN as positions can change while the window is open
+struct Comparison {
+    frns: [u64; 2],
+    hashes: [Option<FileHash>; 2],
+    // The SHA-256 digests or why hashing failed
+    digests: [Option<Result<String, String>>; 2],
+}
+
+// Settings of the "Export as script" window
+struct ScriptDialog {
+    kind: ScriptKind,
+    operation: ScriptOperation,
+    destination: String,
+    // Where the script is saved
+    path: String,
+    // Where it was saved or why it failed
+    message: Option<String>,
+}
+
+// Shown instead of the search when no drive could be opened, reading the MFT needs admin rights
+struct Unindexable {
+    message: String,
+}
+
+impl Unindexable {
+    fn from_error(drive: &str, err: &NtfsReaderError) -> Self {
+        let message = match err {
+            NtfsReaderError::ElevationError => {
+                format!("Reading {drive} needs administrator rights")
+            }
+            err => format!("Couldn't read {drive}: {err}"),
+        };
+
+        Unindexable { message }
+    }
+}
+
+impl eframe::App for Unindexable {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 3.0);
+                ui.heading(&self.message);
+                ui.label("Try running as administrator");
+                ui.add_space(8.0);
+
+                if ui.button("Restart as administrator").clicked() {
+                    match std::env::current_exe() {
+                        // The elevated copy takes over, a cancelled UAC prompt keeps this one open
+                        Ok(exe) if unsafe { shell::run_as_admin(&exe) } => {
+                            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                        }
+                        Ok(_) => warn!("Failed to restart as administrator"),
+                        Err(err) => error!("Failed to find the executable: {err}"),
+                    }
+                }
+            });
+        });
+    }
+}
+
 struct FileSearch {
     filesystem: FileSystem,
+    // Set from the UI, every search combines them with the filters in its query
+    filters: FilterState,
+    config: Config,
     search: String,
     previous_search: String,
-    record_rx: Receiver<UsnRecord>,
+    // FRNs of the files created, renamed or deleted since the results were last searched for that
+    // were or now would be shown, applied once rows are allowed to move
+    pending_changes: FxHashSet<u64>,
+    // The most recent search that wasn't empty, F5 or Ctrl+R runs it again
+    last_search: String,
+    record_rx: Receiver<JournalMessage>,
     // --- Icon Cache ---
     icon_cache: FxHashMap<String, Option<TextureHandle>>, // Key: lowercase extension or "<FOLDER>" or "<NO_EXT>"
     default_icon: Option<TextureHandle>,
     folder_icon: Option<TextureHandle>,
+    // FRN of the highlighted file, positions change as other files are deleted
+    selected: Option<u64>,
+    // Key: FRN, the reason flags and USN of the most recent journal record for that file, only
+    // for indexed files
+    last_reasons: FxHashMap<u64, (u32, i64)>,
+    show_last_reason: bool,
+    // Theme the cached icons were fetched in
+    theme: egui::Theme,
+    // Display scaling the cached icons were fetched for
+    icon_pixels_per_point: f32,
+    // The journal lost records before they were read so the index could be stale
+    journal_gap: bool,
+    // Local time the index was frozen at, journal records are kept in `frozen_records` instead of
+    // being applied so searches keep giving the same results
+    frozen: Option<String>,
+    frozen_records: Vec<JournalMessage>,
+    show_deleted: bool,
+    inspector: Inspector,
+    // None if the volume couldn't be opened
+    records: Option<RecordReader>,
+    // FRN of the file being renamed and the new name
+    renaming: Option<(u64, String)>,
+    // Index of the hotkey waiting for a key press to rebind it
+    capturing_hotkey: Option<usize>,
+    // Estimated bytes not used for lowercase filenames, if running with --low-memory
+    low_memory_saved: Option<usize>,
+    content_query: String,
+    content_search: Option<ContentSearch>,
+    signature_scan: Option<SignatureScan>,
+    // Key: FRN, the types detected from the first bytes of the files, the Type column is shown
+    // while it isn't empty
+    detected_types: FxHashMap<u64, &'static str>,
+    // Key: FRN, where hovered junctions and symlinks point, None if it couldn't be resolved
+    reparse_targets: FxHashMap<u64, Option<String>>,
+    // Started the first time a row is shown with `check_missing_files` on
+    missing_check: Option<MissingCheck>,
+    // The --root directory, kept for rebuilding the index
+    index_root: Option<PathBuf>,
+    // Of the current index, `config.drive` is changed before the rebuild finishes
+    indexed_drive: String,
+    // NTFS drives for the Drive setting, read when its list is opened
+    drives: Option<Vec<String>>,
+    rebuild: Option<Rebuild>,
+    // Why the last rebuild failed
+    rebuild_error: Option<String>,
+    // Of the selected file, if the preview pane is shown
+    preview: Option<Preview>,
+    // Of the last time the index was read
+    stats: IndexStats,
+    hash: Option<FileHash>,
+    // The copied digest or why hashing failed
+    hash_message: Option<String>,
+    // Name of the file last checked for processes using it and the processes or why the check
+    // failed, shown in a window until it is closed
+    in_use: Option<(String, Result<Vec<String>, String>)>,
+    // What is in the Recycle Bin, read when its window is opened
+    recycle_bin: Option<Vec<RecycledFile>>,
+    script_dialog: Option<ScriptDialog>,
+    // Typed name for saving the current columns as a layout
+    preset_name: String,
+    // FRN of the file picked to be compared with the next one
+    compare_first: Option<u64>,
+    comparison: Option<Comparison>,
+    // Only listens once clipboard monitoring has been enabled
+    clipboard_listener: Option<ClipboardListener>,
+    show_settings: bool,
+    // FRN of the folder whose contents are shown instead of the search results
+    browsing: Option<u64>,
+    // Of the depth filter, kept while it is turned off
+    max_depth: usize,
+    // Index into `RECENT_FILTERS` of the toggled quick filter
+    recent_filter: Option<usize>,
+    // Lowercase text typed while the results have focus and when the last key was typed
+    type_ahead: String,
+    type_ahead_time: f64,
+    // Row the table scrolls to on the next frame
+    scroll_to_row: Option<usize>,
+    // FRNs of the shown folders whose total size hasn't been counted yet
+    uncounted_folders: Vec<u64>,
+    // Most common extensions in the results with their counts
+    extension_facets: Vec<(Box<str>, usize)>,
+    // The search, folder and result count the facets were counted for
+    facets_key: Option<(String, Option<u64>, usize)>,
 }
 
 impl FileSearch {
@@ -261,7 +822,8 @@ impl FileSearch {
             FILE_ATTRIBUTE_NORMAL
         };
 
-        let texture_opt = unsafe { fetch_and_convert_icon(ctx, path, attr_flag.0) };
+        let texture_opt =
+            unsafe { fetch_and_convert_icon(ctx, path, attr_flag.0, icon_pixels(ctx)) };
 
         if is_directory {
             self.folder_icon.clone_from(&texture_opt); // cache specific folder icon
@@ -274,221 +836,2874 @@ impl FileSearch {
         texture_opt
     }
 
-    fn get_default_icon(&mut self, ctx: &egui::Context) -> Option<TextureHandle> {
-        if self.default_icon.is_none() {
-            // Try to load a truly generic icon using 0 file attributes? Or known file?
-            // Let's try getting icon for a non-existent file with .txt extension attributes
-            let dummy_path = Path::new("dummy.txt");
-            self.default_icon =
-                unsafe { fetch_and_convert_icon(ctx, dummy_path, FILE_ATTRIBUTE_NORMAL.0) };
+    fn search_changed(&mut self) {
+        self.selected = None;
+        self.pending_changes.clear();
+        // The shown files were a folder's contents rather than search results
+        let was_browsing = self.browsing.take().is_some();
 
-            // Fallback if fetching generic icon fails: create a placeholder egui image
-            if self.default_icon.is_none() {
-                let fallback_image = ColorImage::new([16, 16], egui::Color32::from_gray(200));
-                self.default_icon = Some(ctx.load_texture(
-                    "__default_icon__",                      // Use distinct name
-                    ImageData::Color(fallback_image.into()), // Use ImageData enum
-                    TextureOptions::LINEAR,                  // Use enum variant
-                ));
-            }
+        // Its results are for the files that were shown
+        if let Some(content_search) = self.content_search.take() {
+            content_search.cancel();
+        }
+
+        if !self.search.trim().is_empty() {
+            self.last_search = self.search.trim().to_string();
+        }
+
+        // Spaces around the search are ignored, so a search of only spaces shows everything
+        let search = self.search.trim();
+        let previous_search = self.previous_search.trim();
+
+        // A search that hasn't finished only has partial results so it can't be narrowed
+        // A glob with more characters can match files the shorter one didn't, e.g. C:\* -> C:\*\*
+        // and so can wildcards, e.g. *.tx -> *.txt
+        let can_narrow = !self.filesystem.is_searching()
+            && !self.filesystem.path_glob
+            && !(self.filesystem.wildcards && query::has_wildcards(search))
+            && !query::has_filters(search)
+            && !was_browsing
+            && filesystem::narrows(
+                search,
+                previous_search,
+                self.config.unicode_case,
+                self.filters.match_case,
+            );
+
+        self.filesystem.pending_search = None;
+
+        if search.is_empty() {
+            self.filesystem.show_all(&self.filters);
+        } else if let Some(position) = self.filesystem.find_path(Path::new(search)) {
+            // A pasted path that is in the index, so just show that file
+            self.filesystem.set_shown(vec![position]);
+            self.selected = Some(self.filesystem.frn_mapping[position]);
+        } else if can_narrow {
+            // Only search the currently shown files
+            self.filesystem.search_shown(search, &self.filters);
+        } else {
+            self.filesystem.start_search(search, &self.filters);
         }
-        self.default_icon.clone()
     }
-}
 
-impl eframe::App for FileSearch {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.record_rx.try_iter().for_each(|record| {
-            // https://learn.microsoft.com/en-us/windows/win32/api/winioctl/ns-winioctl-read_usn_journal_data_v1
+    // Searches the current index for the last search again, e.g. after files were moved
+    fn rerun_last_search(&mut self) {
+        if self.last_search.is_empty() {
+            return;
+        }
 
-            if record.reason & Ioctl::USN_REASON_FILE_DELETE != 0 {
-                self.filesystem.delete(record.file_id);
-            }
+        self.search.clone_from(&self.last_search);
+        self.browsing = None;
+        self.refresh_results();
+    }
 
-            // The file or directory is renamed, and the file name in the USN_RECORD structure holding this journal record is the new name.
-            if record.reason & Ioctl::USN_REASON_RENAME_NEW_NAME != 0 {
-                self.filesystem
-                    .rename(record.file_id, record.parent_id, &record.path);
-            }
+    // Runs the search again from scratch after a filter changed
+    fn refresh_results(&mut self) {
+        self.pending_changes.clear();
 
-            if record.reason & Ioctl::USN_REASON_FILE_CREATE != 0 {
-                self.filesystem
-                    .create(record.file_id, record.parent_id, &record.path);
-            }
+        if let Some(frn) = self.browsing {
+            self.filesystem.show_children(frn, &self.filters);
+            return;
+        }
+
+        self.previous_search.clear();
+        self.search_changed();
+
+        // Searches sort once they finish but showing everything doesn't
+        if !self.filesystem.is_searching() {
+            self.filesystem.sort();
+        }
+    }
 
-            // A user has either changed one or more file or directory attributes
-            // (such as the read-only, hidden, system, archive, or sparse attribute), or one or more time stamps.
-            if record.reason & Ioctl::USN_REASON_BASIC_INFO_CHANGE != 0 {
-                self.filesystem
-                    .update(record.file_id, record.parent_id, &record.path);
+    fn sort_header(&mut self, ui: &mut egui::Ui, label: &str, order: FileOrder) {
+        let is_sorted = self.filesystem.order == order;
+
+        let indicator = if is_sorted {
+            if self.filesystem.direction == SortDirection::Ascending {
+                " ↑"
+            } else {
+                " ↓"
             }
+        } else {
+            ""
+        };
 
-            // shouldn't need to handle this as we can get all the information we need in the NEW_NAME record
-            // The file or directory is renamed, and the file name in the USN_RECORD structure holding this journal record is the previous name
-            // if record.reason & Ioctl::USN_REASON_RENAME_OLD_NAME != 0 {}
-        });
+        let button =
+            Button::new(RichText::new(format!("{label}{indicator}")).heading()).frame(false);
 
-        egui::TopBottomPanel::top("top").show(ctx, |ui| {
-            let resp =
-                ui.add(egui::TextEdit::singleline(&mut self.search).desired_width(f32::INFINITY));
+        if ui.add(button).clicked() {
+            if is_sorted {
+                self.filesystem.reverse();
+            } else {
+                self.filesystem.order = order;
+                self.filesystem.direction = SortDirection::Descending;
 
-            if resp.changed() {
-                if self.search.is_empty() {
-                    self.filesystem.shown = (0..self.filesystem.filenames.len()).collect();
-                } else {
-                    if !self.previous_search.is_empty()
-                        && self.search.contains(&self.previous_search)
-                    {
-                        // Might have to use starts_with instead of contains
-                        // Only search the currently shown files
-                        self.filesystem.search_shown(&self.search);
-                    } else {
-                        self.filesystem.search(&self.search);
-                    }
-                }
+                self.filesystem.sort();
             }
+        }
+    }
 
-            self.previous_search.clone_from(&self.search);
+    fn result_text(&self, text: impl Into<String>) -> RichText {
+        let text = RichText::new(text);
 
-            ui.separator();
-        });
+        if self.config.monospace_results {
+            text.monospace()
+        } else {
+            text
+        }
+    }
 
-        let total_rows = self.filesystem.shown.len();
+    fn perform(&mut self, ctx: &egui::Context, action: Action, position: usize) {
+        let Some(full_path) = self.filesystem.full_path(position) else {
+            warn!(
+                "The folder of {} isn't indexed yet",
+                self.filesystem.filenames[position]
+            );
+            return;
+        };
 
-        egui::TopBottomPanel::bottom("bottom").show(ctx, |ui| {
-            // ui.separator();
+        match action {
+            Action::Open => {
+                let _ = unsafe { shell::open(&full_path) };
+            }
+            Action::Reveal => {
+                let _ = unsafe { shell::reveal(&full_path, &self.config.reveal_command) };
+            }
+            Action::CopyPath => ctx.copy_text(full_path.to_string_lossy().to_string()),
+            Action::Rename => {
+                self.renaming = Some((
+                    self.filesystem.frn_mapping[position],
+                    self.filesystem.filenames[position].to_string(),
+                ));
+            }
+            // The journal removes it from the index once it has been moved
+            Action::Recycle => {
+                let _ = unsafe { shell::recycle(&full_path) };
+            }
+        }
+    }
 
-            ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
-                ui.label(format!("{total_rows} files"));
-            });
-        });
+    fn action_button(&mut self, ui: &mut egui::Ui, action: Action, position: usize) {
+        let mut button = Button::new(action.name());
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let column_width = ui.available_width() / 2.0;
-            let height = ui.available_height();
-            let table = TableBuilder::new(ui)
-                // .striped(true)
-                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-                .max_scroll_height(height) // Without this there is a weird empty space below the table
-                .column(Column::exact(column_width.min(400.0)))
-                .column(Column::remainder())
-                .column(Column::remainder());
+        if let Some(shortcut) = hotkeys::shortcut_for(&self.config.hotkeys, action) {
+            button = button.shortcut_text(ui.ctx().format_shortcut(shortcut));
+        }
 
-            table
-                .header(20.0, |mut header| {
-                    header.col(|ui| {
-                        let is_sorted_by_name = self.filesystem.order == FileOrder::Name;
-
-                        let indicator = if is_sorted_by_name {
-                            if self.filesystem.direction == SortDirection::Ascending {
-                                " ↑"
-                            } else {
-                                " ↓"
-                            }
-                        } else {
-                            ""
-                        };
+        if ui.add(button).clicked() {
+            self.perform(ui.ctx(), action, position);
+            ui.close_menu();
+        }
+    }
 
-                        let name_button =
-                            Button::new(RichText::new(format!("Name{}", indicator)).heading())
-                                .frame(false);
+    fn handle_hotkeys(&mut self, ctx: &egui::Context) {
+        if let Some(index) = self.capturing_hotkey {
+            let pressed = ctx.input(|i| {
+                i.events.iter().find_map(|event| match event {
+                    egui::Event::Key {
+                        key,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } => Some(egui::KeyboardShortcut::new(*modifiers, *key)),
+                    _ => hotkeys::clipboard_key(event)
+                        .map(|key| egui::KeyboardShortcut::new(i.modifiers, key)),
+                })
+            });
 
-                        if ui.add(name_button).clicked() {
-                            if is_sorted_by_name {
-                                self.filesystem.direction =
-                                    if self.filesystem.direction == SortDirection::Ascending {
-                                        SortDirection::Descending
-                                    } else {
-                                        SortDirection::Ascending
-                                    };
+            if let Some(shortcut) = pressed {
+                if shortcut.logical_key != egui::Key::Escape {
+                    self.config.hotkeys[index].0 = shortcut;
+                }
+                self.capturing_hotkey = None;
+            }
 
-                                self.filesystem.shown.reverse();
-                            } else {
-                                self.filesystem.order = FileOrder::Name;
-                                self.filesystem.direction = SortDirection::Descending;
+            return;
+        }
 
-                                self.filesystem.sort();
-                            }
-                        }
-                    });
-                    header.col(|ui| {
-                        let is_sorted_by_size = self.filesystem.order == FileOrder::Size;
-
-                        let indicator = if is_sorted_by_size {
-                            if self.filesystem.direction == SortDirection::Ascending {
-                                " ↑"
-                            } else {
-                                " ↓"
-                            }
-                        } else {
-                            ""
-                        };
+        // Don't steal keys from the search box or the rename dialog
+        if ctx.wants_keyboard_input() {
+            return;
+        }
 
-                        let size_button =
-                            Button::new(RichText::new(format!("File Size{}", indicator)).heading())
-                                .frame(false);
+        if let Some(position) = self.selected_position() {
+            let action = ctx.input_mut(|i| {
+                let clipboard_keys: Vec<egui::Key> =
+                    i.events.iter().filter_map(hotkeys::clipboard_key).collect();
+
+                self.config
+                    .hotkeys
+                    .iter()
+                    .find(|(shortcut, _)| {
+                        i.consume_shortcut(shortcut)
+                            || (clipboard_keys.contains(&shortcut.logical_key)
+                                && i.modifiers.matches_logically(shortcut.modifiers))
+                    })
+                    .map(|(_, action)| *action)
+            });
 
-                        if ui.add(size_button).clicked() {
-                            if is_sorted_by_size {
-                                self.filesystem.direction =
-                                    if self.filesystem.direction == SortDirection::Ascending {
-                                        SortDirection::Descending
-                                    } else {
-                                        SortDirection::Ascending
-                                    };
+            if let Some(action) = action {
+                self.perform(ctx, action, position);
+                return;
+            }
+        }
 
-                                self.filesystem.shown.reverse();
-                            } else {
-                                self.filesystem.order = FileOrder::Size;
-                                self.filesystem.direction = SortDirection::Descending;
+        self.type_ahead(ctx);
+    }
 
-                                self.filesystem.sort();
-                            }
-                        }
-                    });
-                    header.col(|ui| {
-                        ui.heading("Path");
-                    });
+    // Explorer-style, typing selects the next result whose filename starts with the typed text
+    fn type_ahead(&mut self, ctx: &egui::Context) {
+        let (typed, time) = ctx.input(|i| {
+            let typed: String = i
+                .events
+                .iter()
+                .filter_map(|event| match event {
+                    egui::Event::Text(text) => Some(text.as_str()),
+                    _ => None,
                 })
-                .body(|body| {
-                    body.rows(18.0, total_rows, |mut row| {
-                        let index = self.filesystem.shown[row.index()];
+                .collect();
 
-                        let mut full_path = self.filesystem.path(index);
+            (typed, i.time)
+        });
 
-                        let path = full_path.to_string_lossy().to_string();
+        if typed.is_empty() {
+            return;
+        }
 
-                        full_path.push(&*self.filesystem.filenames[index]);
+        if time - self.type_ahead_time > TYPE_AHEAD_TIMEOUT {
+            self.type_ahead.clear();
+        }
+        self.type_ahead_time = time;
+        self.type_ahead
+            .push_str(&filesystem::lowercase(&typed, self.config.unicode_case));
 
-                        let icon_texture = self
-                            .get_texture_handle(ctx, &full_path)
-                            .or_else(|| self.get_default_icon(ctx))
-                            .unwrap(); // guaranteed for there to be a default icon
+        // Typing the same letter again cycles through the files starting with it
+        let mut chars = self.type_ahead.chars();
+        let first = chars.next();
+        let repeated = chars.all(|c| Some(c) == first);
+
+        let prefix = if repeated {
+            &self.type_ahead[..first.map_or(0, char::len_utf8)]
+        } else {
+            &self.type_ahead
+        };
+
+        let shown = &self.filesystem.shown;
+        let current_row = self
+            .selected_position()
+            .and_then(|selected| shown.iter().position(|&position| position == selected));
+
+        // A longer prefix can still match the selected file
+        let start = match current_row {
+            Some(row) if repeated => row + 1,
+            Some(row) => row,
+            None => 0,
+        };
+
+        let row = (start..shown.len()).chain(0..start).find(|&row| {
+            self.filesystem
+                .lowercase_filename(shown[row])
+                .starts_with(prefix)
+        });
+
+        if let Some(row) = row {
+            self.selected = Some(self.filesystem.frn_mapping[shown[row]]);
+            self.scroll_to_row = Some(row);
+        }
+    }
+
+    fn context_menu(&mut self, ui: &mut egui::Ui, position: usize, path: &str, full_path: &Path) {
+        self.action_button(ui, Action::Open, position);
+
+        ui.menu_button("Open with", |ui| {
+            let extension = full_path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase());
+
+            let mut chosen_app = None;
+
+            if let Some(apps) = extension
+                .as_ref()
+                .and_then(|ext| self.config.recent_apps.get(ext))
+            {
+                for app in apps {
+                    let name = app.file_stem().unwrap_or(app.as_os_str());
+
+                    if ui
+                        .button(name.to_string_lossy())
+                        .on_hover_text(app.to_string_lossy())
+                        .clicked()
+                    {
+                        if unsafe { shell::open_with(app, full_path) } {
+                            chosen_app = Some(app.clone());
+                        }
+                        ui.close_menu();
+                    }
+                }
+
+                ui.separator();
+            }
+
+            if ui.button("Choose another app…").clicked() {
+                // The dialog doesn't say which app was picked but Windows puts it first in the
+                // extension's Open with list, nothing to remember if it was cancelled
+                if unsafe { shell::open_with_dialog(full_path) } {
+                    chosen_app = extension
+                        .as_ref()
+                        .and_then(|ext| unsafe { shell::last_open_with_app(ext) });
+                }
+                ui.close_menu();
+            }
+
+            if let (Some(ext), Some(app)) = (&extension, chosen_app) {
+                self.config.add_recent_app(ext, app);
+            }
+        });
+
+        self.action_button(ui, Action::Reveal, position);
+
+        ui.menu_button("Copy", |ui| {
+            let size = self.filesystem.filesizes[position];
+            let modified = self.filesystem.modified_dates[position];
+
+            let mut copies = vec![
+                ("Path", path.to_string()),
+                ("Quoted path", quote_path(&full_path.to_string_lossy())),
+                (
+                    "Forward-slash path",
+                    forward_slash_path(&full_path.to_string_lossy()),
+                ),
+                ("Filename", self.filesystem.filenames[position].to_string()),
+                (
+                    "Markdown link",
+                    markdown_link(
+                        &self.filesystem.filenames[position],
+                        &full_path.to_string_lossy(),
+                    ),
+                ),
+                ("Size", format_size(size)),
+                ("Size in bytes", size.to_string()),
+            ];
+
+            if let Some(base) = &self.config.relative_base {
+                copies.push(("Relative path", relative_path(full_path, base)));
+            }
+
+            // Files created since the index was loaded don't have a date yet
+            if let Some(modified) = modified {
+                copies.push(("Modified date", format_date(modified)));
+            }
+
+            for (label, text) in copies {
+                if ui.button(label).on_hover_text(&text).clicked() {
+                    ui.ctx().copy_text(text);
+                    ui.close_menu();
+                }
+            }
+
+            if self.filesystem.is_directory(position)
+                && ui
+                    .button("Set as relative path base")
+                    .on_hover_text("Relative paths copied after this are relative to this folder")
+                    .clicked()
+            {
+                self.config.relative_base = Some(full_path.to_path_buf());
+                ui.close_menu();
+            }
+
+            if let Some(base) = &self.config.relative_base {
+                if ui
+                    .button("Clear relative path base")
+                    .on_hover_text(base.to_string_lossy())
+                    .clicked()
+                {
+                    self.config.relative_base = None;
+                    ui.close_menu();
+                }
+            }
+
+            if !self.filesystem.is_directory(position) {
+                ui.separator();
+
+                for algorithm in HashAlgorithm::ALL {
+                    if ui.button(format!("{} hash", algorithm.name())).clicked() {
+                        if let Some(hash) = self.hash.take() {
+                            hash.cancel();
+                        }
+
+                        self.hash = Some(FileHash::start(
+                            ui.ctx().clone(),
+                            full_path.to_path_buf(),
+                            self.filesystem.filenames[position].to_string(),
+                            size,
+                            algorithm,
+                        ));
+                        ui.close_menu();
+                    }
+                }
+            }
+        });
+
+        if ui.button("Open terminal here").clicked() {
+            let directory = if full_path.is_dir() {
+                full_path
+            } else {
+                Path::new(path)
+            };
+
+            let _ = unsafe { shell::open_terminal(self.config.terminal, directory) };
+            ui.close_menu();
+        }
+
+        if shell::is_executable(full_path) && ui.button("Run as administrator").clicked() {
+            // Nothing to do if the UAC prompt was cancelled
+            let _ = unsafe { shell::run_as_admin(full_path) };
+            ui.close_menu();
+        }
+
+        if !self.filesystem.is_directory(position)
+            && ui
+                .button("Find what's using it")
+                .on_hover_text(
+                    "List the apps that have the file open, e.g. when it can't be deleted",
+                )
+                .clicked()
+        {
+            let processes =
+                unsafe { locks::processes_using(full_path) }.map_err(|err| err.message());
+
+            self.in_use = Some((self.filesystem.filenames[position].to_string(), processes));
+            ui.close_menu();
+        }
+
+        let frn = self.filesystem.frn_mapping[position];
+        let first = self
+            .compare_first
+            .filter(|&first| first != frn)
+            .and_then(|first| Some((first, self.filesystem.position_of(first)?)));
+
+        if let Some((first, first_position)) = first {
+            if ui
+                .button(format!(
+                    "Compare with {}",
+                    self.filesystem.filenames[first_position]
+                ))
+                .clicked()
+            {
+                self.comparison = Some(Comparison {
+                    frns: [first, frn],
+                    hashes: [None, None],
+                    digests: [None, None],
+                });
+                self.compare_first = None;
+                ui.close_menu();
+            }
+        } else if ui
+            .button("Select for compare")
+            .on_hover_text("Then compare it with another file from that file's menu")
+            .clicked()
+        {
+            self.compare_first = Some(frn);
+            ui.close_menu();
+        }
+
+        if ui
+            .button("Show folder contents")
+            .on_hover_text("Everything in the same folder, the search comes back with Back")
+            .clicked()
+        {
+            self.browse(self.filesystem.parent_mapping[position]);
+            ui.close_menu();
+        }
+
+        if self.filesystem.is_directory(position) && ui.button("Show contents").clicked() {
+            self.browse(self.filesystem.frn_mapping[position]);
+            ui.close_menu();
+        }
+
+        if let Some(extension) = self.filesystem.extension(position) {
+            if ui
+                .button(format!("Hide all .{extension}"))
+                .on_hover_text("Until restarting, shown above the results")
+                .clicked()
+            {
+                self.filters.hidden_extensions.insert(extension);
+                self.refresh_results();
+                ui.close_menu();
+            }
+        }
+
+        let pinned = self.filesystem.is_pinned(position);
+
+        if ui
+            .button(if pinned { "Unpin" } else { "Pin to top" })
+            .clicked()
+        {
+            let frn = self.filesystem.frn_mapping[position];
+
+            let pinned_files = self
+                .config
+                .pinned_by_drive
+                .entry(self.indexed_drive.clone())
+                .or_default();
+
+            if pinned {
+                pinned_files.remove(&frn);
+            } else {
+                pinned_files.insert(frn);
+            }
+
+            self.filesystem.pinned.clone_from(pinned_files);
+            self.filesystem.sort();
+            ui.close_menu();
+        }
+
+        ui.separator();
+
+        self.action_button(ui, Action::Rename, position);
+        self.action_button(ui, Action::Recycle, position);
+    }
+
+    fn browse(&mut self, frn: u64) {
+        self.browsing = Some(frn);
+        self.selected = None;
+        self.filesystem.show_children(frn, &self.filters);
+    }
+
+    // Back to the search results and the folders above the one being browsed
+    fn breadcrumb(&mut self, ui: &mut egui::Ui) {
+        let Some(frn) = self.browsing else {
+            return;
+        };
+
+        let mut clicked = None;
+        let mut back = false;
+
+        ui.horizontal_wrapped(|ui| {
+            back = ui
+                .button("← Back")
+                .on_hover_text("Show the search results again")
+                .clicked();
+
+            let volume_path = self.filesystem.volume_path.to_string_lossy();
+            let root = if self.config.show_volume_label {
+                self.filesystem.labelled_path(&volume_path).into_owned()
+            } else {
+                volume_path.into_owned()
+            };
+
+            if ui
+                .selectable_label(frn == self.filesystem.root_frn, root)
+                .clicked()
+            {
+                clicked = Some(self.filesystem.root_frn);
+            }
+
+            for (folder, name) in self.filesystem.folder_chain(frn) {
+                ui.label("›");
+
+                if ui.selectable_label(folder == frn, name).clicked() {
+                    clicked = Some(folder);
+                }
+            }
+        });
+
+        if back {
+            self.browsing = None;
+            self.refresh_results();
+        } else if let Some(folder) = clicked {
+            self.browse(folder);
+        }
+    }
+
+    // Score is only shown for fuzzy searches and Type once types have been detected
+    fn visible_columns(&self) -> Vec<ResultColumn> {
+        ResultColumn::ALL
+            .into_iter()
+            .filter(|column| !self.config.hidden_columns.contains(column))
+            .filter(|column| match column {
+                ResultColumn::Score => self.filesystem.fuzzy,
+                ResultColumn::Type => !self.detected_types.is_empty(),
+                _ => true,
+            })
+            .collect()
+    }
+
+    fn column_menu(&mut self, ui: &mut egui::Ui) {
+        for column in ResultColumn::ALL {
+            if column == ResultColumn::Name {
+                continue;
+            }
+
+            let mut visible = !self.config.hidden_columns.contains(&column);
+
+            if ui.checkbox(&mut visible, column.name()).changed() {
+                if visible {
+                    self.config.hidden_columns.remove(&column);
+                } else {
+                    self.config.hidden_columns.insert(column);
+
+                    // The results would be in an order that can't be seen
+                    if column.order() == Some(self.filesystem.order) {
+                        self.filesystem.order = FileOrder::RecordNumber;
+                        self.filesystem.sort();
+                    }
+                }
+            }
+        }
+
+        ui.separator();
+        ui.label("Layouts");
+
+        let mut applied = None;
+        let mut removed = None;
+
+        for (index, preset) in self.config.column_presets.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.button(&preset.name).clicked() {
+                    applied = Some(preset.clone());
+                }
+
+                if ui
+                    .small_button("🗑")
+                    .on_hover_text("Delete this layout")
+                    .clicked()
+                {
+                    removed = Some(index);
+                }
+            });
+        }
+
+        if let Some(preset) = applied {
+            self.apply_column_preset(preset);
+            ui.close_menu();
+        }
+
+        if let Some(index) = removed {
+            self.config.column_presets.remove(index);
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.preset_name)
+                    .hint_text("Layout name")
+                    .desired_width(120.0),
+            );
+
+            let name = self.preset_name.trim();
+
+            if ui
+                .add_enabled(!name.is_empty(), Button::new("Save"))
+                .on_hover_text("Save the shown columns and the sort as a layout")
+                .clicked()
+            {
+                let preset = ColumnPreset {
+                    name: name.to_string(),
+                    hidden_columns: self.config.hidden_columns.clone(),
+                    date_kind: self.config.date_kind,
+                    order: self.filesystem.order,
+                    direction: self.filesystem.direction,
+                };
+
+                // Saving with the name of a layout replaces it
+                match self
+                    .config
+                    .column_presets
+                    .iter_mut()
+                    .find(|existing| existing.name == preset.name)
+                {
+                    Some(existing) => *existing = preset,
+                    None => self.config.column_presets.push(preset),
+                }
+
+                self.preset_name.clear();
+            }
+        });
+    }
+
+    // One line about the results for pasting into a chat
+    fn results_summary(&self) -> String {
+        let count = self.filesystem.shown.len();
+        let files = if count == 1 { "file" } else { "files" };
+
+        let mut summary = format!(
+            "{} {files} ({})",
+            format_count(count),
+            format_size(self.filesystem.shown_size())
+        );
+
+        let search = self.search.trim();
+
+        if let Some(frn) = self.browsing {
+            if let Some(path) = self
+                .filesystem
+                .position_of(frn)
+                .and_then(|position| self.filesystem.full_path(position))
+            {
+                summary.push_str(&format!(" in {}", path.display()));
+            }
+        } else if !search.is_empty() {
+            summary.push_str(&format!(" matching '{search}'"));
+        }
+
+        summary
+    }
+
+    fn apply_column_preset(&mut self, preset: ColumnPreset) {
+        self.config.hidden_columns = preset.hidden_columns;
+        self.config.date_kind = preset.date_kind;
+        self.filesystem.date_kind = preset.date_kind;
+        self.filesystem.order = preset.order;
+        self.filesystem.direction = preset.direction;
+        self.filesystem.sort();
+    }
+
+    // Counts the sizes of the folders that were shown without one last frame, a few per frame
+    fn count_folder_sizes(&mut self, ctx: &egui::Context) {
+        if self.uncounted_folders.is_empty() {
+            return;
+        }
+
+        let deadline = std::time::Instant::now() + FOLDER_SIZE_BUDGET;
+        let mut folders = std::mem::take(&mut self.uncounted_folders);
+        folders.dedup();
+
+        for frn in folders {
+            if self.filesystem.count_folder_size(frn, deadline).is_none() {
+                // Carried on next frame, the rest are shown again without a size
+                break;
+            }
+        }
+
+        ctx.request_repaint();
+    }
+
+    // The files in the Recycle Bin whose original name contains the search
+    fn recycle_bin_window(&mut self, ctx: &egui::Context) {
+        let Some(files) = &self.recycle_bin else {
+            return;
+        };
+
+        let search = filesystem::lowercase(self.search.trim(), self.config.unicode_case);
+
+        let mut open = true;
+        let mut refresh = false;
+
+        egui::Window::new("Recycle Bin")
+            .open(&mut open)
+            .default_size([700.0, 300.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} files, filtered by the search", files.len()));
+                    refresh = ui.button("Refresh").clicked();
+                });
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("recycle_bin")
+                        .num_columns(4)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Name");
+                            ui.strong("Size");
+                            ui.strong("Deleted");
+                            ui.strong("Original folder");
+                            ui.end_row();
+
+                            for file in files.iter().filter(|file| {
+                                filesystem::lowercase(&file.name, self.config.unicode_case)
+                                    .contains(&search)
+                            }) {
+                                ui.label(&file.name)
+                                    .on_hover_text(file.contents.to_string_lossy());
+                                ui.label(format_size(file.size));
+                                ui.label(format_date(file.deleted));
+                                ui.label(file.folder.to_string_lossy());
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+
+        if !open {
+            self.recycle_bin = None;
+        } else if refresh {
+            self.recycle_bin = Some(recycle::scan(&self.filesystem));
+        }
+    }
+
+    fn script_window(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.script_dialog else {
+            return;
+        };
+
+        let mut open = true;
+
+        egui::Window::new("Export as script")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "One command for each of the {} results",
+                    self.filesystem.shown.len()
+                ));
+
+                egui::Grid::new("script_dialog")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Script");
+
+                        let previous_kind = dialog.kind;
+
+                        egui::ComboBox::from_id_salt("script_kind")
+                            .selected_text(dialog.kind.name())
+                            .show_ui(ui, |ui| {
+                                for kind in ScriptKind::ALL {
+                                    ui.selectable_value(&mut dialog.kind, kind, kind.name());
+                                }
+                            });
+
+                        if dialog.kind != previous_kind {
+                            dialog.path = Path::new(&dialog.path)
+                                .with_extension(dialog.kind.extension())
+                                .to_string_lossy()
+                                .to_string();
+                        }
+
+                        ui.end_row();
+
+                        ui.label("Operation");
+
+                        egui::ComboBox::from_id_salt("script_operation")
+                            .selected_text(dialog.operation.name())
+                            .show_ui(ui, |ui| {
+                                for operation in ScriptOperation::ALL {
+                                    ui.selectable_value(
+                                        &mut dialog.operation,
+                                        operation,
+                                        operation.name(),
+                                    );
+                                }
+                            });
+
+                        ui.end_row();
+
+                        ui.label("Destination");
+                        ui.add_enabled(
+                            dialog.operation != ScriptOperation::Delete,
+                            egui::TextEdit::singleline(&mut dialog.destination)
+                                .hint_text(r"D:\Backup"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Save as");
+                        ui.text_edit_singleline(&mut dialog.path);
+                        ui.end_row();
+                    });
+
+                let needs_destination = dialog.operation != ScriptOperation::Delete
+                    && dialog.destination.trim().is_empty();
+
+                if ui
+                    .add_enabled(!needs_destination, egui::Button::new("Save"))
+                    .clicked()
+                {
+                    let script = export::to_script(
+                        &self.filesystem,
+                        &self.filesystem.shown,
+                        dialog.kind,
+                        dialog.operation,
+                        &dialog.destination,
+                    );
+                    let path = Path::new(dialog.path.trim());
+
+                    dialog.message = Some(match export::write_script(path, dialog.kind, &script) {
+                        Ok(()) => format!("Saved to {}", path.display()),
+                        Err(err) => format!("Failed to save: {err}"),
+                    });
+                }
+
+                if let Some(message) = &dialog.message {
+                    ui.label(message);
+                }
+            });
+
+        if !open {
+            self.script_dialog = None;
+        }
+    }
+
+    // The metadata of two files next to each other with the differences highlighted
+    fn comparison_window(&mut self, ctx: &egui::Context) {
+        let Some(frns) = self.comparison.as_ref().map(|comparison| comparison.frns) else {
+            return;
+        };
+
+        let positions = frns.map(|frn| self.filesystem.position_of(frn));
+        let full_paths = positions
+            .map(|position| position.and_then(|position| self.filesystem.full_path(position)));
+
+        let value = |describe: &dyn Fn(usize) -> String| {
+            positions
+                .map(|position| position.map_or_else(|| "Not in the index".to_string(), describe))
+        };
+
+        let mut rows = vec![
+            (
+                "Name",
+                value(&|position| self.filesystem.filenames[position].to_string()),
+            ),
+            (
+                "Folder",
+                value(&|position| self.filesystem.display_path(position)),
+            ),
+            (
+                "Size",
+                value(&|position| {
+                    let size = self.filesystem.filesizes[position];
+                    format!("{} ({size} bytes)", format_size(size))
+                }),
+            ),
+        ];
+
+        for kind in DateKind::ALL {
+            rows.push((
+                kind.name(),
+                value(&|position| {
+                    self.filesystem.dates(kind)[position]
+                        .map(format_date)
+                        .unwrap_or_default()
+                }),
+            ));
+        }
+
+        rows.push((
+            "Attributes",
+            value(&|position| {
+                FILTER_ATTRIBUTES
+                    .iter()
+                    .filter(|(flag, _)| self.filesystem.attributes[position] & flag != 0)
+                    .map(|(_, name)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }),
+        ));
+
+        let both_files = positions.iter().all(|position| {
+            position.is_some_and(|position| !self.filesystem.is_directory(position))
+        });
+        let sizes = positions
+            .map(|position| position.map_or(0, |position| self.filesystem.filesizes[position]));
+        let filenames = rows[0].1.clone();
+
+        let Some(comparison) = &mut self.comparison else {
+            return;
+        };
+
+        for (hash, digest) in comparison.hashes.iter_mut().zip(&mut comparison.digests) {
+            if let Some(result) = hash.as_ref().and_then(FileHash::poll) {
+                *digest = Some(result.map_err(|err| err.to_string()));
+                *hash = None;
+            }
+        }
+
+        let hashing = comparison.hashes.iter().any(Option::is_some);
+
+        rows.push((
+            "SHA-256",
+            std::array::from_fn(|i| match (&comparison.hashes[i], &comparison.digests[i]) {
+                (Some(_), _) => "Hashing…".to_string(),
+                (None, Some(Ok(digest))) => digest.clone(),
+                (None, Some(Err(err))) => format!("Failed: {err}"),
+                (None, None) => String::new(),
+            }),
+        ));
+
+        let mut open = true;
+
+        egui::Window::new("Compare")
+            .open(&mut open)
+            .default_width(700.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("comparison")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (label, [first, second]) in &rows {
+                            ui.strong(*label);
+
+                            for text in [first, second] {
+                                if first == second {
+                                    ui.label(text);
+                                } else {
+                                    ui.label(RichText::new(text).color(ui.visuals().warn_fg_color));
+                                }
+                            }
+
+                            ui.end_row();
+                        }
+                    });
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(both_files && !hashing, Button::new("Hash both"))
+                        .on_hover_text("Read both files to check if their contents are the same")
+                        .clicked()
+                    {
+                        for (i, full_path) in full_paths.iter().enumerate() {
+                            if let Some(full_path) = full_path {
+                                comparison.digests[i] = None;
+                                comparison.hashes[i] = Some(FileHash::start(
+                                    ctx.clone(),
+                                    full_path.clone(),
+                                    filenames[i].clone(),
+                                    sizes[i],
+                                    HashAlgorithm::Sha256,
+                                ));
+                            }
+                        }
+                    }
+
+                    if let [Some(Ok(first)), Some(Ok(second))] = &comparison.digests {
+                        ui.label(if first == second {
+                            "The contents are the same"
+                        } else {
+                            "The contents are different"
+                        });
+                    }
+                });
+            });
+
+        if !open {
+            if let Some(comparison) = self.comparison.take() {
+                for hash in comparison.hashes.into_iter().flatten() {
+                    hash.cancel();
+                }
+            }
+        }
+    }
+
+    fn in_use_window(&mut self, ctx: &egui::Context) {
+        let Some((filename, processes)) = &self.in_use else {
+            return;
+        };
+
+        let mut open = true;
+
+        egui::Window::new(format!("Apps using {filename}"))
+            .id(egui::Id::new("in_use"))
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| match processes {
+                Ok(processes) if processes.is_empty() => {
+                    ui.label("No apps have the file open");
+                }
+                Ok(processes) => {
+                    for process in processes {
+                        ui.label(process);
+                    }
+                }
+                Err(err) => {
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                }
+            });
+
+        if !open {
+            self.in_use = None;
+        }
+    }
+
+    // Hides files too many folders below the root, e.g. deep in node_modules
+    fn depth_filter(&mut self, ui: &mut egui::Ui) {
+        let mut enabled = self.filters.max_depth.is_some();
+
+        let toggled = ui
+            .checkbox(&mut enabled, "Max depth")
+            .on_hover_text(format!(
+                "Only show files at most this many folders below {}",
+                self.filesystem.volume_path.display()
+            ))
+            .changed();
+
+        let edited = ui
+            .add_enabled(
+                enabled,
+                egui::DragValue::new(&mut self.max_depth).range(0..=MAX_DEPTH_FILTER),
+            )
+            .on_hover_text("0 only shows what is directly in the root")
+            .changed();
+
+        if toggled || edited {
+            self.filters.max_depth = enabled.then_some(self.max_depth);
+            self.refresh_results();
+        }
+    }
+
+    // The most common extensions in the results, clicking one only shows those files
+    fn facet_row(&mut self, ui: &mut egui::Ui) {
+        // Counted again once the results change, partial results would keep changing them
+        if !self.filesystem.is_searching() {
+            let key = (
+                self.search.clone(),
+                self.browsing,
+                self.filesystem.shown.len(),
+            );
+
+            if self.facets_key.as_ref() != Some(&key) {
+                self.extension_facets = self.filesystem.extension_counts();
+                self.extension_facets.truncate(MAX_FACETS);
+                self.facets_key = Some(key);
+            }
+        }
+
+        if self.extension_facets.is_empty() {
+            return;
+        }
+
+        let mut clicked = None;
+
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Extensions");
+
+            for (extension, count) in &self.extension_facets {
+                if ui
+                    .small_button(format!("{extension}: {count}"))
+                    .on_hover_text(format!("Only show the .{extension} files"))
+                    .clicked()
+                {
+                    clicked = Some(extension.clone());
+                }
+            }
+        });
+
+        if let Some(extension) = clicked {
+            let mut shown = std::mem::take(&mut self.filesystem.shown);
+            shown.retain(|&position| {
+                self.filesystem.extension(position).as_deref() == Some(&*extension)
+            });
+            self.filesystem.set_shown(shown);
+            self.selected = None;
+        }
+    }
+
+    // Detecting types from file contents reads every shown file so it is only done when asked
+    fn type_row(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        ui.label("Type");
+
+        if let Some(signature_scan) = &self.signature_scan {
+            ui.add(
+                egui::ProgressBar::new(
+                    signature_scan.scanned() as f32 / signature_scan.total.max(1) as f32,
+                )
+                .desired_width(200.0)
+                .text(format!(
+                    "{} / {} files",
+                    signature_scan.scanned(),
+                    signature_scan.total
+                )),
+            );
+
+            if ui.button("Cancel").clicked() {
+                signature_scan.cancel();
+                self.signature_scan = None;
+            }
+
+            return;
+        }
+
+        let too_many = self.filesystem.shown.len() > signature::MAX_FILES;
+
+        if ui
+            .add_enabled(!too_many, Button::new("Detect types"))
+            .on_hover_text("Read the first bytes of the shown files to find their real type")
+            .on_disabled_hover_text(format!(
+                "Narrow the search to at most {} files first",
+                signature::MAX_FILES
+            ))
+            .clicked()
+        {
+            let files = self
+                .filesystem
+                .shown
+                .iter()
+                .filter_map(|&position| {
+                    Some((
+                        self.filesystem.frn_mapping[position],
+                        self.filesystem.full_path(position)?,
+                    ))
+                })
+                .collect();
+
+            self.signature_scan = Some(SignatureScan::start(ctx.clone(), files));
+        }
+
+        if self.detected_types.is_empty() {
+            return;
+        }
+
+        let mut types: Vec<&'static str> = self.detected_types.values().copied().collect();
+        types.sort_unstable();
+        types.dedup();
+
+        egui::ComboBox::from_id_salt("detected_type")
+            .selected_text("Only show…")
+            .show_ui(ui, |ui| {
+                for name in types {
+                    if ui.selectable_label(false, name).clicked() {
+                        let mut shown = std::mem::take(&mut self.filesystem.shown);
+                        shown.retain(|position| {
+                            self.detected_types
+                                .get(&self.filesystem.frn_mapping[*position])
+                                == Some(&name)
+                        });
+                        self.filesystem.set_shown(shown);
+                    }
+                }
+            });
+
+        if ui
+            .button("Mismatched extensions")
+            .on_hover_text("Only show files whose type doesn't match their extension, e.g. an executable named .txt")
+            .clicked()
+        {
+            let mut shown = std::mem::take(&mut self.filesystem.shown);
+            shown.retain(|&position| {
+                self.detected_types
+                    .get(&self.filesystem.frn_mapping[position])
+                    .is_some_and(|name| {
+                        signature::is_mismatch(name, self.filesystem.extension(position).as_deref())
+                    })
+            });
+            self.filesystem.set_shown(shown);
+            self.selected = None;
+        }
+
+        if ui
+            .button("Clear types")
+            .on_hover_text("Hide the Type column")
+            .clicked()
+        {
+            self.detected_types.clear();
+        }
+    }
+
+    // Everything saved in the config, the options that only affect the current search stay in the
+    // top bar
+    fn settings_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_settings;
+
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.collapsing("Search", |ui| self.search_settings(ui));
+                    ui.collapsing("Results", |ui| self.results_settings(ui));
+                    ui.collapsing("Index", |ui| self.index_settings(ui, ctx));
+                    ui.collapsing("Integration", |ui| self.integration_settings(ui));
+                    ui.collapsing("Hotkeys", |ui| self.hotkey_settings(ui));
+                });
+            });
+
+        self.show_settings = open;
+    }
+
+    fn search_settings(&mut self, ui: &mut egui::Ui) {
+        if ui
+            .add(
+                egui::DragValue::new(&mut self.config.search_threads)
+                    .range(0..=std::thread::available_parallelism().map_or(64, usize::from))
+                    .prefix("Threads: ")
+                    .custom_formatter(|threads, _| {
+                        if threads == 0.0 {
+                            "all".to_string()
+                        } else {
+                            threads.to_string()
+                        }
+                    }),
+            )
+            .on_hover_text("Threads used for searching and sorting, 0 uses every core")
+            .changed()
+        {
+            self.filesystem.set_threads(self.config.search_threads);
+        }
+
+        if ui
+            .checkbox(&mut self.config.unicode_case, "Unicode case")
+            .on_hover_text(
+                "Ignore case for all letters instead of only A-Z, slower to load and search",
+            )
+            .changed()
+        {
+            self.filesystem.set_unicode_case(self.config.unicode_case);
+
+            if !self.search.trim().is_empty() {
+                self.filesystem.start_search(&self.search, &self.filters);
+            }
+        }
+
+        if ui
+            .checkbox(
+                &mut self.config.case_insensitive_sort,
+                "Ignore case when sorting",
+            )
+            .on_hover_text("Sort names A to Z regardless of case, else uppercase comes first")
+            .changed()
+        {
+            self.filesystem.case_insensitive_sort = self.config.case_insensitive_sort;
+
+            if self.filesystem.order == FileOrder::Name {
+                self.filesystem.sort();
+            }
+        }
+
+        ui.checkbox(&mut self.config.enter_opens_result, "Enter opens")
+            .on_hover_text(
+                "Enter in the search box opens the only result, or the selected or top one",
+            );
+    }
+
+    fn results_settings(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.config.monospace_results, "Monospace");
+
+        ui.checkbox(&mut self.config.stable_results, "Keep results stable")
+            .on_hover_text(
+                "Don't add or remove rows as files are created, renamed or deleted, refresh when ready",
+            );
+
+        ui.checkbox(&mut self.config.check_missing_files, "Mark missing files")
+            .on_hover_text("Check whether the files of visible rows still exist");
+
+        ui.add_enabled(
+            self.filesystem.volume_label.is_some(),
+            egui::Checkbox::new(&mut self.config.show_volume_label, "Drive names"),
+        )
+        .on_hover_text("Start paths with the drive's name, e.g. \"Windows (C:)\"")
+        .on_disabled_hover_text("The drive doesn't have a name");
+
+        ui.checkbox(&mut self.config.show_parent_only, "Parent folder only")
+            .on_hover_text("Only show the folder a file is in, hover for the full path");
+
+        if ui
+            .checkbox(&mut self.config.show_child_counts, "Folder item counts")
+            .on_hover_text("Show the number of items directly in a folder as its size")
+            .changed()
+            && self.config.show_child_counts
+        {
+            self.filesystem.build_children();
+        }
+
+        if ui
+            .checkbox(
+                &mut self.config.recursive_folder_sizes,
+                "Total folder sizes",
+            )
+            .on_hover_text("Show the size of everything in a folder, counted as folders are shown")
+            .changed()
+            && self.config.recursive_folder_sizes
+        {
+            self.filesystem.build_children();
+        }
+
+        if ui
+            .checkbox(
+                &mut self.config.exclude_system_files,
+                "Leave out system files",
+            )
+            .on_hover_text("Don't count the page, swap and hibernation files in size totals")
+            .changed()
+        {
+            self.filesystem
+                .set_exclude_system_files(self.config.exclude_system_files);
+        }
+
+        ui.menu_button("Columns", |ui| self.column_menu(ui));
+
+        let previous_date_kind = self.config.date_kind;
+
+        egui::ComboBox::from_label("Date column")
+            .selected_text(self.config.date_kind.name())
+            .show_ui(ui, |ui| {
+                for kind in DateKind::ALL {
+                    ui.selectable_value(&mut self.config.date_kind, kind, kind.name());
+                }
+            });
+
+        if self.config.date_kind != previous_date_kind {
+            self.filesystem.date_kind = self.config.date_kind;
+
+            if self.filesystem.order == FileOrder::Date {
+                self.filesystem.sort();
+            }
+        }
+
+        if ui
+            .checkbox(&mut self.config.tree_view, "Tree")
+            .on_hover_text("Indent results by folder depth and sort them by path")
+            .changed()
+            && self.config.tree_view
+        {
+            self.filesystem.order = FileOrder::Path;
+            self.filesystem.direction = SortDirection::Ascending;
+            self.filesystem.sort();
+        }
+
+        ui.checkbox(&mut self.config.show_preview, "Preview")
+            .on_hover_text("Show the start of text files and thumbnails of the selected file");
+
+        ui.checkbox(
+            &mut self.config.refresh_icons_on_theme_change,
+            "Refresh icons on theme change",
+        )
+        .on_hover_text("Shell icons can differ between light and dark mode");
+    }
+
+    fn index_settings(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let drive = self.config.drive.clone();
+
+        ui.add_enabled_ui(self.rebuild.is_none(), |ui| {
+            let list = egui::ComboBox::from_label("Drive")
+                .selected_text(&drive)
+                .show_ui(ui, |ui| {
+                    for available in self.drives.get_or_insert_with(ntfs_drives).iter() {
+                        ui.selectable_value(&mut self.config.drive, available.clone(), available);
+                    }
+                });
+
+            // Read again the next time it is opened, drives may have been added since
+            if list.inner.is_none() {
+                self.drives = None;
+            }
+        });
+
+        if self.config.drive != drive {
+            self.start_rebuild(ctx);
+        }
+
+        ui.label("The journal settings are applied when the index is rebuilt or on restart");
+
+        ui.add(
+            egui::DragValue::new(&mut self.config.journal_poll_interval_ms)
+                .range(100..=60_000)
+                .prefix("Poll interval: ")
+                .suffix(" ms"),
+        );
+
+        ui.checkbox(&mut self.config.short_names, "Read 8.3 short names")
+            .on_hover_text(
+                "For the short: filter, e.g. short:progra~1. Applied when the index is rebuilt.",
+            );
+
+        if ui
+            .add_enabled(self.rebuild.is_none(), Button::new("Rebuild index"))
+            .on_hover_text("Read the whole MFT again, e.g. after changes were missed")
+            .clicked()
+        {
+            self.start_rebuild(ctx);
+        }
+    }
+
+    fn integration_settings(&mut self, ui: &mut egui::Ui) {
+        if ui
+            .checkbox(&mut self.config.always_on_top, "Always on top")
+            .on_hover_text("Keep the window above other apps")
+            .changed()
+        {
+            let level = if self.config.always_on_top {
+                egui::WindowLevel::AlwaysOnTop
+            } else {
+                egui::WindowLevel::Normal
+            };
+
+            ui.ctx()
+                .send_viewport_cmd(egui::ViewportCommand::WindowLevel(level));
+        }
+
+        egui::ComboBox::from_label("Terminal")
+            .selected_text(self.config.terminal.name())
+            .show_ui(ui, |ui| {
+                for terminal in Terminal::ALL {
+                    ui.selectable_value(&mut self.config.terminal, terminal, terminal.name());
+                }
+            });
+
+        ui.horizontal(|ui| {
+            ui.label("File manager");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.config.reveal_command)
+                    .hint_text("Explorer")
+                    .desired_width(300.0),
+            )
+            .on_hover_text(
+                "Command \"Open containing folder\" runs, {path} is replaced by the file and {dir} \
+                 by its folder, e.g. \"C:\\Program Files\\GPSoftware\\Directory Opus\\dopusrt.exe\" \
+                 /cmd Go \"{path}\"",
+            );
+        });
+
+        if ui
+            .checkbox(&mut self.config.monitor_clipboard, "Search copied paths")
+            .on_hover_text("Jump to a file when its path is copied in another app")
+            .changed()
+        {
+            // Dropping the listener stops it
+            self.clipboard_listener = self
+                .config
+                .monitor_clipboard
+                .then(|| clipboard::spawn_listener(ui.ctx().clone()));
+        }
+    }
+
+    fn hotkey_settings(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("hotkeys").num_columns(2).show(ui, |ui| {
+            for (index, (shortcut, action)) in self.config.hotkeys.iter().enumerate() {
+                ui.label(action.name());
+
+                let text = if self.capturing_hotkey == Some(index) {
+                    "Press a key…".to_string()
+                } else {
+                    ui.ctx().format_shortcut(shortcut)
+                };
+
+                if ui.button(text).on_hover_text("Click to change").clicked() {
+                    self.capturing_hotkey = Some(index);
+                }
+
+                ui.end_row();
+            }
+        });
+
+        if ui.button("Reset to defaults").clicked() {
+            self.config.hotkeys = hotkeys::default_hotkeys();
+            self.capturing_hotkey = None;
+        }
+    }
+
+    fn preview_panel(&mut self, ctx: &egui::Context) {
+        egui::SidePanel::right("preview")
+            .resizable(true)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                let Some(position) = self.selected_position() else {
+                    if let Some(preview) = self.preview.take() {
+                        preview.cancel();
+                    }
+
+                    ui.weak("Select a file to preview it");
+                    return;
+                };
+
+                let frn = self.filesystem.frn_mapping[position];
+                let full_path = self.full_path(position);
+
+                if self
+                    .preview
+                    .as_ref()
+                    .is_none_or(|preview| preview.frn != frn)
+                {
+                    if let Some(preview) = &self.preview {
+                        preview.cancel();
+                    }
+
+                    self.preview = Some(Preview::start(ctx.clone(), frn, full_path.clone()));
+                }
+
+                ui.heading(&*self.filesystem.filenames[position]);
+                ui.label(full_path.to_string_lossy());
+
+                egui::Grid::new("preview_details").show(ui, |ui| {
+                    ui.label("Size");
+                    if self.filesystem.is_directory(position) {
+                        ui.label("Folder");
+                    } else {
+                        ui.label(format_size(self.filesystem.filesizes[position]));
+                    }
+                    ui.end_row();
+
+                    // Files created since the index was loaded don't have a date yet
+                    if let Some(modified) = self.filesystem.modified_dates[position] {
+                        ui.label("Modified");
+                        ui.label(format_date(modified));
+                        ui.end_row();
+                    }
+                });
+
+                ui.separator();
+
+                if let Some(preview) = &mut self.preview {
+                    preview.show(ui);
+                }
+            });
+    }
+
+    fn start_rebuild(&mut self, ctx: &egui::Context) {
+        self.rebuild_error = None;
+        self.rebuild = Some(Rebuild::start(
+            ctx.clone(),
+            self.config.drive.clone(),
+            IndexOptions {
+                low_memory: self.filesystem.low_memory,
+                unicode_case: self.config.unicode_case,
+                modified_since: self.filesystem.modified_since,
+                short_names: self.config.short_names,
+            },
+            self.index_root.clone(),
+            Duration::from_millis(self.config.journal_poll_interval_ms),
+        ));
+    }
+
+    // Swaps in the rebuilt index, keeping the search and sort
+    fn finish_rebuild(
+        &mut self,
+        mut filesystem: FileSystem,
+        stats: IndexStats,
+        record_rx: Receiver<JournalMessage>,
+    ) {
+        filesystem.take_settings(&mut self.filesystem);
+
+        self.indexed_drive.clone_from(&self.config.drive);
+        // Pinned files are FRNs so each drive has its own
+        filesystem.pinned = self
+            .config
+            .pinned_by_drive
+            .get(&self.indexed_drive)
+            .cloned()
+            .unwrap_or_default();
+
+        self.stats = stats;
+
+        // The drive may have changed
+        self.records = Volume::new(format!(r"\\.\{}", self.config.drive))
+            .ok()
+            .and_then(|volume| RecordReader::new(volume).ok());
+
+        self.low_memory_saved = filesystem
+            .low_memory
+            .then(|| filesystem.lowercase_filenames_size());
+
+        self.filesystem = filesystem;
+        // Dropping the old receiver stops the old journal reader
+        self.record_rx = record_rx;
+        self.journal_gap = false;
+        // The buffered records are for the old index
+        self.frozen = None;
+        self.frozen_records.clear();
+        // FRNs can belong to different files in the new index, e.g. of another drive
+        self.selected = None;
+        self.renaming = None;
+        self.reparse_targets.clear();
+
+        if let Some(missing_check) = &mut self.missing_check {
+            missing_check.clear();
+        }
+
+        self.refresh_results();
+    }
+
+    fn apply_journal_records(&mut self) {
+        while let Ok(message) = self.record_rx.try_recv() {
+            if self.frozen.is_some() {
+                self.frozen_records.push(message);
+            } else {
+                self.apply_journal_message(message);
+            }
+        }
+    }
+
+    // Position of the selected file, None once it has been deleted
+    fn selected_position(&self) -> Option<usize> {
+        self.selected
+            .and_then(|frn| self.filesystem.position_of(frn))
+    }
+
+    fn belongs_in_results(&self, frn: u64) -> bool {
+        self.filesystem
+            .position_of(frn)
+            .is_some_and(|position| self.filesystem.belongs_in_shown(position))
+    }
+
+    // Adds and removes the changed files without searching again, so the selection and a running
+    // content search are kept
+    fn apply_pending_changes(&mut self) {
+        let frns = std::mem::take(&mut self.pending_changes);
+        self.filesystem.apply_changes(frns);
+    }
+
+    fn apply_journal_message(&mut self, message: JournalMessage) {
+        let record = match message {
+            JournalMessage::Record(record) => record,
+            JournalMessage::Gap => {
+                self.journal_gap = true;
+                return;
+            }
+        };
+
+        // https://learn.microsoft.com/en-us/windows/win32/api/winioctl/ns-winioctl-read_usn_journal_data_v1
+
+        let frn = file_id_to_frn(record.file_id);
+
+        let changes_shown = record.reason
+            & (Ioctl::USN_REASON_FILE_DELETE
+                | Ioctl::USN_REASON_RENAME_NEW_NAME
+                | Ioctl::USN_REASON_FILE_CREATE)
+            != 0;
+
+        // Changes to files that aren't and won't be shown don't change the results
+        let was_shown = changes_shown && self.belongs_in_results(frn);
+
+        if record.reason & Ioctl::USN_REASON_FILE_DELETE != 0 {
+            self.filesystem.delete(record.file_id);
+
+            // The FRN can be given to a new file
+            if self.selected == Some(frn) {
+                self.selected = None;
+            }
+
+            if self
+                .renaming
+                .as_ref()
+                .is_some_and(|(renamed, _)| *renamed == frn)
+            {
+                self.renaming = None;
+            }
+        }
+
+        // The file or directory is renamed, and the file name in the USN_RECORD structure holding this journal record is the new name.
+        if record.reason & Ioctl::USN_REASON_RENAME_NEW_NAME != 0 {
+            self.filesystem
+                .rename(record.file_id, record.parent_id, &record.path);
+        }
+
+        if record.reason & Ioctl::USN_REASON_FILE_CREATE != 0 {
+            self.filesystem
+                .create(record.file_id, record.parent_id, &record.path);
+        }
+
+        // A user has either changed one or more file or directory attributes
+        // (such as the read-only, hidden, system, archive, or sparse attribute), or one or more time stamps.
+        if record.reason & Ioctl::USN_REASON_BASIC_INFO_CHANGE != 0 {
+            self.filesystem
+                .update(record.file_id, record.parent_id, &record.path);
+        }
+
+        // shouldn't need to handle this as we can get all the information we need in the NEW_NAME record
+        // The file or directory is renamed, and the file name in the USN_RECORD structure holding this journal record is the previous name
+        // if record.reason & Ioctl::USN_REASON_RENAME_OLD_NAME != 0 {}
+
+        if was_shown || (changes_shown && self.belongs_in_results(frn)) {
+            self.pending_changes.insert(frn);
+        }
+
+        // Deleted files can't be hovered and their FRN is reused by a new file
+        if self.filesystem.position_of(frn).is_some() {
+            self.last_reasons.insert(frn, (record.reason, record.usn));
+
+            if self.last_reasons.len() > MAX_LAST_REASONS {
+                forget_oldest_reasons(&mut self.last_reasons);
+            }
+        } else {
+            self.last_reasons.remove(&frn);
+        }
+    }
+
+    // Stops applying changes so the index stays as it is now
+    fn freeze(&mut self) {
+        let time = unsafe { GetLocalTime() };
+
+        self.frozen = Some(format!(
+            "{:02}:{:02}:{:02}",
+            time.wHour, time.wMinute, time.wSecond
+        ));
+    }
+
+    // Applies the changes made while the index was frozen
+    fn unfreeze(&mut self) {
+        self.frozen = None;
+
+        for message in std::mem::take(&mut self.frozen_records) {
+            self.apply_journal_message(message);
+        }
+
+        self.refresh_results();
+    }
+
+    fn clear_icon_cache(&mut self) {
+        self.icon_cache.clear();
+        self.folder_icon = None;
+        self.default_icon = None;
+    }
+
+    fn get_default_icon(&mut self, ctx: &egui::Context) -> Option<TextureHandle> {
+        if self.default_icon.is_none() {
+            // Try to load a truly generic icon using 0 file attributes? Or known file?
+            // Let's try getting icon for a non-existent file with .txt extension attributes
+            let dummy_path = Path::new("dummy.txt");
+            self.default_icon = unsafe {
+                fetch_and_convert_icon(ctx, dummy_path, FILE_ATTRIBUTE_NORMAL.0, icon_pixels(ctx))
+            };
+
+            // Fallback if fetching generic icon fails: create a placeholder egui image
+            if self.default_icon.is_none() {
+                let fallback_image = ColorImage::new([16, 16], egui::Color32::from_gray(200));
+                self.default_icon = Some(ctx.load_texture(
+                    "__default_icon__",                      // Use distinct name
+                    ImageData::Color(fallback_image.into()), // Use ImageData enum
+                    TextureOptions::LINEAR,                  // Use enum variant
+                ));
+            }
+        }
+        self.default_icon.clone()
+    }
+}
+
+impl eframe::App for FileSearch {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, &self.config);
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let theme = ctx.theme();
+
+        if theme != self.theme {
+            if self.config.refresh_icons_on_theme_change {
+                self.clear_icon_cache();
+            }
+
+            self.theme = theme;
+        }
+
+        // E.g. the window moved to a screen with different scaling
+        if ctx.pixels_per_point() != self.icon_pixels_per_point {
+            self.clear_icon_cache();
+            self.icon_pixels_per_point = ctx.pixels_per_point();
+        }
+
+        if let Some(missing_check) = &mut self.missing_check {
+            missing_check.poll();
+        }
+
+        if self.filesystem.poll_owners() {
+            self.refresh_results();
+        }
+
+        if self.filesystem.owners.is_resolving() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        if let Some(clipboard_listener) = &self.clipboard_listener {
+            let copied_path = clipboard_listener
+                .rx
+                .try_iter()
+                .filter_map(|text| clipboard::as_path(&text).map(str::to_owned))
+                .last();
+
+            if let Some(copied_path) = copied_path {
+                self.search = copied_path;
+                self.search_changed();
+            }
+        }
+
+        // Changes would move files around in the middle of a search so they are applied once it
+        // has finished
+        if self.filesystem.is_searching() {
+            if !self.filesystem.continue_search() {
+                ctx.request_repaint();
+            }
+        } else {
+            self.apply_journal_records();
+
+            // Otherwise the changes wait until the badge is clicked
+            if !self.pending_changes.is_empty() && !self.config.stable_results {
+                self.apply_pending_changes();
+            }
+        }
+
+        self.handle_hotkeys(ctx);
+        self.count_folder_sizes(ctx);
+
+        if let Some(result) = self.rebuild.as_mut().and_then(Rebuild::poll) {
+            self.rebuild = None;
+
+            match result {
+                Ok((filesystem, stats, record_rx)) => {
+                    self.finish_rebuild(filesystem, stats, record_rx);
+                }
+                Err(err) => {
+                    error!("{err}");
+                    self.rebuild_error = Some(err);
+                    // The old index is still used so the drive it was read from is selected
+                    self.config.drive.clone_from(&self.indexed_drive);
+                }
+            }
+        }
+
+        if let Some(hash) = &self.hash {
+            if let Some(result) = hash.poll() {
+                self.hash_message = Some(match result {
+                    Ok(digest) => {
+                        let message = format!(
+                            "Copied the {} of {}: {digest}",
+                            hash.algorithm.name(),
+                            hash.filename
+                        );
+                        ctx.copy_text(digest);
+                        message
+                    }
+                    Err(err) => format!("Failed to hash {}: {err}", hash.filename),
+                });
+                self.hash = None;
+            }
+        }
+
+        if let Some(frns) = self.content_search.as_mut().and_then(ContentSearch::poll) {
+            let frns: FxHashSet<u64> = frns.into_iter().collect();
+
+            // Files deleted while searching are already gone from the shown list
+            self.filesystem
+                .shown
+                .retain(|&position| frns.contains(&self.filesystem.frn_mapping[position]));
+            self.content_search = None;
+        }
+
+        if let Some(detected) = self.signature_scan.as_mut().and_then(SignatureScan::poll) {
+            self.detected_types.extend(detected);
+            self.signature_scan = None;
+        }
+
+        egui::TopBottomPanel::top("top").show(ctx, |ui| {
+            let resp = ui
+                .with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let help = ui.button("?").on_hover_text("Search syntax");
+                    let popup_id = ui.make_persistent_id("syntax_help");
+
+                    if help.clicked() {
+                        ui.memory_mut(|mem| mem.toggle_popup(popup_id));
+                    }
+
+                    egui::popup_below_widget(
+                        ui,
+                        popup_id,
+                        &help,
+                        PopupCloseBehavior::CloseOnClickOutside,
+                        show_syntax_help,
+                    );
+
+                    if let Some(progress) = self.filesystem.search_progress() {
+                        ui.label(format!("Searching… {:.0}%", progress * 100.0));
+                        ui.spinner();
+                    } else if self.filesystem.is_reading_owners() {
+                        ui.label("Reading owners…");
+                        ui.spinner();
+                    }
+
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.search).desired_width(f32::INFINITY),
+                    )
+                })
+                .inner;
+
+            if resp.changed() {
+                self.search_changed();
+            }
+
+            self.previous_search.clone_from(&self.search);
+
+            if self.config.enter_opens_result
+                && resp.lost_focus()
+                && ui.input(|i| i.key_pressed(egui::Key::Enter))
+            {
+                // The selected file may have been deleted or left the results since, then the
+                // top one is opened
+                let position = match self.filesystem.shown[..] {
+                    [position] => Some(position),
+                    _ => self
+                        .selected_position()
+                        .filter(|selected| self.filesystem.shown.contains(selected))
+                        .or_else(|| self.filesystem.shown.first().copied()),
+                };
+
+                if let Some(position) = position {
+                    self.perform(ctx, Action::Open, position);
+                }
+            }
+
+            if !self.filters.hidden_extensions.is_empty() {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("Hidden");
+
+                    let mut extensions: Vec<Box<str>> =
+                        self.filters.hidden_extensions.iter().cloned().collect();
+                    extensions.sort_unstable();
+
+                    for extension in extensions {
+                        if ui
+                            .small_button(format!(".{extension} ✕"))
+                            .on_hover_text("Show these files again")
+                            .clicked()
+                        {
+                            self.filters.hidden_extensions.remove(&extension);
+                            self.refresh_results();
+                        }
+                    }
+                });
+            }
+
+            self.facet_row(ui);
+
+            ui.horizontal(|ui| {
+                ui.label("Contents");
+
+                if let Some(content_search) = &self.content_search {
+                    ui.add(
+                        egui::ProgressBar::new(
+                            content_search.searched() as f32 / content_search.total.max(1) as f32,
+                        )
+                        .desired_width(200.0)
+                        .text(format!(
+                            "{} / {} files",
+                            content_search.searched(),
+                            content_search.total
+                        )),
+                    );
+
+                    if ui.button("Cancel").clicked() {
+                        content_search.cancel();
+                        self.content_search = None;
+                    }
+                } else {
+                    let resp = ui.add(
+                        egui::TextEdit::singleline(&mut self.content_query)
+                            .hint_text("Text inside the shown files"),
+                    );
+
+                    let submitted =
+                        resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                    let too_many = self.filesystem.shown.len() > grep::MAX_FILES;
+
+                    let search_button = ui
+                        .add_enabled(
+                            !too_many && !self.content_query.is_empty(),
+                            Button::new("Search contents"),
+                        )
+                        .on_disabled_hover_text(format!(
+                            "Narrow the search to at most {} files first",
+                            grep::MAX_FILES
+                        ));
+
+                    if (search_button.clicked() || submitted)
+                        && !too_many
+                        && !self.content_query.is_empty()
+                    {
+                        let files = self
+                            .filesystem
+                            .shown
+                            .iter()
+                            .filter_map(|&position| {
+                                Some((
+                                    self.filesystem.frn_mapping[position],
+                                    self.filesystem.full_path(position)?,
+                                ))
+                            })
+                            .collect();
+
+                        self.content_search = Some(ContentSearch::start(
+                            ctx.clone(),
+                            files,
+                            &self.content_query,
+                        ));
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| self.type_row(ui, ctx));
+
+            ui.horizontal(|ui| {
+                if ui
+                    .checkbox(&mut self.filesystem.fuzzy, "Fuzzy")
+                    .on_hover_text("Match the characters of the search in order, ranked by score")
+                    .changed()
+                {
+                    if self.filesystem.fuzzy {
+                        self.filesystem.order = FileOrder::Score;
+                        self.filesystem.direction = SortDirection::Descending;
+                    } else if self.filesystem.order == FileOrder::Score {
+                        self.filesystem.order = FileOrder::RecordNumber;
+                    }
+
+                    if self.search.trim().is_empty() {
+                        self.filesystem.sort();
+                    } else {
+                        self.filesystem.start_search(&self.search, &self.filters);
+                    }
+                }
 
-                        row.col(|ui| {
-                            let sized_texture =
-                                egui::load::SizedTexture::new(icon_texture.id(), (16.0, 16.0));
-                            ui.add(egui::Image::from_texture(sized_texture));
+                if ui
+                    .add_enabled(
+                        self.filesystem.fuzzy,
+                        egui::Checkbox::new(&mut self.filesystem.fuzzy_paths, "Paths"),
+                    )
+                    .on_hover_text("Fuzzy match whole paths, matches in the filename rank higher")
+                    .changed()
+                    && !self.search.trim().is_empty()
+                {
+                    self.filesystem.start_search(&self.search, &self.filters);
+                }
+
+                let path_glob = ui
+                    .checkbox(&mut self.filesystem.path_glob, "Path glob")
+                    .on_hover_text("Match the search as a glob against whole paths, see ?")
+                    .changed();
+
+                let recursive_glob = ui
+                    .add_enabled(
+                        self.filesystem.path_glob,
+                        egui::Checkbox::new(&mut self.filesystem.recursive_glob, "**"),
+                    )
+                    .on_hover_text("** matches any number of folders")
+                    .changed();
+
+                let wildcards = ui
+                    .checkbox(&mut self.filesystem.wildcards, "Wildcards")
+                    .on_hover_text("Match * and ? against filenames the same way as Explorer")
+                    .changed();
+
+                if (path_glob || recursive_glob || wildcards) && !self.search.trim().is_empty() {
+                    self.filesystem.start_search(&self.search, &self.filters);
+                }
+
+                if ui
+                    .checkbox(&mut self.config.match_case, "Match case")
+                    .on_hover_text(
+                        "Only match the search with the same upper and lowercase letters",
+                    )
+                    .changed()
+                {
+                    self.filters.match_case = self.config.match_case;
+                    self.refresh_results();
+                }
+
+                let recent_kind = self.config.recent_date_kind;
+
+                // Creation dates show what was recently installed or downloaded, modified dates
+                // also include everything that was only written to
+                if ui
+                    .button(recent_kind.name())
+                    .on_hover_text("Which date the quick filters look at, click to switch")
+                    .clicked()
+                {
+                    self.config.recent_date_kind = match recent_kind {
+                        DateKind::Modified => DateKind::Created,
+                        _ => DateKind::Modified,
+                    };
+
+                    if let Some((_, cutoff)) = self.filters.recent_after {
+                        self.filters.recent_after = Some((self.config.recent_date_kind, cutoff));
+                        self.refresh_results();
+                    }
+                }
+
+                for (index, (name, days)) in RECENT_FILTERS.into_iter().enumerate() {
+                    let toggled = self.recent_filter == Some(index);
+
+                    if ui
+                        .selectable_label(toggled, name)
+                        .on_hover_text(format!(
+                            "Only show files {} in this time, click again to clear",
+                            self.config.recent_date_kind.name().to_lowercase()
+                        ))
+                        .clicked()
+                    {
+                        if toggled {
+                            self.recent_filter = None;
+                            self.filters.recent_after = None;
+                        } else {
+                            self.recent_filter = Some(index);
+                            self.filters.recent_after =
+                                Some((self.config.recent_date_kind, recent_cutoff(days)));
+                        }
+
+                        self.refresh_results();
+                    }
+                }
 
-                            let resp = ui.add(
-                                Label::new(&*self.filesystem.filenames[index])
-                                    .sense(Sense::click()),
+                self.depth_filter(ui);
+
+                ui.menu_button("Attributes", |ui| {
+                    ui.label("Click to cycle between any, with and without");
+
+                    let mut changed = false;
+
+                    for ((_, name), filter) in FILTER_ATTRIBUTES
+                        .iter()
+                        .zip(&mut self.config.attribute_filters)
+                    {
+                        let mut checked = *filter == AttributeFilter::Set;
+
+                        let resp = ui.add(
+                            egui::Checkbox::new(&mut checked, *name)
+                                .indeterminate(*filter == AttributeFilter::Any),
+                        );
+
+                        if resp.clicked() {
+                            *filter = filter.next();
+                            changed = true;
+                        }
+                    }
+
+                    if changed {
+                        (
+                            self.filters.required_attributes,
+                            self.filters.excluded_attributes,
+                        ) = self.config.attribute_masks();
+
+                        // The filters can show files the previous search excluded
+                        self.refresh_results();
+                    }
+                });
+
+                let previous_empty_filter = self.config.empty_filter;
+
+                egui::ComboBox::from_id_salt("empty_filter")
+                    .selected_text(self.config.empty_filter.name())
+                    .show_ui(ui, |ui| {
+                        for filter in EmptyFilter::ALL {
+                            ui.selectable_value(
+                                &mut self.config.empty_filter,
+                                filter,
+                                filter.name(),
                             );
+                        }
+                    });
 
-                            resp.context_menu(|ui| {
-                                if ui.button("Copy path").clicked() {
-                                    ui.ctx().copy_text(path.to_string());
-                                    ui.close_menu();
-                                }
-                            });
-                        });
-                        row.col(|ui| {
-                            ui.label(format_size(self.filesystem.filesizes[index]));
+                if self.config.empty_filter != previous_empty_filter {
+                    self.filters.empty_filter = self.config.empty_filter;
+
+                    self.refresh_results();
+                }
+
+                ui.toggle_value(&mut self.show_deleted, "Recently deleted");
+
+                if ui
+                    .selectable_label(self.recycle_bin.is_some(), "🗑 Recycle Bin")
+                    .on_hover_text("Search the files in the Recycle Bin by their original names")
+                    .clicked()
+                {
+                    self.recycle_bin = match self.recycle_bin {
+                        Some(_) => None,
+                        None => Some(recycle::scan(&self.filesystem)),
+                    };
+                }
+
+                if self.frozen.is_none()
+                    && ui
+                        .button("❄ Freeze")
+                        .on_hover_text(
+                            "Stop applying changes so searches run on the index as it is now",
+                        )
+                        .clicked()
+                {
+                    self.freeze();
+                }
+
+                ui.checkbox(&mut self.show_last_reason, "Show last change")
+                    .on_hover_text("Hover a filename to see its last journal record reasons");
+
+                ui.toggle_value(&mut self.show_settings, "⚙ Settings");
+            });
+
+            ui.separator();
+        });
+
+        if ctx.input_mut(|i| {
+            i.consume_shortcut(&egui::KeyboardShortcut::new(
+                egui::Modifiers::CTRL | egui::Modifiers::SHIFT,
+                egui::Key::I,
+            ))
+        }) {
+            self.inspector.open = !self.inspector.open;
+        }
+
+        if ctx.input_mut(|i| {
+            i.consume_key(egui::Modifiers::NONE, egui::Key::F5)
+                || i.consume_key(egui::Modifiers::CTRL, egui::Key::R)
+        }) {
+            self.rerun_last_search();
+        }
+
+        self.inspector.show(ctx, self.records.as_mut());
+
+        self.settings_window(ctx);
+
+        let mut rename_confirmed = false;
+
+        // The file was deleted while the dialog was open
+        if self
+            .renaming
+            .as_ref()
+            .is_some_and(|(frn, _)| self.filesystem.position_of(*frn).is_none())
+        {
+            self.renaming = None;
+        }
+
+        if let Some((_, new_name)) = &mut self.renaming {
+            let mut open = true;
+            let mut cancelled = false;
+
+            egui::Window::new("Rename")
+                .open(&mut open)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    let resp = ui.text_edit_singleline(new_name);
+
+                    if resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        rename_confirmed = true;
+                    }
+
+                    ui.horizontal(|ui| {
+                        rename_confirmed |= ui.button("Rename").clicked();
+                        cancelled = ui.button("Cancel").clicked();
+                    });
+                });
+
+            if cancelled || !open {
+                self.renaming = None;
+            }
+        }
+
+        if rename_confirmed {
+            if let Some((frn, new_name)) = self.renaming.take() {
+                // The journal updates the index once it has been renamed
+                if let Some(full_path) = self
+                    .filesystem
+                    .position_of(frn)
+                    .and_then(|position| self.filesystem.full_path(position))
+                {
+                    if let Err(err) =
+                        std::fs::rename(&full_path, full_path.with_file_name(new_name))
+                    {
+                        warn!("Failed to rename {}: {err}", full_path.display());
+                    }
+                }
+            }
+        }
+
+        egui::Window::new("Recently deleted")
+            .open(&mut self.show_deleted)
+            .default_size([600.0, 300.0])
+            .show(ctx, |ui| {
+                if self.filesystem.tombstones.is_empty() {
+                    ui.label("No files have been deleted since the index was loaded");
+                    return;
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("tombstones")
+                        .num_columns(4)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Name");
+                            ui.strong("Size");
+                            ui.strong("Deleted");
+                            ui.strong("Path");
+                            ui.end_row();
+
+                            for tombstone in &self.filesystem.tombstones {
+                                ui.label(&*tombstone.filename);
+                                ui.label(format_size(tombstone.size));
+                                ui.label(format_elapsed(
+                                    tombstone.deleted.elapsed().unwrap_or_default(),
+                                ));
+                                ui.label(tombstone.path.as_deref().map_or_else(
+                                    || UNKNOWN_FOLDER.into(),
+                                    |path| path.to_string_lossy(),
+                                ));
+                                ui.end_row();
+                            }
                         });
-                        row.col(|ui| {
-                            // So we can hover to get the full path
-                            ui.label(&path).on_hover_text(path);
+                });
+            });
+
+        self.in_use_window(ctx);
+        self.recycle_bin_window(ctx);
+        self.script_window(ctx);
+        self.comparison_window(ctx);
+
+        let total_rows = self.filesystem.shown.len();
+
+        egui::TopBottomPanel::bottom("bottom").show(ctx, |ui| {
+            // ui.separator();
+
+            ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
+                // The total is only added up while hovered
+                ui.label(format!("{total_rows} files")).on_hover_ui(|ui| {
+                    ui.label(format!(
+                        "{} files indexed in {:.1}s, using ~{}",
+                        self.stats.files,
+                        self.stats.scan_ms as f64 / 1000.0,
+                        format_size(self.stats.memory_bytes as u64)
+                    ));
+                    ui.label(format!(
+                        "Total size of the results: {}",
+                        format_size(self.filesystem.shown_size())
+                    ));
+                });
+
+                if self.config.stable_results
+                    && !self.pending_changes.is_empty()
+                    && ui
+                        .button(format!(
+                            "{} changes pending, click to refresh",
+                            self.pending_changes.len()
+                        ))
+                        .on_hover_text("Show the changed files, rows can move")
+                        .clicked()
+                {
+                    self.apply_pending_changes();
+                }
+
+                if let Some(saved) = self.low_memory_saved {
+                    ui.label(format!("Low memory mode, saving ~{}", format_size(saved as u64)))
+                        .on_hover_text("Searches lowercase filenames as they go so are slower");
+                }
+
+                if ui
+                    .button("Copy as table")
+                    .on_hover_text("Copy the results as tab-separated values to paste into a spreadsheet")
+                    .clicked()
+                {
+                    ctx.copy_text(export::to_tsv(&self.filesystem, &self.filesystem.shown));
+                }
+
+                if ui
+                    .button("Copy summary")
+                    .on_hover_text("Copy e.g. \"1,234 files (4.2 GiB) matching 'foo'\"")
+                    .clicked()
+                {
+                    ctx.copy_text(self.results_summary());
+                }
+
+                if ui
+                    .button("Export as script")
+                    .on_hover_text("Save a script that copies, moves or deletes the results, to review before running it")
+                    .clicked()
+                {
+                    let folder = std::env::var("USERPROFILE").unwrap_or_default();
+
+                    self.script_dialog = Some(ScriptDialog {
+                        kind: ScriptKind::PowerShell,
+                        operation: ScriptOperation::Copy,
+                        destination: String::new(),
+                        path: Path::new(&folder).join("results.ps1").to_string_lossy().to_string(),
+                        message: None,
+                    });
+                }
+
+                if let Some(rebuild) = &self.rebuild {
+                    ui.separator();
+                    ui.spinner();
+                    ui.label(format!(
+                        "Rebuilding index: {} ({}s)",
+                        rebuild.phase,
+                        rebuild.start.elapsed().as_secs()
+                    ));
+                }
+
+                if let Some(hash) = &self.hash {
+                    ui.separator();
+
+                    let progress = if hash.total == 0 {
+                        0.0
+                    } else {
+                        hash.hashed() as f32 / hash.total as f32
+                    };
+
+                    ui.add(
+                        egui::ProgressBar::new(progress)
+                            .desired_width(200.0)
+                            .text(format!("{} of {}", hash.algorithm.name(), hash.filename)),
+                    );
+
+                    if ui.small_button("Cancel").clicked() {
+                        hash.cancel();
+                        self.hash = None;
+                    }
+                }
+
+                if let Some(message) = &self.hash_message {
+                    ui.separator();
+                    ui.label(message);
+
+                    if ui.small_button("Dismiss").clicked() {
+                        self.hash_message = None;
+                    }
+                }
+
+                if let Some(err) = &self.rebuild_error {
+                    ui.separator();
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+
+                    if ui.small_button("Dismiss").clicked() {
+                        self.rebuild_error = None;
+                    }
+                }
+
+                if let Some(time) = &self.frozen {
+                    ui.separator();
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!(
+                            "❄ Frozen snapshot from {time}, {} changes since then aren't shown",
+                            self.frozen_records.len()
+                        ),
+                    );
+
+                    if ui.small_button("Unfreeze").clicked() {
+                        self.unfreeze();
+                    }
+                }
+
+                if self.journal_gap {
+                    ui.separator();
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "⚠ The journal dropped changes before they were read, the index may be stale",
+                    );
+
+                    if self.rebuild.is_none() && ui.small_button("Rebuild index").clicked() {
+                        self.start_rebuild(ctx);
+                    }
+
+                    if ui.small_button("Dismiss").clicked() {
+                        self.journal_gap = false;
+                    }
+                }
+            });
+        });
+
+        if self.config.show_preview {
+            self.preview_panel(ctx);
+        } else if let Some(preview) = self.preview.take() {
+            preview.cancel();
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.breadcrumb(ui);
+
+            // The breadcrumb can change the shown files
+            let total_rows = self.filesystem.shown.len();
+
+            // An empty table looks like something went wrong
+            if total_rows == 0
+                && !self.search.trim().is_empty()
+                && !self.filesystem.is_searching()
+                && self.browsing.is_none()
+            {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(ui.available_height() / 3.0);
+
+                    ui.heading(format!("No files match '{}'", self.search.trim()));
+                    ui.label("Try a shorter search, check the filters or clear the search");
+
+                    if ui.button("Clear search").clicked() {
+                        self.search.clear();
+                        self.previous_search.clear();
+                        self.search_changed();
+                    }
+                });
+                return;
+            }
+
+            let column_width = ui.available_width() / 2.0;
+            let height = ui.available_height();
+            let mut table = TableBuilder::new(ui)
+                // .striped(true)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .max_scroll_height(height) // Without this there is a weird empty space below the table
+                .sense(Sense::click()); // So the context menu opens anywhere on a row
+
+            if let Some(row) = self.scroll_to_row.take() {
+                table = table.scroll_to_row(row, None);
+            }
+
+            let columns = self.visible_columns();
+
+            for column in &columns {
+                table = table.column(match column {
+                    ResultColumn::Name => Column::exact(column_width.min(400.0)),
+                    ResultColumn::Owner => Column::initial(200.0).resizable(true),
+                    ResultColumn::SizeOnDisk
+                    | ResultColumn::Date
+                    | ResultColumn::Score
+                    | ResultColumn::Type => Column::auto(),
+                    ResultColumn::Size | ResultColumn::Path => Column::remainder(),
+                });
+            }
+
+            table
+                .header(20.0, |mut header| {
+                    for &column in &columns {
+                        // Named after the timestamp it shows
+                        let name = match column {
+                            ResultColumn::Date => self.config.date_kind.name(),
+                            _ => column.name(),
+                        };
+
+                        let (_, resp) = header.col(|ui| match column.order() {
+                            Some(order) => self.sort_header(ui, name, order),
+                            None => {
+                                ui.heading(name);
+                            }
                         });
+
+                        resp.context_menu(|ui| self.column_menu(ui));
+                    }
+                })
+                .body(|body| {
+                    body.rows(18.0, total_rows, |mut row| {
+                        // A context menu action on an earlier row can change the shown files
+                        let Some(&index) = self.filesystem.shown.get(row.index()) else {
+                            return;
+                        };
+
+                        let path = self.filesystem.display_path(index);
+
+                        // None while a folder above the file isn't indexed yet, e.g. halfway
+                        // through extracting an archive
+                        let full_path = self.filesystem.full_path(index);
+
+                        row.set_selected(self.selected == Some(self.filesystem.frn_mapping[index]));
+
+                        let icon_texture = self
+                            .get_texture_handle(
+                                ctx,
+                                &full_path.clone().unwrap_or_else(|| {
+                                    PathBuf::from(&*self.filesystem.filenames[index])
+                                }),
+                            )
+                            .or_else(|| self.get_default_icon(ctx))
+                            .unwrap(); // guaranteed for there to be a default icon
+
+                        for &column in &columns {
+                            row.col(|ui| match column {
+                                ResultColumn::Name => {
+                                    if self.config.tree_view {
+                                        ui.add_space(
+                                            self.filesystem.depth(index) as f32 * TREE_INDENT,
+                                        );
+                                    }
+
+                                    let sized_texture = egui::load::SizedTexture::new(
+                                        icon_texture.id(),
+                                        (ICON_SIZE, ICON_SIZE),
+                                    );
+                                    ui.add(egui::Image::from_texture(sized_texture));
+
+                                    let mut name =
+                                        self.result_text(&*self.filesystem.filenames[index]);
+
+                                    if self.filesystem.is_pinned(index) {
+                                        ui.label("📌");
+                                        name = name.strong();
+                                    }
+
+                                    // Not selectable so clicks go through to the row
+                                    let mut resp = ui.add(Label::new(name).selectable(false));
+
+                                    if let (true, Some(full_path)) =
+                                        (self.config.check_missing_files, &full_path)
+                                    {
+                                        let frn = self.filesystem.frn_mapping[index];
+
+                                        let missing = self
+                                            .missing_check
+                                            .get_or_insert_with(|| MissingCheck::start(ctx.clone()))
+                                            .is_missing(frn, || full_path.clone());
+
+                                        if missing {
+                                            ui.label(
+                                                RichText::new("missing")
+                                                    .small()
+                                                    .color(ui.visuals().error_fg_color),
+                                            )
+                                            .on_hover_text(
+                                                "The file no longer exists, the index hasn't caught up yet",
+                                            );
+                                        }
+                                    }
+
+                                    if self.show_last_reason {
+                                        let frn = self.filesystem.frn_mapping[index];
+
+                                        if let Some(&(reason, _)) = self.last_reasons.get(&frn) {
+                                            resp = resp.on_hover_text(format!(
+                                                "Last change: {}",
+                                                journal::format_reason(reason)
+                                            ));
+                                        }
+                                    }
+                                }
+                                ResultColumn::Size => {
+                                    let is_directory = self.filesystem.is_directory(index);
+
+                                    let child_count =
+                                        if self.config.show_child_counts && is_directory {
+                                            self.filesystem.child_count(index)
+                                        } else {
+                                            None
+                                        };
+
+                                    let size = if self.config.recursive_folder_sizes && is_directory
+                                    {
+                                        let frn = self.filesystem.frn_mapping[index];
+
+                                        match self.filesystem.folder_size(frn) {
+                                            Some(size) => format_size(size),
+                                            None => {
+                                                self.uncounted_folders.push(frn);
+                                                "…".to_string()
+                                            }
+                                        }
+                                    } else {
+                                        match child_count {
+                                            Some(1) => "1 item".to_string(),
+                                            Some(count) => format!("{count} items"),
+                                            None => format_size(self.filesystem.filesizes[index]),
+                                        }
+                                    };
+
+                                    ui.add(Label::new(size).selectable(false));
+                                }
+                                ResultColumn::SizeOnDisk => {
+                                    // Folders' contents aren't stored in clusters of their own
+                                    let size = if self.filesystem.is_directory(index) {
+                                        String::new()
+                                    } else {
+                                        self.filesystem
+                                            .size_on_disk(index)
+                                            .map(format_size)
+                                            .unwrap_or_default()
+                                    };
+
+                                    ui.add(Label::new(size).selectable(false));
+                                }
+                                ResultColumn::Date => {
+                                    let date = self.filesystem.dates(self.config.date_kind)[index]
+                                        .map(format_date)
+                                        .unwrap_or_default();
+
+                                    ui.add(Label::new(date).selectable(false));
+                                }
+                                ResultColumn::Score => {
+                                    ui.add(
+                                        Label::new(self.filesystem.score(index).to_string())
+                                            .selectable(false),
+                                    );
+                                }
+                                ResultColumn::Owner => {
+                                    // Only visible rows are resolved, once per security id
+                                    let security_id = self.filesystem.security_ids[index];
+                                    let owner = self
+                                        .filesystem
+                                        .owners
+                                        .lookup(security_id, || full_path.clone())
+                                        .unwrap_or_default();
+
+                                    ui.add(Label::new(owner).selectable(false));
+                                }
+                                ResultColumn::Type => {
+                                    let frn = self.filesystem.frn_mapping[index];
+                                    let name = self.detected_types.get(&frn).copied();
+                                    let extension = self.filesystem.extension(index);
+
+                                    match name {
+                                        Some(name)
+                                            if signature::is_mismatch(
+                                                name,
+                                                extension.as_deref(),
+                                            ) =>
+                                        {
+                                            ui.add(
+                                                Label::new(
+                                                    RichText::new(format!("⚠ {name}"))
+                                                        .color(ui.visuals().warn_fg_color),
+                                                )
+                                                .selectable(false),
+                                            )
+                                            .on_hover_text(format!(
+                                                "Not what .{} files usually are",
+                                                extension.unwrap_or_default()
+                                            ));
+                                        }
+                                        _ => {
+                                            ui.add(
+                                                Label::new(name.unwrap_or_default())
+                                                    .selectable(false),
+                                            );
+                                        }
+                                    }
+                                }
+                                ResultColumn::Path => {
+                                    let full_shown_path = if self.config.show_volume_label {
+                                        self.filesystem.labelled_path(&path).into_owned()
+                                    } else {
+                                        path.clone()
+                                    };
+
+                                    let shown_path = if self.config.show_parent_only {
+                                        // Files in the volume root have no parent folder name so fall back to the full path
+                                        Path::new(&path).file_name().map_or_else(
+                                            || full_shown_path,
+                                            |name| name.to_string_lossy().to_string(),
+                                        )
+                                    } else {
+                                        full_shown_path
+                                    };
+
+                                    // So we can hover to get the full path
+                                    let response = ui.add(
+                                        Label::new(self.result_text(shown_path)).selectable(false),
+                                    );
+
+                                    let is_reparse_point = self.filesystem.attributes[index]
+                                        & FILE_ATTRIBUTE_REPARSE_POINT.0
+                                        != 0;
+
+                                    // Only resolved on hover so rows don't touch the disk
+                                    match &full_path {
+                                        Some(full_path) if is_reparse_point && response.hovered() => {
+                                            let frn = self.filesystem.frn_mapping[index];
+                                            let target =
+                                                self.reparse_targets.entry(frn).or_insert_with(
+                                                    || shell::reparse_target(full_path),
+                                                );
+
+                                            let target = target.as_deref().unwrap_or("unresolved");
+                                            response.on_hover_text(format!("{path}\n→ {target}"));
+                                        }
+                                        _ => {
+                                            response.on_hover_text(path.as_str());
+                                        }
+                                    }
+                                }
+                            });
+                        }
+
+                        let resp = row.response();
+
+                        // Keep the selection on the row the menu is for
+                        if resp.clicked() || resp.secondary_clicked() {
+                            self.selected = Some(self.filesystem.frn_mapping[index]);
+                        }
+
+                        // Nothing can be done with the file until its path is known
+                        if let Some(full_path) = &full_path {
+                            if resp.double_clicked() {
+                                let _ = unsafe { shell::open(full_path) };
+                            }
+
+                            resp.context_menu(|ui| self.context_menu(ui, index, &path, full_path));
+                        }
                     });
                 });
         });
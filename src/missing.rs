@@ -0,0 +1,97 @@
+use std::{
+    io,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+// A row's file is checked at most this often while it stays visible
+const RECHECK_INTERVAL: Duration = Duration::from_secs(5);
+// Visible rows are rechecked well within this, so older answers are for rows scrolled away from
+const FORGET_AFTER: Duration = Duration::from_secs(60);
+
+// Checks whether the files of visible rows still exist on a worker thread, e.g. on network
+// drives or while the journal hasn't caught up yet
+pub struct MissingCheck {
+    ctx: egui::Context,
+    tx: Sender<(u64, PathBuf)>,
+    rx: Receiver<(u64, bool)>,
+    // Key: FRN, whether the file was missing and when that was checked
+    checked: FxHashMap<u64, (bool, Instant)>,
+    // FRNs sent to the worker that haven't been answered yet
+    pending: FxHashSet<u64>,
+}
+
+// Stops once its channels are dropped
+fn spawn_worker(ctx: egui::Context) -> (Sender<(u64, PathBuf)>, Receiver<(u64, bool)>) {
+    let (tx, worker_rx) = mpsc::channel::<(u64, PathBuf)>();
+    let (worker_tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for (frn, path) in worker_rx {
+            let missing = matches!(
+                std::fs::symlink_metadata(&path),
+                Err(err) if err.kind() == io::ErrorKind::NotFound
+            );
+
+            if worker_tx.send((frn, missing)).is_err() {
+                break;
+            }
+
+            if missing {
+                ctx.request_repaint();
+            }
+        }
+    });
+
+    (tx, rx)
+}
+
+impl MissingCheck {
+    pub fn start(ctx: egui::Context) -> Self {
+        let (tx, rx) = spawn_worker(ctx.clone());
+
+        MissingCheck {
+            ctx,
+            tx,
+            rx,
+            checked: FxHashMap::default(),
+            pending: FxHashSet::default(),
+        }
+    }
+
+    pub fn poll(&mut self) {
+        while let Ok((frn, missing)) = self.rx.try_recv() {
+            self.pending.remove(&frn);
+            self.checked.insert(frn, (missing, Instant::now()));
+        }
+
+        self.checked
+            .retain(|_, (_, checked)| checked.elapsed() < FORGET_AFTER);
+    }
+
+    // The last known answer, the file is queued for a check if it hasn't been checked recently
+    pub fn is_missing(&mut self, frn: u64, path: impl FnOnce() -> PathBuf) -> bool {
+        let (missing, fresh) = match self.checked.get(&frn) {
+            Some(&(missing, checked)) => (missing, checked.elapsed() < RECHECK_INTERVAL),
+            None => (false, false),
+        };
+
+        if !fresh && self.pending.insert(frn) {
+            let _ = self.tx.send((frn, path()));
+        }
+
+        missing
+    }
+
+    // FRNs are reused by new files and positions change when the index is rebuilt. The worker is
+    // replaced so the answers still queued for the old files are dropped with it.
+    pub fn clear(&mut self) {
+        (self.tx, self.rx) = spawn_worker(self.ctx.clone());
+        self.checked.clear();
+        self.pending.clear();
+    }
+}
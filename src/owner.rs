@@ -0,0 +1,191 @@
+use std::{
+    os::windows::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{LocalFree, ERROR_SUCCESS, HLOCAL},
+        Security::{
+            Authorization::{ConvertSidToStringSidW, GetNamedSecurityInfoW, SE_FILE_OBJECT},
+            LookupAccountSidW, OWNER_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, PSID,
+            SID_NAME_USE,
+        },
+    },
+};
+
+// Account names are at most 256 characters
+const MAX_NAME_LENGTH: usize = 257;
+
+// Owners are looked up by the security id in $STANDARD_INFORMATION, files with the same id share
+// a security descriptor so only one file per id has to be read. Looking up an account can be
+// slow (it may ask a domain controller) so names are only resolved when needed, on a worker
+// thread.
+#[derive(Default)]
+pub struct OwnerCache {
+    // None if the owner couldn't be read
+    names: FxHashMap<u32, Option<String>>,
+    // To and from the worker, started by the first lookup
+    tx: Option<Sender<(u32, PathBuf)>>,
+    rx: Option<Receiver<(u32, Option<String>)>>,
+    // Security ids sent to the worker that haven't been answered yet
+    pending: FxHashSet<u32>,
+}
+
+impl OwnerCache {
+    // The owner if it has been resolved, otherwise it is queued. `path` is any file with the
+    // security id. Id 0 is used for files created since the index was loaded which have no id yet
+    // so they aren't resolved.
+    pub fn lookup(
+        &mut self,
+        security_id: u32,
+        path: impl FnOnce() -> Option<PathBuf>,
+    ) -> Option<&str> {
+        if security_id == 0 {
+            return None;
+        }
+
+        if !self.is_resolved(security_id) && !self.pending.contains(&security_id) {
+            if let Some(path) = path() {
+                self.request(security_id, path);
+            }
+        }
+
+        self.name(security_id)
+    }
+
+    fn request(&mut self, security_id: u32, path: PathBuf) {
+        let tx = self.tx.get_or_insert_with(|| {
+            let (tx, worker_rx) = mpsc::channel::<(u32, PathBuf)>();
+            let (worker_tx, rx) = mpsc::channel();
+
+            // Stops once the cache is dropped
+            std::thread::spawn(move || {
+                for (security_id, path) in worker_rx {
+                    let owner = unsafe { file_owner(&path) };
+
+                    if worker_tx.send((security_id, owner)).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            self.rx = Some(rx);
+            tx
+        });
+
+        if tx.send((security_id, path)).is_ok() {
+            self.pending.insert(security_id);
+        }
+    }
+
+    // Takes in the owners the worker has resolved, returns true if there were any
+    pub fn poll(&mut self) -> bool {
+        let Some(rx) = &self.rx else {
+            return false;
+        };
+
+        let mut resolved = false;
+
+        while let Ok((security_id, owner)) = rx.try_recv() {
+            self.pending.remove(&security_id);
+            self.names.insert(security_id, owner);
+            resolved = true;
+        }
+
+        resolved
+    }
+
+    pub fn is_resolving(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn is_resolved(&self, security_id: u32) -> bool {
+        self.names.contains_key(&security_id)
+    }
+
+    // None if it hasn't been resolved yet or couldn't be read
+    pub fn name(&self, security_id: u32) -> Option<&str> {
+        self.names.get(&security_id)?.as_deref()
+    }
+}
+
+// DOMAIN\name, or the SID string (S-1-5-…) if the account doesn't exist anymore
+unsafe fn file_owner(path: &Path) -> Option<String> {
+    let mut path_utf16: Vec<u16> = path.as_os_str().encode_wide().collect();
+    path_utf16.push(0);
+
+    let mut owner = PSID::default();
+    let mut descriptor = PSECURITY_DESCRIPTOR::default();
+
+    let result = GetNamedSecurityInfoW(
+        PCWSTR::from_raw(path_utf16.as_ptr()),
+        SE_FILE_OBJECT,
+        OWNER_SECURITY_INFORMATION,
+        Some(&mut owner),
+        None,
+        None,
+        None,
+        &mut descriptor,
+    );
+
+    if result != ERROR_SUCCESS {
+        return None;
+    }
+
+    // `owner` points into the descriptor so it has to be used before freeing it
+    let name = account_name(owner).or_else(|| sid_string(owner));
+
+    LocalFree(Some(HLOCAL(descriptor.0)));
+
+    name
+}
+
+unsafe fn account_name(sid: PSID) -> Option<String> {
+    let mut name = [0u16; MAX_NAME_LENGTH];
+    let mut domain = [0u16; MAX_NAME_LENGTH];
+    let mut name_length = name.len() as u32;
+    let mut domain_length = domain.len() as u32;
+    let mut sid_type = SID_NAME_USE::default();
+
+    LookupAccountSidW(
+        PCWSTR::null(),
+        sid,
+        Some(PWSTR::from_raw(name.as_mut_ptr())),
+        &mut name_length,
+        Some(PWSTR::from_raw(domain.as_mut_ptr())),
+        &mut domain_length,
+        &mut sid_type,
+    )
+    .ok()?;
+
+    // On success the lengths don't include the null terminator
+    let name = String::from_utf16_lossy(&name[..name_length as usize]);
+    let domain = String::from_utf16_lossy(&domain[..domain_length as usize]);
+
+    Some(if domain.is_empty() {
+        name
+    } else {
+        format!("{domain}\\{name}")
+    })
+}
+
+unsafe fn sid_string(sid: PSID) -> Option<String> {
+    let mut string = PWSTR::null();
+
+    ConvertSidToStringSidW(sid, &mut string).ok()?;
+
+    let sid_string = string.to_string().ok();
+
+    LocalFree(Some(HLOCAL(string.0.cast())));
+
+    sid_string
+}
+
+// For the owner: filter, `query` is lowercase
+pub fn matches(owner: Option<&str>, query: &str) -> bool {
+    owner.is_some_and(|owner| owner.to_lowercase().contains(query))
+}
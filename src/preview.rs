@@ -0,0 +1,188 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    os::windows::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+};
+
+use eframe::egui::{self, ColorImage, TextureHandle, TextureOptions};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::SIZE,
+        Graphics::Gdi::DeleteObject,
+        System::Com::{CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED},
+        UI::Shell::{
+            IShellItemImageFactory, SHCreateItemFromParsingName, SIIGBF_BIGGERSIZEOK,
+            SIIGBF_THUMBNAILONLY,
+        },
+    },
+};
+
+use crate::icon::bitmap_to_image;
+
+// Only the start of a text file is shown so huge logs don't have to be read
+const MAX_TEXT_SIZE: u64 = 64 * 1024;
+
+const THUMBNAIL_SIZE: i32 = 256;
+
+enum Content {
+    // The text and whether the file is longer than what was read
+    Text(String, bool),
+    Image(ColorImage),
+    // Binary, unreadable or a directory, only the details are shown
+    None,
+}
+
+// The preview of the selected file, loaded on a worker thread
+pub struct Preview {
+    pub frn: u64,
+    cancelled: Arc<AtomicBool>,
+    rx: Receiver<Content>,
+    // None while loading
+    content: Option<Content>,
+    texture: Option<TextureHandle>,
+}
+
+impl Preview {
+    pub fn start(ctx: egui::Context, frn: u64, path: PathBuf) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        {
+            let cancelled = cancelled.clone();
+
+            std::thread::spawn(move || {
+                let content = load(&path, &cancelled);
+
+                // Another file was selected in the meantime
+                if !cancelled.load(Ordering::Relaxed) && tx.send(content).is_ok() {
+                    ctx.request_repaint();
+                }
+            });
+        }
+
+        Preview {
+            frn,
+            cancelled,
+            rx,
+            content: None,
+            texture: None,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        if self.content.is_none() {
+            self.content = self.rx.try_recv().ok();
+        }
+
+        match &mut self.content {
+            None => {
+                ui.spinner();
+            }
+            Some(Content::Text(text, truncated)) => {
+                if *truncated {
+                    ui.weak(format!("Showing the first {} KiB", MAX_TEXT_SIZE / 1024));
+                }
+
+                egui::ScrollArea::both().show(ui, |ui| {
+                    ui.add(egui::Label::new(egui::RichText::new(&*text).monospace()).extend());
+                });
+            }
+            Some(Content::Image(image)) => {
+                let texture = self.texture.get_or_insert_with(|| {
+                    ui.ctx()
+                        .load_texture("preview", std::mem::take(image), TextureOptions::LINEAR)
+                });
+
+                ui.add(egui::Image::from_texture(&*texture).shrink_to_fit());
+            }
+            Some(Content::None) => {
+                ui.weak("No preview");
+            }
+        }
+    }
+}
+
+fn load(path: &Path, cancelled: &AtomicBool) -> Content {
+    if path.is_dir() {
+        return Content::None;
+    }
+
+    if let Ok(Some((text, truncated))) = read_text(path) {
+        return Content::Text(text, truncated);
+    }
+
+    if cancelled.load(Ordering::Relaxed) {
+        return Content::None;
+    }
+
+    unsafe { thumbnail(path) }.map_or(Content::None, Content::Image)
+}
+
+// None if the file looks binary
+fn read_text(path: &Path) -> io::Result<Option<(String, bool)>> {
+    let file = File::open(path)?;
+    let size = file.metadata()?.len();
+
+    let mut bytes = Vec::new();
+    file.take(MAX_TEXT_SIZE).read_to_end(&mut bytes)?;
+
+    if bytes.contains(&0) {
+        return Ok(None);
+    }
+
+    // A character cut off at the end just becomes U+FFFD
+    Ok(Some((
+        String::from_utf8_lossy(&bytes).into_owned(),
+        size > MAX_TEXT_SIZE,
+    )))
+}
+
+// The same thumbnail Explorer shows, so anything with a thumbnail handler works (images, videos,
+// PDFs). Each preview has its own thread so COM is initialized here.
+unsafe fn thumbnail(path: &Path) -> Option<ColorImage> {
+    let initialized = CoInitializeEx(None, COINIT_APARTMENTTHREADED).is_ok();
+
+    let image = shell_thumbnail(path);
+
+    if initialized {
+        CoUninitialize();
+    }
+
+    image
+}
+
+unsafe fn shell_thumbnail(path: &Path) -> Option<ColorImage> {
+    let mut path_utf16: Vec<u16> = path.as_os_str().encode_wide().collect();
+    path_utf16.push(0);
+
+    let factory: IShellItemImageFactory =
+        SHCreateItemFromParsingName(PCWSTR::from_raw(path_utf16.as_ptr()), None).ok()?;
+
+    let bitmap = factory
+        .GetImage(
+            SIZE {
+                cx: THUMBNAIL_SIZE,
+                cy: THUMBNAIL_SIZE,
+            },
+            SIIGBF_THUMBNAILONLY | SIIGBF_BIGGERSIZEOK,
+        )
+        .ok()?;
+
+    let image = bitmap_to_image(bitmap, None);
+
+    let _ = DeleteObject(bitmap.into());
+
+    image
+}
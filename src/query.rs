@@ -0,0 +1,659 @@
+use std::ops::RangeInclusive;
+
+// A row of the syntax help popup
+pub struct Syntax {
+    pub syntax: &'static str,
+    pub description: &'static str,
+    pub example: &'static str,
+}
+
+// How the search text itself is matched, the filters are in `FILTERS`
+const MATCHING: &[Syntax] = &[
+    Syntax {
+        syntax: "text",
+        description: "Filenames containing the text, ignoring case",
+        example: "report",
+    },
+    Syntax {
+        syntax: "absolute path",
+        description: "Only that file, if it is in the index",
+        example: r"C:\Windows\notepad.exe",
+    },
+    Syntax {
+        syntax: r"text\text",
+        description: "Full paths containing the text, for searches with a backslash",
+        example: r"steam\steamapps",
+    },
+    Syntax {
+        syntax: "*",
+        description: "Path glob mode: any characters within one folder or filename",
+        example: r"C:\Users\*\Desktop\*.lnk",
+    },
+    Syntax {
+        syntax: "?",
+        description: "Path glob mode: any single character except \\",
+        example: r"C:\Windows\System32\d3d??.dll",
+    },
+    Syntax {
+        syntax: "**",
+        description: "Path glob mode: any number of folders, if recursive globs are enabled",
+        example: r"C:\Users\**\*.pst",
+    },
+    Syntax {
+        syntax: "* ?",
+        description: "Wildcard mode: filenames matching like in Explorer, *. has no extension",
+        example: "*.tx?",
+    },
+];
+
+enum FilterKind {
+    Size,
+    Owner,
+    ShortName,
+}
+
+// A word of the search that filters instead of being searched for. The parser and the syntax
+// help both go through this table so a new filter only has to be added here.
+pub struct Filter {
+    pub prefix: &'static str,
+    kind: FilterKind,
+    // Written without the prefix, forms separated by two spaces each get it
+    pub usages: &'static [Syntax],
+}
+
+pub const FILTERS: &[Filter] = &[
+    Filter {
+        prefix: "size:",
+        kind: FilterKind::Size,
+        usages: &[
+            Syntax {
+                syntax: "n",
+                description:
+                    "Files of exactly that size, units are kb/kib, mb/mib, gb/gib and tb/tib",
+                example: "0",
+            },
+            Syntax {
+                syntax: ">n  >=n  <n  <=n",
+                description: "Files bigger or smaller than a size",
+                example: ">=1.5gb",
+            },
+            Syntax {
+                syntax: "a..b",
+                description: "Files with a size in the range, inclusive",
+                example: "1mb..10mb",
+            },
+        ],
+    },
+    Filter {
+        prefix: "owner:",
+        kind: FilterKind::Owner,
+        usages: &[Syntax {
+            syntax: "name",
+            description: "Files whose owner's account name contains the name",
+            example: "administrators",
+        }],
+    },
+    Filter {
+        prefix: "short:",
+        kind: FilterKind::ShortName,
+        usages: &[Syntax {
+            syntax: "name",
+            description: "Files whose 8.3 short name contains the name, if short names are read",
+            example: "progra~1",
+        }],
+    },
+];
+
+// The syntax, description and example of everything the search box understands
+pub fn syntax_help() -> Vec<(String, &'static str, String)> {
+    let matching = MATCHING.iter().map(|syntax| {
+        (
+            syntax.syntax.to_string(),
+            syntax.description,
+            syntax.example.to_string(),
+        )
+    });
+
+    let filters = FILTERS.iter().flat_map(|filter| {
+        filter.usages.iter().map(|usage| {
+            let forms: Vec<String> = usage
+                .syntax
+                .split("  ")
+                .map(|form| format!("{}{form}", filter.prefix))
+                .collect();
+
+            (
+                forms.join("  "),
+                usage.description,
+                format!("{}{}", filter.prefix, usage.example),
+            )
+        })
+    });
+
+    matching.chain(filters).collect()
+}
+
+// A search split into the filename text and any filters
+pub struct Query {
+    pub text: String,
+    // Inclusive, None if there is no valid size filter
+    pub size: Option<RangeInclusive<u64>>,
+    // Lowercase part of the owner's account name
+    pub owner: Option<String>,
+    // Lowercase part of the 8.3 name
+    pub short_name: Option<String>,
+}
+
+// Filters are separate words, e.g. "report size:>1mb" searches for "report" in bigger files
+pub fn parse(search: &str) -> Query {
+    let mut size = None;
+    let mut owner = None;
+    let mut short_name = None;
+    let mut has_filters = false;
+
+    // Split on single spaces so the text can be put back together exactly
+    let words: Vec<&str> = search
+        .split(' ')
+        .filter(|word| {
+            let Some((filter, value)) = FILTERS.iter().find_map(|filter| {
+                strip_prefix_ignore_case(word, filter.prefix).map(|value| (filter, value))
+            }) else {
+                return true;
+            };
+
+            match filter.kind {
+                // An invalid size leaves the filter off instead of matching nothing
+                FilterKind::Size => {
+                    if let Some(range) = parse_size_filter(value) {
+                        size = Some(range);
+                    }
+                }
+                FilterKind::Owner => {
+                    if !value.is_empty() {
+                        owner = Some(value.to_lowercase());
+                    }
+                }
+                FilterKind::ShortName => {
+                    if !value.is_empty() {
+                        short_name = Some(value.to_lowercase());
+                    }
+                }
+            }
+
+            has_filters = true;
+            false
+        })
+        .collect();
+
+    let text = if has_filters {
+        words.join(" ").trim().to_string()
+    } else {
+        search.to_string()
+    };
+
+    Query {
+        text,
+        size,
+        owner,
+        short_name,
+    }
+}
+
+// Filters can widen a search as they are typed (size:<1 -> size:<10) so it can't be narrowed
+pub fn has_filters(search: &str) -> bool {
+    search.split(' ').any(|word| {
+        FILTERS
+            .iter()
+            .any(|filter| strip_prefix_ignore_case(word, filter.prefix).is_some())
+    })
+}
+
+fn strip_prefix_ignore_case<'a>(word: &'a str, prefix: &str) -> Option<&'a str> {
+    let start = word.get(..prefix.len())?;
+
+    start
+        .eq_ignore_ascii_case(prefix)
+        .then(|| &word[prefix.len()..])
+}
+
+fn parse_size_filter(value: &str) -> Option<RangeInclusive<u64>> {
+    if let Some((min, max)) = value.split_once("..") {
+        return Some(parse_human_size(min)?..=parse_human_size(max)?);
+    }
+
+    if let Some(size) = value.strip_prefix(">=") {
+        Some(parse_human_size(size)?..=u64::MAX)
+    } else if let Some(size) = value.strip_prefix("<=") {
+        Some(0..=parse_human_size(size)?)
+    } else if let Some(size) = value.strip_prefix('>') {
+        Some(parse_human_size(size)?.checked_add(1)?..=u64::MAX)
+    } else if let Some(size) = value.strip_prefix('<') {
+        Some(0..=parse_human_size(size)?.checked_sub(1)?)
+    } else {
+        let size = parse_human_size(value)?;
+        Some(size..=size)
+    }
+}
+
+// Sizes like 500, 500b, 1.5gb or 10KiB. Units are binary, like the sizes shown in the results.
+fn parse_human_size(text: &str) -> Option<u64> {
+    let text = text.trim().to_ascii_lowercase();
+
+    let unit_start = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(unit_start);
+
+    let multiplier: u64 = match unit.trim() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return None,
+    };
+
+    // Whole numbers are parsed separately so big byte counts don't lose precision
+    if let Ok(number) = number.parse::<u64>() {
+        return number.checked_mul(multiplier);
+    }
+
+    let number: f64 = number.parse().ok()?;
+    let size = (number * multiplier as f64).round();
+
+    (size.is_finite() && size >= 0.0 && size <= u64::MAX as f64).then_some(size as u64)
+}
+
+enum GlobToken {
+    Literal(char),
+    // ?
+    AnyChar,
+    // * within one path component
+    Star,
+    // ** across components
+    DoubleStar,
+    // **\ which also matches no directories at all
+    AnyDirectories,
+}
+
+// A glob matched against a whole lowercase path, e.g. c:\users\*\desktop\*.lnk
+pub struct Glob {
+    tokens: Vec<GlobToken>,
+}
+
+impl Glob {
+    // `pattern` is lowercase the same way as the paths. `**` is only special if recursive,
+    // otherwise it is the same as `*`
+    pub fn new(pattern: &str, recursive: bool) -> Self {
+        let pattern = pattern.replace('/', "\\");
+
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '?' => GlobToken::AnyChar,
+                '*' if chars.peek() == Some(&'*') => {
+                    while chars.peek() == Some(&'*') {
+                        chars.next();
+                    }
+
+                    if !recursive {
+                        GlobToken::Star
+                    } else if chars.peek() == Some(&'\\') {
+                        chars.next();
+                        GlobToken::AnyDirectories
+                    } else {
+                        GlobToken::DoubleStar
+                    }
+                }
+                '*' => GlobToken::Star,
+                c => GlobToken::Literal(c),
+            };
+
+            tokens.push(token);
+        }
+
+        Glob { tokens }
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        matches(&self.tokens, path)
+    }
+}
+
+// Steps through the text once, keeping every token the text so far can be matched up to instead
+// of trying each way a star could match, so patterns with many stars don't take exponential time.
+// The greedy two-pointer matcher can't be used since **\ has to end at a \ or match nothing.
+fn matches(tokens: &[GlobToken], text: &str) -> bool {
+    // states[i]: the text so far matches the first i tokens
+    let mut states = vec![false; tokens.len() + 1];
+    let mut next = states.clone();
+    // inside[i]: the text so far matches up to the **\ at i, which has matched some of a folder
+    let mut inside = states.clone();
+    let mut next_inside = states.clone();
+
+    states[0] = true;
+    skip_stars(tokens, &mut states);
+
+    for c in text.chars() {
+        next.fill(false);
+        next_inside.fill(false);
+
+        for (i, token) in tokens.iter().enumerate() {
+            if !states[i] && !inside[i] {
+                continue;
+            }
+
+            match token {
+                GlobToken::Literal(literal) if *literal == c => next[i + 1] = true,
+                GlobToken::AnyChar if c != '\\' => next[i + 1] = true,
+                GlobToken::Star if c != '\\' => next[i] = true,
+                GlobToken::DoubleStar => next[i] = true,
+                GlobToken::AnyDirectories => {
+                    next_inside[i] = true;
+
+                    if c == '\\' {
+                        next[i + 1] = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        skip_stars(tokens, &mut next);
+        std::mem::swap(&mut states, &mut next);
+        std::mem::swap(&mut inside, &mut next_inside);
+
+        if !states.contains(&true) && !inside.contains(&true) {
+            return false;
+        }
+    }
+
+    states[tokens.len()]
+}
+
+// Every kind of star can also match nothing
+fn skip_stars(tokens: &[GlobToken], states: &mut [bool]) {
+    for (i, token) in tokens.iter().enumerate() {
+        if states[i]
+            && matches!(
+                token,
+                GlobToken::Star | GlobToken::DoubleStar | GlobToken::AnyDirectories
+            )
+        {
+            states[i + 1] = true;
+        }
+    }
+}
+
+// The characters FindFirstFile turns a Win32 pattern into before FsRtlIsNameInExpression sees it
+enum WildcardToken {
+    Literal(char),
+    // *
+    Star,
+    // * followed by a ., any characters up to the final . in the name
+    DosStar,
+    // ? which also matches nothing before a . or the end of the name
+    DosQuestionMark,
+    // . followed by ? or * or at the end, matches a . or the end of the name
+    DosDot,
+}
+
+// A filename pattern with the same results as Explorer and dir, including the DOS quirks:
+// *. only matches names without an extension, *.* matches every name and file.? matches file
+pub struct Wildcard {
+    tokens: Vec<WildcardToken>,
+}
+
+impl Wildcard {
+    // `pattern` is lowercase the same way as the filenames
+    pub fn new(pattern: &str) -> Self {
+        let chars: Vec<char> = pattern.chars().collect();
+
+        let tokens = chars
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| {
+                let next = chars.get(i + 1).copied();
+
+                match c {
+                    '?' => WildcardToken::DosQuestionMark,
+                    '*' if next == Some('.') => WildcardToken::DosStar,
+                    '*' => WildcardToken::Star,
+                    '.' if matches!(next, Some('?' | '*') | None) => WildcardToken::DosDot,
+                    c => WildcardToken::Literal(c),
+                }
+            })
+            .collect();
+
+        Wildcard { tokens }
+    }
+
+    pub fn is_match(&self, filename: &str) -> bool {
+        wildcard_matches(&self.tokens, filename)
+    }
+}
+
+pub fn has_wildcards(query: &str) -> bool {
+    query.contains(['*', '?'])
+}
+
+// Like `matches`, every token the text so far can be matched up to is kept instead of trying
+// each way a star could match
+fn wildcard_matches(tokens: &[WildcardToken], text: &str) -> bool {
+    // A * followed by a . can't go past it
+    let final_dot = text.rfind('.');
+
+    // states[i]: the text so far matches the first i tokens
+    let mut states = vec![false; tokens.len() + 1];
+    let mut next = states.clone();
+    states[0] = true;
+
+    let mut chars = text.char_indices().peekable();
+
+    loop {
+        let current = chars.peek().copied();
+
+        // Tokens that can match nothing here, in order so they can be skipped one after another
+        for (i, token) in tokens.iter().enumerate() {
+            let skippable = match token {
+                WildcardToken::Literal(_) => false,
+                WildcardToken::Star | WildcardToken::DosStar => true,
+                WildcardToken::DosQuestionMark => matches!(current, None | Some((_, '.'))),
+                WildcardToken::DosDot => current.is_none(),
+            };
+
+            if states[i] && skippable {
+                states[i + 1] = true;
+            }
+        }
+
+        let Some((index, c)) = current else {
+            return states[tokens.len()];
+        };
+
+        next.fill(false);
+
+        for (i, token) in tokens.iter().enumerate() {
+            if !states[i] {
+                continue;
+            }
+
+            match token {
+                WildcardToken::Literal(literal) if *literal == c => next[i + 1] = true,
+                WildcardToken::Star => next[i] = true,
+                WildcardToken::DosStar if !(c == '.' && Some(index) == final_dot) => next[i] = true,
+                WildcardToken::DosQuestionMark if c != '.' => next[i + 1] = true,
+                WildcardToken::DosDot if c == '.' => next[i + 1] = true,
+                _ => {}
+            }
+        }
+
+        std::mem::swap(&mut states, &mut next);
+        chars.next();
+
+        if !states.contains(&true) {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn human_sizes_in_every_unit() {
+        assert_eq!(parse_human_size("0"), Some(0));
+        assert_eq!(parse_human_size("500"), Some(500));
+        assert_eq!(parse_human_size("500b"), Some(500));
+        assert_eq!(parse_human_size("10k"), Some(10 << 10));
+        assert_eq!(parse_human_size("10KB"), Some(10 << 10));
+        assert_eq!(parse_human_size("10KiB"), Some(10 << 10));
+        assert_eq!(parse_human_size("3mb"), Some(3 << 20));
+        assert_eq!(parse_human_size("2 GiB"), Some(2 << 30));
+        assert_eq!(parse_human_size("1tb"), Some(1 << 40));
+        assert_eq!(parse_human_size("18446744073709551615"), Some(u64::MAX));
+    }
+
+    #[test]
+    fn human_sizes_with_decimals() {
+        assert_eq!(parse_human_size("1.5gb"), Some(3 << 29));
+        assert_eq!(parse_human_size("0.5k"), Some(512));
+        assert_eq!(parse_human_size(".5k"), Some(512));
+        assert_eq!(parse_human_size("1.0001"), Some(1));
+    }
+
+    #[test]
+    fn invalid_human_sizes() {
+        assert_eq!(parse_human_size(""), None);
+        assert_eq!(parse_human_size("kb"), None);
+        assert_eq!(parse_human_size("-1"), None);
+        assert_eq!(parse_human_size("1.2.3"), None);
+        assert_eq!(parse_human_size("10 pb"), None);
+        assert_eq!(parse_human_size("1x"), None);
+        assert_eq!(parse_human_size("20000000tb"), None);
+    }
+
+    #[test]
+    fn size_filter_forms() {
+        assert_eq!(parse("size:0").size, Some(0..=0));
+        assert_eq!(parse("size:>=500k").size, Some(500 << 10..=u64::MAX));
+        assert_eq!(parse("size:>1kb").size, Some(1025..=u64::MAX));
+        assert_eq!(parse("size:<1kb").size, Some(0..=1023));
+        assert_eq!(parse("size:<=1kb").size, Some(0..=1024));
+        assert_eq!(parse("SIZE:1mb..1gb").size, Some(1 << 20..=1 << 30));
+        assert_eq!(parse("size:<0").size, None);
+    }
+
+    #[test]
+    fn invalid_size_leaves_the_filter_off() {
+        let query = parse("report size:big");
+
+        assert_eq!(query.size, None);
+        assert_eq!(query.text, "report");
+    }
+
+    fn wildcard(pattern: &str, filename: &str) -> bool {
+        Wildcard::new(pattern).is_match(filename)
+    }
+
+    #[test]
+    fn star_dot_matches_names_without_an_extension() {
+        assert!(wildcard("*.", "readme"));
+        assert!(wildcard("*.", "makefile"));
+        assert!(!wildcard("*.", "readme.md"));
+        assert!(!wildcard("*.", "archive.tar.gz"));
+    }
+
+    #[test]
+    fn dot_question_mark_allows_no_extension() {
+        assert!(wildcard("file.?", "file.c"));
+        assert!(wildcard("file.?", "file"));
+        assert!(wildcard("file.?", "file."));
+        assert!(!wildcard("file.?", "file.rs"));
+        assert!(!wildcard("file.?", "files"));
+    }
+
+    #[test]
+    fn star_dot_star_matches_everything() {
+        assert!(wildcard("*.*", "readme"));
+        assert!(wildcard("*.*", "readme.md"));
+        assert!(wildcard("*.*", "archive.tar.gz"));
+        assert!(wildcard("*.*", ".gitignore"));
+    }
+
+    #[test]
+    fn star_before_a_dot_stops_at_the_final_dot() {
+        assert!(wildcard("*.gz", "archive.tar.gz"));
+        assert!(wildcard("a*.tar.gz", "archive.tar.gz"));
+        assert!(!wildcard("*.tar", "archive.tar.gz"));
+        assert!(wildcard("*tar*", "archive.tar.gz"));
+    }
+
+    #[test]
+    fn many_wildcard_stars_dont_backtrack_exponentially() {
+        let name = "a".repeat(200);
+
+        assert!(!wildcard("*a*a*a*a*a*a*a*a*a*a*b", &name));
+        assert!(wildcard("*a*a*a*a*a*a*a*a*a*a*", &name));
+    }
+
+    fn glob(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern, true).is_match(path)
+    }
+
+    #[test]
+    fn star_stays_in_one_folder() {
+        assert!(glob(
+            r"c:\users\*\desktop\*.lnk",
+            r"c:\users\me\desktop\app.lnk"
+        ));
+        assert!(!glob(
+            r"c:\users\*\desktop\*.lnk",
+            r"c:\users\me\old\desktop\app.lnk"
+        ));
+        assert!(glob(r"c:\*", r"c:\"));
+        assert!(!glob(r"c:\*", r"c:\windows\notepad.exe"));
+        assert!(glob(r"*.tar.gz", "backup.tar.tar.gz"));
+        assert!(!glob(r"*.txt", "notes.txt.bak"));
+    }
+
+    #[test]
+    fn question_mark_is_one_character_in_a_folder() {
+        assert!(glob("file?.txt", "file1.txt"));
+        assert!(!glob("file?.txt", "file.txt"));
+        assert!(!glob(r"a?b", r"a\b"));
+        assert!(glob("caf?", "café"));
+    }
+
+    #[test]
+    fn double_star_crosses_folders() {
+        assert!(glob(r"c:\**\*.rs", r"c:\src\query\glob.rs"));
+        assert!(glob(r"c:\**\*.rs", r"c:\main.rs"));
+        assert!(glob(r"c:\src**.rs", r"c:\src\query\glob.rs"));
+        assert!(glob(
+            r"c:\**\target\*\*.exe",
+            r"c:\a\target\b\c\target\debug\app.exe"
+        ));
+        assert!(!glob(r"c:\**\*.rs", r"c:\src\main.rs.bak"));
+        // A * after ** still can't cross a folder
+        assert!(!glob(r"c:\**\src\*.rs", r"c:\app\src\query\glob.rs"));
+    }
+
+    #[test]
+    fn double_star_is_a_star_unless_recursive() {
+        assert!(Glob::new(r"c:\**.rs", false).is_match(r"c:\main.rs"));
+        assert!(!Glob::new(r"c:\**.rs", false).is_match(r"c:\src\main.rs"));
+    }
+
+    #[test]
+    fn many_stars_dont_backtrack_exponentially() {
+        let text = "a".repeat(200);
+
+        assert!(!glob("*a*a*a*a*a*a*a*a*a*a*b", &text));
+        assert!(!glob("**a**a**a**a**a**a**a**a**b", &text));
+        assert!(glob("*a*a*a*a*a*a*a*a*a*a*", &text));
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+use log::warn;
+use ntfs_reader::{mft::Mft, volume::Volume};
+
+use crate::{
+    filesystem::{FileSystem, IndexOptions, IndexStats},
+    journal::{self, JournalMessage},
+};
+
+enum RebuildMessage {
+    Phase(&'static str),
+    Done(Box<FileSystem>, IndexStats, Receiver<JournalMessage>),
+    Failed(String),
+}
+
+// Reads the MFT again on a background thread so the index can be refreshed without restarting,
+// the old index keeps being used until the new one is ready
+pub struct Rebuild {
+    pub phase: &'static str,
+    pub start: Instant,
+    rx: Receiver<RebuildMessage>,
+}
+
+impl Rebuild {
+    // `drive` is e.g. C:, `root` is the --root directory to keep
+    pub fn start(
+        ctx: egui::Context,
+        drive: String,
+        options: IndexOptions,
+        root: Option<PathBuf>,
+        poll_interval: Duration,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let start = Instant::now();
+
+            let send = |message| {
+                // The app was closed
+                let _ = tx.send(message);
+                ctx.request_repaint();
+            };
+
+            send(RebuildMessage::Phase("Reading MFT"));
+
+            let start_usn = journal::next_usn(&drive);
+
+            let mft = match Volume::new(format!(r"\\.\{drive}")).and_then(Mft::new) {
+                Ok(mft) => mft,
+                Err(err) => {
+                    send(RebuildMessage::Failed(format!(
+                        "Failed to read the MFT: {err}"
+                    )));
+                    return;
+                }
+            };
+
+            let record_rx = journal::spawn_reader(drive.clone(), start_usn, poll_interval);
+
+            send(RebuildMessage::Phase("Building index"));
+
+            let mut filesystem = FileSystem::from_mft(&mft, format!(r"{drive}\").into(), options);
+            drop(mft);
+
+            if let Some(root) = &root {
+                if !filesystem.retain_subtree(root) {
+                    warn!(
+                        "{} isn't an indexed directory anymore, indexing the whole volume",
+                        root.display()
+                    );
+                }
+            }
+
+            let stats = filesystem.stats(start.elapsed());
+
+            send(RebuildMessage::Done(Box::new(filesystem), stats, record_rx));
+        });
+
+        Rebuild {
+            phase: "Starting",
+            start: Instant::now(),
+            rx,
+        }
+    }
+
+    // The new index, its stats and its journal reader once it has been built
+    pub fn poll(
+        &mut self,
+    ) -> Option<Result<(FileSystem, IndexStats, Receiver<JournalMessage>), String>> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(RebuildMessage::Phase(phase)) => self.phase = phase,
+                Ok(RebuildMessage::Done(filesystem, stats, record_rx)) => {
+                    return Some(Ok((*filesystem, stats, record_rx)))
+                }
+                Ok(RebuildMessage::Failed(err)) => return Some(Err(err)),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    return Some(Err("The rebuild thread stopped unexpectedly".to_string()))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    ops::Range,
+};
+
+use ntfs_reader::{
+    aligned_reader::{open_volume, AlignedReader},
+    api::NtfsAttributeType,
+    file::NtfsFile,
+    mft::Mft,
+    volume::Volume,
+};
+
+// Reads single MFT records straight from the volume.
+//
+// The whole MFT is over a gigabyte on a big drive so it is dropped once the index has been built.
+// This only keeps a handle to the volume and where the MFT is on it, so anything that needs to
+// re-read a record (the inspector, refreshing a size) costs a disk read per record instead.
+pub struct RecordReader {
+    volume: Volume,
+    reader: BufReader<AlignedReader<File>>,
+    // Byte ranges of the volume holding the MFT, in record order
+    runs: Vec<Range<usize>>,
+}
+
+impl RecordReader {
+    pub fn new(volume: Volume) -> io::Result<Self> {
+        let mut reader = open_volume(&volume.path)?;
+
+        let mft_record = Mft::get_record_fs(
+            &mut reader,
+            volume.file_record_size as usize,
+            volume.mft_position,
+        );
+
+        if mft_record.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the $MFT record is corrupt",
+            ));
+        }
+
+        let mut runs = Vec::new();
+
+        NtfsFile::new(0, &mft_record).attributes(|att| {
+            if att.header.type_id == NtfsAttributeType::Data as u32
+                && att.header.is_non_resident != 0
+            {
+                runs = att.get_nonresident_data_runs(&volume).1;
+            }
+        });
+
+        Ok(RecordReader {
+            volume,
+            reader,
+            runs,
+        })
+    }
+
+    // None if the record is past the end of the MFT or isn't a valid file record
+    pub fn read(&mut self, frn: u64) -> Option<Vec<u8>> {
+        let record_size = self.volume.file_record_size as usize;
+
+        let mut offset = frn as usize * record_size;
+
+        for run in &self.runs {
+            if offset < run.len() {
+                let data =
+                    Mft::get_record_fs(&mut self.reader, record_size, (run.start + offset) as u64);
+
+                return (!data.is_empty()).then_some(data);
+            }
+
+            offset -= run.len();
+        }
+
+        None
+    }
+
+    pub fn with_record<T>(&mut self, frn: u64, f: impl FnOnce(&NtfsFile) -> T) -> Option<T> {
+        let data = self.read(frn)?;
+
+        Some(f(&NtfsFile::new(frn, &data)))
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::filesystem::FileSystem;
+
+// On every drive, with a folder per user named after their SID
+const RECYCLE_BIN: &str = "$Recycle.Bin";
+
+// Deleting C:\a.txt moves it to $RXXXXXX.txt and writes its original path and size to
+// $IXXXXXX.txt, which starts with the format version, the size and when it was deleted
+const HEADER_SIZE: usize = 24;
+
+// Version 1 (before Windows 10) has the path in a fixed MAX_PATH buffer, version 2 has its length
+// in characters first
+const V1_PATH_LENGTH: usize = 260;
+
+pub struct RecycledFile {
+    pub name: String,
+    // Folder it was deleted from
+    pub folder: PathBuf,
+    pub size: u64,
+    // NTFS time
+    pub deleted: u64,
+    // The $R file with the contents
+    pub contents: PathBuf,
+}
+
+// Reads the $I file of everything in the Recycle Bin of the indexed drive, only when asked as
+// each one has to be opened
+pub fn scan(filesystem: &FileSystem) -> Vec<RecycledFile> {
+    let mut files: Vec<RecycledFile> = (0..filesystem.filenames.len())
+        .filter(|&position| filesystem.filenames[position].starts_with("$I"))
+        .filter_map(|position| {
+            let folder = filesystem.path(position)?;
+
+            if !folder
+                .components()
+                .any(|component| component.as_os_str().eq_ignore_ascii_case(RECYCLE_BIN))
+            {
+                return None;
+            }
+
+            let filename = &filesystem.filenames[position];
+
+            parse(
+                &fs::read(folder.join(&**filename)).ok()?,
+                folder.join(format!("$R{}", &filename[2..])),
+            )
+        })
+        .collect();
+
+    // Most recently deleted first
+    files.sort_unstable_by(|a, b| b.deleted.cmp(&a.deleted));
+    files
+}
+
+fn parse(info: &[u8], contents: PathBuf) -> Option<RecycledFile> {
+    let field = |offset: usize| {
+        info.get(offset..offset + 8)?
+            .try_into()
+            .ok()
+            .map(u64::from_le_bytes)
+    };
+
+    let version = field(0)?;
+    let size = field(8)?;
+    let deleted = field(16)?;
+
+    let path = match version {
+        1 => info.get(HEADER_SIZE..HEADER_SIZE + V1_PATH_LENGTH * 2)?,
+        2 => {
+            let length: [u8; 4] = info.get(HEADER_SIZE..HEADER_SIZE + 4)?.try_into().ok()?;
+            let length = u32::from_le_bytes(length) as usize;
+
+            info.get(HEADER_SIZE + 4..HEADER_SIZE + 4 + length * 2)?
+        }
+        _ => return None,
+    };
+
+    let path: Vec<u16> = path
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+
+    let path = PathBuf::from(String::from_utf16_lossy(&path));
+
+    Some(RecycledFile {
+        name: path.file_name()?.to_string_lossy().into_owned(),
+        folder: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        size,
+        deleted,
+        contents,
+    })
+}
@@ -0,0 +1,269 @@
+use std::{
+    ffi::{OsStr, OsString},
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+};
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::MAX_PATH,
+        System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_SZ},
+        UI::{
+            Shell::{
+                AssocQueryStringW, SHFileOperationW, SHOpenWithDialog, ShellExecuteW, ASSOCF,
+                ASSOCF_OPEN_BYEXENAME, ASSOCSTR_EXECUTABLE, FOF_ALLOWUNDO, FOF_WANTNUKEWARNING,
+                FO_DELETE, OAIF_ALLOW_REGISTRATION, OAIF_EXEC, OAIF_REGISTER_EXT, OPENASINFO,
+                SE_ERR_NOASSOC, SHFILEOPSTRUCTW,
+            },
+            WindowsAndMessaging::SW_SHOWNORMAL,
+        },
+    },
+};
+
+use crate::config::Terminal;
+
+// Extensions that can be launched and so can be run elevated
+const EXECUTABLE_EXTENSIONS: [&str; 4] = ["exe", "msi", "bat", "cmd"];
+
+fn to_wide(string: &OsStr) -> Vec<u16> {
+    let mut wide: Vec<u16> = string.encode_wide().collect();
+    wide.push(0); // null-terminate
+    wide
+}
+
+fn optional_pcwstr(wide: Option<&Vec<u16>>) -> PCWSTR {
+    wide.map_or_else(PCWSTR::null, |wide| PCWSTR::from_raw(wide.as_ptr()))
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    path.extension().and_then(OsStr::to_str).is_some_and(|ext| {
+        EXECUTABLE_EXTENSIONS
+            .iter()
+            .any(|executable| ext.eq_ignore_ascii_case(executable))
+    })
+}
+
+// Returns the raw ShellExecuteW result which is greater than 32 on success
+unsafe fn execute(
+    verb: &str,
+    file: &OsStr,
+    parameters: Option<&OsStr>,
+    directory: Option<&Path>,
+) -> isize {
+    let verb_utf16 = to_wide(OsStr::new(verb));
+    let file_utf16 = to_wide(file);
+    let parameters_utf16 = parameters.map(to_wide);
+    let directory_utf16 = directory.map(|directory| to_wide(directory.as_os_str()));
+
+    let result = ShellExecuteW(
+        None,
+        PCWSTR::from_raw(verb_utf16.as_ptr()),
+        PCWSTR::from_raw(file_utf16.as_ptr()),
+        optional_pcwstr(parameters_utf16.as_ref()),
+        optional_pcwstr(directory_utf16.as_ref()),
+        SW_SHOWNORMAL,
+    );
+
+    result.0 as isize
+}
+
+// Returns false if the operation failed or was cancelled by the user (e.g. declining the UAC prompt)
+pub unsafe fn shell_execute(verb: &str, path: &Path) -> bool {
+    execute(verb, path.as_os_str(), None, None) > 32
+}
+
+pub unsafe fn open(path: &Path) -> bool {
+    let result = execute("open", path.as_os_str(), None, None);
+
+    // Let the user pick an app for files without a default association
+    if result == SE_ERR_NOASSOC as isize {
+        return open_with_dialog(path);
+    }
+
+    result > 32
+}
+
+// The program and its arguments, the program is quoted if its path has spaces
+fn split_command(command: &str) -> (&str, &str) {
+    let command = command.trim();
+
+    let split = match command.strip_prefix('"') {
+        Some(quoted) => quoted.split_once('"'),
+        None => command.split_once(' '),
+    };
+
+    match split {
+        Some((program, arguments)) => (program, arguments.trim()),
+        None => (command.trim_matches('"'), ""),
+    }
+}
+
+// Runs the reveal command with {path} replaced by the file and {dir} by its folder, or opens
+// Explorer with the file selected if there is no command
+pub unsafe fn reveal(path: &Path, command: &str) -> bool {
+    if command.trim().is_empty() {
+        let parameters = format!("/select,\"{}\"", path.display());
+
+        return execute(
+            "open",
+            OsStr::new("explorer.exe"),
+            Some(OsStr::new(&parameters)),
+            None,
+        ) > 32;
+    }
+
+    let directory = path.parent().unwrap_or(path);
+
+    let (program, arguments) = split_command(command);
+    let arguments = arguments
+        .replace("{path}", &path.to_string_lossy())
+        .replace("{dir}", &directory.to_string_lossy());
+
+    execute(
+        "open",
+        OsStr::new(program),
+        (!arguments.is_empty()).then_some(OsStr::new(&arguments)),
+        Some(directory),
+    ) > 32
+}
+
+// Where a junction or symlink points, std resolves it with GetFinalPathNameByHandleW
+pub fn reparse_target(path: &Path) -> Option<String> {
+    let target = std::fs::canonicalize(path).ok()?;
+    let target = target.to_string_lossy();
+
+    // Shown without the \\?\ prefix, like the paths in the index
+    Some(match target.strip_prefix(r"\\?\UNC\") {
+        Some(share) => format!(r"\\{share}"),
+        None => target.strip_prefix(r"\\?\").unwrap_or(&target).to_string(),
+    })
+}
+
+// Asks for confirmation like Explorer does, returns false if it failed or was cancelled
+pub unsafe fn recycle(path: &Path) -> bool {
+    // pFrom is a list of paths so it has to end with two nulls
+    let mut path_utf16 = to_wide(path.as_os_str());
+    path_utf16.push(0);
+
+    let mut operation = SHFILEOPSTRUCTW {
+        wFunc: FO_DELETE,
+        pFrom: PCWSTR::from_raw(path_utf16.as_ptr()),
+        // Warn if the file is too big for the Recycle Bin and would be deleted permanently
+        fFlags: (FOF_ALLOWUNDO | FOF_WANTNUKEWARNING).0 as u16,
+        ..Default::default()
+    };
+
+    SHFileOperationW(&mut operation) == 0 && !operation.fAnyOperationsAborted.as_bool()
+}
+
+pub unsafe fn run_as_admin(path: &Path) -> bool {
+    shell_execute("runas", path)
+}
+
+// Shows the Windows "Open with" dialog, returns false if it was cancelled
+pub unsafe fn open_with_dialog(path: &Path) -> bool {
+    let path_utf16 = to_wide(path.as_os_str());
+
+    let info = OPENASINFO {
+        pcszFile: PCWSTR::from_raw(path_utf16.as_ptr()),
+        pcszClass: PCWSTR::null(),
+        oaifInFlags: OAIF_ALLOW_REGISTRATION | OAIF_REGISTER_EXT | OAIF_EXEC,
+    };
+
+    SHOpenWithDialog(None, &info).is_ok()
+}
+
+pub unsafe fn open_with(executable: &Path, path: &Path) -> bool {
+    let parameters = format!("\"{}\"", path.display());
+
+    execute(
+        "open",
+        executable.as_os_str(),
+        Some(OsStr::new(&parameters)),
+        None,
+    ) > 32
+}
+
+pub unsafe fn open_terminal(terminal: Terminal, directory: &Path) -> bool {
+    let (program, parameters) = match terminal {
+        // wt ignores the working directory it is launched with so it has to be passed as well,
+        // joining "." stops a trailing backslash (e.g. C:\) from escaping the closing quote
+        Terminal::WindowsTerminal => (
+            "wt.exe",
+            Some(format!("-d \"{}\"", directory.join(".").display())),
+        ),
+        Terminal::PowerShell => ("powershell.exe", None),
+        Terminal::Cmd => ("cmd.exe", None),
+    };
+
+    execute(
+        "open",
+        OsStr::new(program),
+        parameters.as_deref().map(OsStr::new),
+        Some(directory),
+    ) > 32
+}
+
+// The full path of an executable, `association` is e.g. an extension or with
+// ASSOCF_OPEN_BYEXENAME the name of a registered app such as notepad.exe
+unsafe fn associated_executable(flags: ASSOCF, association: &str) -> Option<PathBuf> {
+    let association_utf16 = to_wide(OsStr::new(association));
+
+    let mut length = MAX_PATH;
+    let mut buffer = vec![0u16; length as usize];
+
+    AssocQueryStringW(
+        flags,
+        ASSOCSTR_EXECUTABLE,
+        PCWSTR::from_raw(association_utf16.as_ptr()),
+        PCWSTR::null(),
+        Some(PWSTR::from_raw(buffer.as_mut_ptr())),
+        &mut length,
+    )
+    .ok()
+    .ok()?;
+
+    // the length includes the null terminator
+    buffer.truncate(length.saturating_sub(1) as usize);
+
+    Some(PathBuf::from(OsString::from_wide(&buffer)))
+}
+
+// A string value under HKEY_CURRENT_USER, None if it doesn't exist
+unsafe fn user_registry_string(key: &str, value: &str) -> Option<String> {
+    let key_utf16 = to_wide(OsStr::new(key));
+    let value_utf16 = to_wide(OsStr::new(value));
+
+    let mut buffer = [0u16; MAX_PATH as usize];
+    let mut size = std::mem::size_of_val(&buffer) as u32;
+
+    RegGetValueW(
+        HKEY_CURRENT_USER,
+        PCWSTR::from_raw(key_utf16.as_ptr()),
+        PCWSTR::from_raw(value_utf16.as_ptr()),
+        RRF_RT_REG_SZ,
+        None,
+        Some(buffer.as_mut_ptr().cast()),
+        Some(&mut size),
+    )
+    .ok()
+    .ok()?;
+
+    // the size is in bytes and includes the null terminator
+    let length = (size as usize / 2).saturating_sub(1);
+
+    Some(String::from_utf16_lossy(&buffer[..length]))
+}
+
+// The app last picked in the "Open with" dialog for files with the extension. Windows keeps the
+// picked apps in the extension's OpenWithList, MRUList has their value names most recent first.
+pub unsafe fn last_open_with_app(extension: &str) -> Option<PathBuf> {
+    let key = format!(
+        r"Software\Microsoft\Windows\CurrentVersion\Explorer\FileExts\.{extension}\OpenWithList"
+    );
+
+    let order = user_registry_string(&key, "MRUList")?;
+    let app = user_registry_string(&key, &order.chars().next()?.to_string())?;
+
+    associated_executable(ASSOCF_OPEN_BYEXENAME, &app)
+}
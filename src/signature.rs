@@ -0,0 +1,308 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+};
+
+use eframe::egui;
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use rustc_hash::FxHashMap;
+
+// Every file is opened so the filename search has to narrow things down first
+pub const MAX_FILES: usize = 100_000;
+
+// Enough for every signature in the table
+const HEADER_SIZE: usize = 64;
+
+// Few threads as the disk is the bottleneck and the UI still needs the rest
+const THREADS: usize = 4;
+
+// A file type and the bytes its files have at the given offsets
+struct Signature {
+    name: &'static str,
+    parts: &'static [(usize, &'static [u8])],
+    // Lowercase extensions files of this type have
+    extensions: &'static [&'static str],
+}
+
+// Checked in order, the first match wins. Formats built on ZIP (docx, jar, …) are just ZIP.
+const SIGNATURES: &[Signature] = &[
+    Signature {
+        name: "PNG",
+        parts: &[(0, b"\x89PNG\r\n\x1a\n")],
+        extensions: &["png", "apng"],
+    },
+    Signature {
+        name: "JPEG",
+        parts: &[(0, b"\xff\xd8\xff")],
+        extensions: &["jpg", "jpeg", "jpe", "jfif"],
+    },
+    Signature {
+        name: "GIF",
+        parts: &[(0, b"GIF8")],
+        extensions: &["gif"],
+    },
+    Signature {
+        name: "WebP",
+        parts: &[(0, b"RIFF"), (8, b"WEBP")],
+        extensions: &["webp"],
+    },
+    Signature {
+        name: "WAV",
+        parts: &[(0, b"RIFF"), (8, b"WAVE")],
+        extensions: &["wav"],
+    },
+    Signature {
+        name: "AVI",
+        parts: &[(0, b"RIFF"), (8, b"AVI ")],
+        extensions: &["avi"],
+    },
+    Signature {
+        name: "BMP",
+        parts: &[(0, b"BM")],
+        extensions: &["bmp", "dib"],
+    },
+    Signature {
+        name: "ICO",
+        parts: &[(0, b"\x00\x00\x01\x00")],
+        extensions: &["ico"],
+    },
+    Signature {
+        name: "TIFF",
+        parts: &[(0, b"II*\x00")],
+        extensions: &["tif", "tiff"],
+    },
+    Signature {
+        name: "TIFF",
+        parts: &[(0, b"MM\x00*")],
+        extensions: &["tif", "tiff"],
+    },
+    Signature {
+        name: "PSD",
+        parts: &[(0, b"8BPS")],
+        extensions: &["psd"],
+    },
+    Signature {
+        name: "PDF",
+        parts: &[(0, b"%PDF-")],
+        extensions: &["pdf", "ai"],
+    },
+    Signature {
+        name: "ZIP",
+        parts: &[(0, b"PK\x03\x04")],
+        extensions: &[
+            "zip", "docx", "xlsx", "pptx", "odt", "ods", "odp", "jar", "apk", "epub", "vsix",
+            "nupkg", "whl", "xpi", "appx", "msix", "3mf", "kmz",
+        ],
+    },
+    Signature {
+        name: "RAR",
+        parts: &[(0, b"Rar!\x1a\x07")],
+        extensions: &["rar"],
+    },
+    Signature {
+        name: "7z",
+        parts: &[(0, b"7z\xbc\xaf\x27\x1c")],
+        extensions: &["7z"],
+    },
+    Signature {
+        name: "gzip",
+        parts: &[(0, b"\x1f\x8b")],
+        extensions: &["gz", "tgz"],
+    },
+    Signature {
+        name: "bzip2",
+        parts: &[(0, b"BZh")],
+        extensions: &["bz2", "tbz2"],
+    },
+    Signature {
+        name: "xz",
+        parts: &[(0, b"\xfd7zXZ\x00")],
+        extensions: &["xz", "txz"],
+    },
+    Signature {
+        name: "Zstandard",
+        parts: &[(0, b"\x28\xb5\x2f\xfd")],
+        extensions: &["zst"],
+    },
+    Signature {
+        name: "CAB",
+        parts: &[(0, b"MSCF")],
+        extensions: &["cab", "msu"],
+    },
+    Signature {
+        name: "MP4",
+        parts: &[(4, b"ftyp")],
+        extensions: &["mp4", "m4a", "m4v", "mov", "3gp", "heic", "heif", "avif"],
+    },
+    Signature {
+        name: "Matroska",
+        parts: &[(0, b"\x1a\x45\xdf\xa3")],
+        extensions: &["mkv", "mka", "webm"],
+    },
+    Signature {
+        name: "MP3",
+        parts: &[(0, b"ID3")],
+        extensions: &["mp3"],
+    },
+    Signature {
+        name: "FLAC",
+        parts: &[(0, b"fLaC")],
+        extensions: &["flac"],
+    },
+    Signature {
+        name: "Ogg",
+        parts: &[(0, b"OggS")],
+        extensions: &["ogg", "oga", "ogv", "opus"],
+    },
+    Signature {
+        name: "SQLite",
+        parts: &[(0, b"SQLite format 3\x00")],
+        extensions: &["db", "sqlite", "sqlite3"],
+    },
+    Signature {
+        name: "Executable",
+        parts: &[(0, b"MZ")],
+        extensions: &[
+            "exe", "dll", "sys", "ocx", "cpl", "scr", "drv", "efi", "mui", "com", "ax", "pyd",
+            "node",
+        ],
+    },
+    Signature {
+        name: "ELF",
+        parts: &[(0, b"\x7fELF")],
+        extensions: &["so", "elf", "o"],
+    },
+    Signature {
+        name: "Java class",
+        parts: &[(0, b"\xca\xfe\xba\xbe")],
+        extensions: &["class"],
+    },
+    Signature {
+        name: "WebAssembly",
+        parts: &[(0, b"\x00asm")],
+        extensions: &["wasm"],
+    },
+];
+
+// Detects file types from their first bytes on worker threads, for files with a wrong or missing
+// extension
+pub struct SignatureScan {
+    pub total: usize,
+    scanned: Arc<AtomicUsize>,
+    cancelled: Arc<AtomicBool>,
+    rx: Receiver<(u64, &'static str)>,
+    // Key: FRN
+    detected: FxHashMap<u64, &'static str>,
+}
+
+impl SignatureScan {
+    // `files` are FRNs with their full paths, positions can change while this runs
+    pub fn start(ctx: egui::Context, files: Vec<(u64, PathBuf)>) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        let scanned = Arc::new(AtomicUsize::new(0));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let total = files.len();
+
+        {
+            let scanned = scanned.clone();
+            let cancelled = cancelled.clone();
+
+            std::thread::spawn(move || {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(THREADS)
+                    .build()
+                    .expect("failed to build signature scan thread pool");
+
+                pool.install(|| {
+                    files.par_iter().for_each_with(tx, |tx, (frn, path)| {
+                        if cancelled.load(Ordering::Relaxed) {
+                            return;
+                        }
+
+                        // Unreadable files (no permission, in use, directories) have no type
+                        if let Ok(Some(name)) = detect(path) {
+                            let _ = tx.send((*frn, name));
+                        }
+
+                        if scanned.fetch_add(1, Ordering::Relaxed) % 256 == 0 {
+                            ctx.request_repaint();
+                        }
+                    });
+                });
+
+                // The sender has been dropped so the next poll sees that it finished
+                ctx.request_repaint();
+            });
+        }
+
+        SignatureScan {
+            total,
+            scanned,
+            cancelled,
+            rx,
+            detected: FxHashMap::default(),
+        }
+    }
+
+    pub fn scanned(&self) -> usize {
+        self.scanned.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    // Returns the type of every file with a known signature by FRN once all have been read
+    pub fn poll(&mut self) -> Option<FxHashMap<u64, &'static str>> {
+        loop {
+            match self.rx.try_recv() {
+                Ok((frn, name)) => {
+                    self.detected.insert(frn, name);
+                }
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => return Some(std::mem::take(&mut self.detected)),
+            }
+        }
+    }
+}
+
+// Whether a file detected as `name` has an extension files of that type don't, e.g. an
+// executable named .txt. Files without an extension or with an unknown type never mismatch.
+pub fn is_mismatch(name: &str, extension: Option<&str>) -> bool {
+    let Some(extension) = extension else {
+        return false;
+    };
+
+    // Some types like TIFF have more than one signature
+    let mut signatures = SIGNATURES.iter().filter(|signature| signature.name == name);
+
+    signatures.clone().next().is_some()
+        && !signatures.any(|signature| signature.extensions.contains(&extension))
+}
+
+// The name of the file's type, None if its first bytes don't match any signature
+fn detect(path: &Path) -> io::Result<Option<&'static str>> {
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    File::open(path)?
+        .take(HEADER_SIZE as u64)
+        .read_to_end(&mut header)?;
+
+    Ok(SIGNATURES
+        .iter()
+        .find(|signature| {
+            signature.parts.iter().all(|(offset, magic)| {
+                header
+                    .get(*offset..offset + magic.len())
+                    .is_some_and(|bytes| bytes == *magic)
+            })
+        })
+        .map(|signature| signature.name))
+}